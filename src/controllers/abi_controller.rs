@@ -0,0 +1,115 @@
+use crate::services::ethers::abi_registry_service::{AbiKey, AbiRegistryError, AbiRegistryService, RegisterAbi};
+use actix_web::{web, HttpResponse, Responder, Route};
+use ethers::types::Address;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub struct AbiController;
+
+#[derive(Deserialize)]
+pub struct AbiIdPathParams {
+    id: String,
+}
+
+#[derive(Deserialize)]
+pub struct AbiContractPathParams {
+    chain_id: u64,
+    address: Address,
+}
+
+impl AbiController {
+    pub fn new() -> Self {
+        AbiController
+    }
+
+    pub fn error_response(error: AbiRegistryError) -> HttpResponse {
+        let body = serde_json::json!({ "error": error.to_string() });
+        match error {
+            AbiRegistryError::InvalidAbi(_) => HttpResponse::BadRequest().json(body),
+            AbiRegistryError::NotFound(_) => HttpResponse::NotFound().json(body),
+            AbiRegistryError::RedisError(_) => HttpResponse::InternalServerError().json(body),
+        }
+    }
+
+    async fn get(key: AbiKey, service: &AbiRegistryService) -> HttpResponse {
+        match service.get(&key).await {
+            Ok(Some(abi)) => HttpResponse::Ok().content_type("application/json").body(abi),
+            Ok(None) => Self::error_response(AbiRegistryError::NotFound(key.to_string())),
+            Err(e) => Self::error_response(e),
+        }
+    }
+
+    async fn remove(key: AbiKey, service: &AbiRegistryService) -> HttpResponse {
+        match service.remove(&key).await {
+            Ok(true) => HttpResponse::NoContent().finish(),
+            Ok(false) => Self::error_response(AbiRegistryError::NotFound(key.to_string())),
+            Err(e) => Self::error_response(e),
+        }
+    }
+
+    pub async fn register_abi_ctrl(
+        request: web::Json<RegisterAbi>,
+        service: web::Data<Arc<AbiRegistryService>>,
+    ) -> impl Responder {
+        match service.register(request.into_inner()).await {
+            Ok(()) => HttpResponse::Ok().finish(),
+            Err(e) => Self::error_response(e),
+        }
+    }
+
+    pub async fn list_abis_ctrl(service: web::Data<Arc<AbiRegistryService>>) -> impl Responder {
+        match service.list().await {
+            Ok(keys) => HttpResponse::Ok().json(keys),
+            Err(e) => Self::error_response(e),
+        }
+    }
+
+    pub async fn get_abi_by_id_ctrl(
+        path: web::Path<AbiIdPathParams>,
+        service: web::Data<Arc<AbiRegistryService>>,
+    ) -> impl Responder {
+        Self::get(AbiKey::Id { id: path.into_inner().id }, &service).await
+    }
+
+    pub async fn remove_abi_by_id_ctrl(
+        path: web::Path<AbiIdPathParams>,
+        service: web::Data<Arc<AbiRegistryService>>,
+    ) -> impl Responder {
+        Self::remove(AbiKey::Id { id: path.into_inner().id }, &service).await
+    }
+
+    pub async fn get_abi_by_contract_ctrl(
+        path: web::Path<AbiContractPathParams>,
+        service: web::Data<Arc<AbiRegistryService>>,
+    ) -> impl Responder {
+        let AbiContractPathParams { chain_id, address } = path.into_inner();
+        Self::get(AbiKey::Contract { chain_id, address }, &service).await
+    }
+
+    pub async fn remove_abi_by_contract_ctrl(
+        path: web::Path<AbiContractPathParams>,
+        service: web::Data<Arc<AbiRegistryService>>,
+    ) -> impl Responder {
+        let AbiContractPathParams { chain_id, address } = path.into_inner();
+        Self::remove(AbiKey::Contract { chain_id, address }, &service).await
+    }
+
+    pub fn routes(self) -> HashMap<String, Route> {
+        let mut routes = HashMap::new();
+
+        // Exemplo do corpo: {"id": "uniswap_v2_pair", "abi": "[...]"} ou
+        // {"chain_id": 1, "address": "0x...", "abi": "[...]"}
+        routes.insert(String::from("abis/register"), web::post().to(Self::register_abi_ctrl));
+        routes.insert(String::from("abis"), web::get().to(Self::list_abis_ctrl));
+        routes.insert(String::from("abis/id/{id}"), web::get().to(Self::get_abi_by_id_ctrl));
+        routes.insert(String::from("abis/id/{id}/remove"), web::delete().to(Self::remove_abi_by_id_ctrl));
+        routes.insert(String::from("abis/contract/{chain_id}/{address}"), web::get().to(Self::get_abi_by_contract_ctrl));
+        routes.insert(
+            String::from("abis/contract/{chain_id}/{address}/remove"),
+            web::delete().to(Self::remove_abi_by_contract_ctrl),
+        );
+
+        routes
+    }
+}
@@ -1,3 +1,4 @@
+use crate::services::elastic::get_blocks_service::{BlocksQuery, GetBlocksError, GetBlocksService};
 use crate::services::elastic::get_erc20_contracts_service::GetErc20ContractsService;
 use crate::services::elastic::get_labels_service::GetLabelsService;
 use crate::services::elastic::get_transactions_service::GetTransactionsService;
@@ -87,6 +88,19 @@ impl ElasticController {
         }
     }
 
+    pub async fn get_blocks_ctrl(
+        query: web::Query<BlocksQuery>,
+        service: web::Data<Arc<GetBlocksService>>,
+    ) -> impl Responder {
+        match service.exec(query.into_inner()).await {
+            Ok(blocks) => HttpResponse::Ok().json(&blocks),
+            Err(e @ GetBlocksError::InvalidQuery(_)) => {
+                HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() }))
+            }
+            Err(_) => HttpResponse::InternalServerError().finish(),
+        }
+    }
+
     pub fn routes(self) -> HashMap<String, Route> {
         let mut routes = HashMap::new();
 
@@ -107,6 +121,14 @@ impl ElasticController {
             web::get().to(Self::get_transactions_ctrl),
         );
 
+        // Exemplos da requisição:
+        // http://localhost:8080/elastic/blocks?chain_id=1&number=19000000
+        // http://localhost:8080/elastic/blocks?chain_id=1&from_timestamp=1700000000&to_timestamp=1700003600
+        routes.insert(
+            String::from("elastic/blocks"),
+            web::get().to(Self::get_blocks_ctrl),
+        );
+
         routes
     }
 }
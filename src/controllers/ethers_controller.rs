@@ -1,26 +1,61 @@
-use crate::services::ethers::apply_rpc_service::ApplyRpcService;
+use crate::controllers::abi_controller::AbiController;
+use crate::services::ethers::abi_registry_service::AbiSource;
+use crate::services::ethers::apply_rpc_service::{ApplyRpcError, ApplyRpcService};
 use actix_web::{web, HttpResponse, Responder, Route};
 use serde::Deserialize;
+use serde_json::Value;
+use ethers::types::Address;
 use std::collections::HashMap;
 use std::sync::Arc;
 
-use crate::services::ethers::call_functions_service::CallFunctionsService;
-use crate::services::ethers::get_logs_service::GetLogsService;
-use crate::services::ethers::listen_contract_event_service::ListenContractEventsService;
+use crate::services::ethers::batch_call_service::{BatchCall, BatchCallError, BatchCallService};
+use crate::services::ethers::call_functions_service::{
+    BlockError, CallError, CallFunctionsError, CallFunctionsService, CallOptions, FunctionCall,
+};
+use crate::services::ethers::address_inspection_service::{AddressInspectionError, AddressInspectionService};
+use crate::services::ethers::simulate_service::{SimulateError, SimulateRequest, SimulateService};
+use crate::services::ethers::token_analysis_service::{AnalysisOptions, TokenAnalysisError, TokenAnalysisService};
+use crate::services::ethers::token_service::{TokenError, TokenService};
+use crate::services::ethers::decode_service::{DecodeError, DecodeRequest, DecodeService};
+use crate::services::ethers::get_connection_status_service::GetConnectionStatusService;
+use crate::repositories::backfill_repository::{JobState, LogBackfill};
+use crate::services::ethers::get_logs_service::{BackfillError, GetLogsService};
+use crate::services::ethers::listen_contract_event_service::{
+    ContractEventSubscription, ListenContractEventsError, ListenContractEventsService,
+};
 use crate::services::ethers::listen_deploy_erc20_contracts_service::ListenDeployErc20ContractsService;
+use crate::utils::ethers_utils::DeliveryOptions;
 
 pub struct EthersController;
 
 #[derive(Deserialize)]
-struct ApplyRpcCtrl {
-    endpoint: String,
+pub struct ApplyRpcCtrl {
+    #[serde(default)]
+    endpoint: Option<String>,
+    #[serde(default)]
+    endpoints: Vec<String>,
 }
 
 #[derive(Deserialize)]
 struct CallFunctionsCtrl {
+    #[serde(default)]
     functions_name: Vec<String>,
-    abi:String,
-    address:String
+    #[serde(default)]
+    calls: Vec<FunctionCall>,
+    #[serde(flatten)]
+    abi: AbiSource,
+    address:String,
+    #[serde(flatten)]
+    options: CallOptions,
+}
+
+#[derive(Deserialize)]
+pub struct BatchCallCtrl {
+    calls: Vec<BatchCall>,
+    #[serde(flatten)]
+    abi: AbiSource,
+    chunk_size: Option<usize>,
+    block: Option<Value>,
 }
 
 #[derive(Deserialize)]
@@ -29,31 +64,347 @@ struct GetLogsCtrl {
     to_block: u64
 }
 
+#[derive(Deserialize)]
+pub struct LogsBackfillCtrl {
+    from_block: u64,
+    to_block: u64,
+    #[serde(flatten)]
+    backfill: LogBackfill,
+}
+
 #[derive(Deserialize)]
 struct ListenContractEventsCtrl {
-    address: String,
-    webhook:String,
-    event_signature: String,
+    #[serde(flatten)]
+    subscription: ContractEventSubscription,
+    #[serde(flatten)]
+    delivery: DeliveryOptions,
 }
 
 #[derive(Deserialize)]
 struct ListenDeployErc20ContractsCtrl {
-    webhook:String
+    webhook:String,
+    #[serde(flatten)]
+    delivery: DeliveryOptions,
+    #[serde(default)]
+    analysis: Option<AnalysisOptions>,
+}
+
+
+#[derive(Deserialize)]
+pub struct TokenBlockCtrl {
+    block: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct TokenAnalysisCtrl {
+    router: Option<String>,
+    amount_in: Option<String>,
+    block: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct Erc1155BalancesCtrl {
+    accounts: Vec<Address>,
+    ids: Vec<Value>,
+    block: Option<Value>,
+}
+
+#[derive(Deserialize)]
+pub struct TokenInterfacesCtrl {
+    #[serde(default)]
+    interfaces: Option<String>,
+    block: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct TokenPathParams {
+    id: i32,
+    chain_id: u64,
+    address: String,
 }
 
+#[derive(Deserialize)]
+pub struct Erc721PathParams {
+    id: i32,
+    chain_id: u64,
+    address: String,
+    token_id: String,
+}
 
 #[derive(Deserialize)]
-struct PathParams {
+pub struct BackfillPathParams {
+    id: i32,
+    job_id: u64,
+}
+
+#[derive(Deserialize)]
+pub struct PathParams {
     id: i32
 }
 
+#[derive(Deserialize)]
+pub struct ChainPathParams {
+    id: i32,
+    chain_id: u64
+}
+
+impl ApplyRpcCtrl {
+    fn endpoints(&self) -> Vec<String> {
+        let mut endpoints = self.endpoints.clone();
+        if let Some(endpoint) = self.endpoint.clone() {
+            endpoints.insert(0, endpoint);
+        }
+        endpoints
+    }
+}
+
 impl EthersController {
     pub fn new() -> Self {
         EthersController {}
     }
 
+    fn apply_rpc_error_response(error: ApplyRpcError) -> HttpResponse {
+        let body = serde_json::json!({ "error": error.to_string() });
+
+        match error {
+            ApplyRpcError::NoEndpoints => HttpResponse::BadRequest().json(body),
+            ApplyRpcError::NotFound(..) => HttpResponse::NotFound().json(body),
+            ApplyRpcError::ChainMismatch { .. } => HttpResponse::Conflict().json(body),
+            _ => HttpResponse::BadGateway().json(body),
+        }
+    }
+
+    pub async fn decode_ctrl(
+        request: web::Json<DecodeRequest>,
+        service: web::Data<Arc<DecodeService>>,
+    ) -> impl Responder {
+        match service.exec(request.into_inner()).await {
+            Ok(decoded) => HttpResponse::Ok().json(decoded),
+            Err(e) => {
+                let body = serde_json::json!({ "error": e.to_string() });
+                match e {
+                    DecodeError::AbiError(e) => AbiController::error_response(e),
+                    DecodeError::MissingInput => HttpResponse::BadRequest().json(body),
+                    DecodeError::DecodeFailed(_) => HttpResponse::UnprocessableEntity().json(body),
+                }
+            }
+        }
+    }
+
+    pub async fn inspect_address_ctrl(
+        path: web::Path<TokenPathParams>,
+        query: web::Query<TokenBlockCtrl>,
+        service: web::Data<Arc<AddressInspectionService>>,
+    ) -> impl Responder {
+        let path = path.into_inner();
+        let block = query.into_inner().block.map(Value::String);
+
+        match service.exec(path.id, path.chain_id, path.address, block).await {
+            Ok(inspection) => HttpResponse::Ok().json(inspection),
+            Err(e) => {
+                let body = serde_json::json!({ "error": e.to_string() });
+                match e {
+                    AddressInspectionError::NotFound(_, _) => HttpResponse::NotFound().json(body),
+                    AddressInspectionError::InvalidAddress(_, _) => HttpResponse::BadRequest().json(body),
+                    AddressInspectionError::BlockError(e) => Self::block_error_response(e, body),
+                    AddressInspectionError::RpcError(_) => HttpResponse::BadGateway().json(body),
+                }
+            }
+        }
+    }
+
+    pub async fn simulate_ctrl(
+        path: web::Path<ChainPathParams>,
+        request: web::Json<SimulateRequest>,
+        service: web::Data<Arc<SimulateService>>,
+    ) -> impl Responder {
+        let path = path.into_inner();
+
+        match service.exec(path.id, path.chain_id, request.into_inner()).await {
+            Ok(simulation) => HttpResponse::Ok().json(simulation),
+            Err(e) => {
+                let body = serde_json::json!({ "error": e.to_string() });
+                match e {
+                    SimulateError::NotFound(_, _) => HttpResponse::NotFound().json(body),
+                    SimulateError::InvalidArgument(_) => HttpResponse::BadRequest().json(body),
+                    SimulateError::AbiError(e) => AbiController::error_response(e),
+                    SimulateError::BlockError(e) => Self::block_error_response(e, body),
+                }
+            }
+        }
+    }
+
+    fn token_error_response(error: TokenError) -> HttpResponse {
+        let body = serde_json::json!({ "error": error.to_string() });
+        match error {
+            TokenError::NotFound(_, _) => HttpResponse::NotFound().json(body),
+            TokenError::InvalidArgument(_) => HttpResponse::BadRequest().json(body),
+            TokenError::BlockError(e) => Self::block_error_response(e, body),
+            TokenError::CallError(CallError::Rpc { .. }) => HttpResponse::BadGateway().json(body),
+            TokenError::CallError(e) => HttpResponse::UnprocessableEntity().json(serde_json::json!({ "error": e })),
+        }
+    }
+
+    pub async fn token_erc20_ctrl(
+        path: web::Path<TokenPathParams>,
+        query: web::Query<TokenBlockCtrl>,
+        service: web::Data<Arc<TokenService>>,
+    ) -> impl Responder {
+        let path = path.into_inner();
+        let block = query.into_inner().block.map(Value::String);
+
+        let result = match TokenService::parse_address(&path.address) {
+            Ok(address) => service.erc20_metadata(path.id, path.chain_id, address, block).await,
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(metadata) => HttpResponse::Ok().json(metadata),
+            Err(e) => Self::token_error_response(e),
+        }
+    }
+
+    pub async fn token_erc721_ctrl(
+        path: web::Path<Erc721PathParams>,
+        query: web::Query<TokenBlockCtrl>,
+        service: web::Data<Arc<TokenService>>,
+    ) -> impl Responder {
+        let path = path.into_inner();
+        let block = query.into_inner().block.map(Value::String);
+
+        let result = match (
+            TokenService::parse_address(&path.address),
+            TokenService::parse_token_id(&Value::String(path.token_id)),
+        ) {
+            (Ok(address), Ok(token_id)) => service.erc721_token(path.id, path.chain_id, address, token_id, block).await,
+            (Err(e), _) | (_, Err(e)) => Err(e),
+        };
+
+        match result {
+            Ok(token) => HttpResponse::Ok().json(token),
+            Err(e) => Self::token_error_response(e),
+        }
+    }
+
+    pub async fn token_erc1155_balances_ctrl(
+        path: web::Path<TokenPathParams>,
+        request: web::Json<Erc1155BalancesCtrl>,
+        service: web::Data<Arc<TokenService>>,
+    ) -> impl Responder {
+        let path = path.into_inner();
+        let request = request.into_inner();
+
+        let ids = request.ids.iter().map(TokenService::parse_token_id).collect::<Result<Vec<_>, _>>();
+        let result = match (TokenService::parse_address(&path.address), ids) {
+            (Ok(address), Ok(ids)) => {
+                service
+                    .erc1155_balances(path.id, path.chain_id, address, request.accounts, ids, request.block)
+                    .await
+            }
+            (Err(e), _) | (_, Err(e)) => Err(e),
+        };
+
+        match result {
+            Ok(balances) => HttpResponse::Ok().json(balances),
+            Err(e) => Self::token_error_response(e),
+        }
+    }
+
+    pub async fn token_interfaces_ctrl(
+        path: web::Path<TokenPathParams>,
+        query: web::Query<TokenInterfacesCtrl>,
+        service: web::Data<Arc<TokenService>>,
+    ) -> impl Responder {
+        let path = path.into_inner();
+        let query = query.into_inner();
+        let interfaces = query
+            .interfaces
+            .map(|interfaces| {
+                interfaces
+                    .split(',')
+                    .map(|interface| interface.trim().to_string())
+                    .filter(|interface| !interface.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let result = match TokenService::parse_address(&path.address) {
+            Ok(address) => {
+                service
+                    .interfaces(path.id, path.chain_id, address, interfaces, query.block.map(Value::String))
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(support) => HttpResponse::Ok().json(support),
+            Err(e) => Self::token_error_response(e),
+        }
+    }
+
+    pub async fn token_analysis_ctrl(
+        path: web::Path<TokenPathParams>,
+        query: web::Query<TokenAnalysisCtrl>,
+        service: web::Data<Arc<TokenAnalysisService>>,
+    ) -> impl Responder {
+        let path = path.into_inner();
+        let query = query.into_inner();
+
+        let router = match query.router.as_deref().map(TokenService::parse_address).transpose() {
+            Ok(router) => router,
+            Err(e) => return Self::token_error_response(e),
+        };
+        let token = match TokenService::parse_address(&path.address) {
+            Ok(token) => token,
+            Err(e) => return Self::token_error_response(e),
+        };
+        let options = AnalysisOptions {
+            router,
+            amount_in: query.amount_in.map(Value::String),
+            block: query.block.map(Value::String),
+        };
+
+        match service.exec(path.id, path.chain_id, token, options).await {
+            Ok(analysis) => HttpResponse::Ok().json(analysis),
+            Err(e) => {
+                let body = serde_json::json!({ "error": e.to_string() });
+                match e {
+                    TokenAnalysisError::NotFound(_, _) => HttpResponse::NotFound().json(body),
+                    TokenAnalysisError::InvalidArgument(_) | TokenAnalysisError::UnknownRouter(_) => {
+                        HttpResponse::BadRequest().json(body)
+                    }
+                    TokenAnalysisError::NoMulticall3(_) => HttpResponse::UnprocessableEntity().json(body),
+                    TokenAnalysisError::BlockError(e) => Self::block_error_response(e, body),
+                    TokenAnalysisError::CallError(CallError::Rpc { .. }) => HttpResponse::BadGateway().json(body),
+                    TokenAnalysisError::CallError(e) => {
+                        HttpResponse::UnprocessableEntity().json(serde_json::json!({ "error": e }))
+                    }
+                }
+            }
+        }
+    }
+
+    fn block_error_response(error: BlockError, body: Value) -> HttpResponse {
+        match error {
+            BlockError::InvalidBlock(_) => HttpResponse::BadRequest().json(body),
+            BlockError::NotFound(_) => HttpResponse::NotFound().json(body),
+            BlockError::RpcError(_) => HttpResponse::BadGateway().json(body),
+        }
+    }
+
+    fn backfill_error_response(error: BackfillError) -> HttpResponse {
+        let body = serde_json::json!({ "error": error.to_string() });
+        match error {
+            BackfillError::NotFound(_, _) | BackfillError::JobNotFound(_) => HttpResponse::NotFound().json(body),
+            BackfillError::InvalidRange(_, _) | BackfillError::InvalidFilter(_) => HttpResponse::BadRequest().json(body),
+            BackfillError::JobFinished(_, _) | BackfillError::JobNotRunning(_) => HttpResponse::Conflict().json(body),
+            BackfillError::RedisError(_) => HttpResponse::InternalServerError().json(body),
+        }
+    }
+
     pub async fn get_logs_ctrl(
-        path: web::Path<PathParams>,
+        path: web::Path<ChainPathParams>,
         request: web::Json<GetLogsCtrl>,
         service: web::Data<Arc<GetLogsService>>,
     ) -> impl Responder {
@@ -62,48 +413,151 @@ impl EthersController {
         let from_block = request.from_block;
         let to_block = request.to_block;
 
-        service.exec(user_id,from_block,to_block).await;
-        HttpResponse::Ok()
+        match service.exec(user_id,path.chain_id,from_block,to_block).await {
+            Ok(job) => HttpResponse::Accepted().json(job),
+            Err(e) => Self::backfill_error_response(e),
+        }
     }
 
-    pub async fn listen_contract_events_ctrl(
+    pub async fn logs_backfill_ctrl(
+        path: web::Path<ChainPathParams>,
+        request: web::Json<LogsBackfillCtrl>,
+        service: web::Data<Arc<GetLogsService>>,
+    ) -> impl Responder {
+        let LogsBackfillCtrl { from_block, to_block, backfill } = request.into_inner();
+
+        match service.exec_logs(path.id, path.chain_id, from_block, to_block, backfill).await {
+            Ok(job) => HttpResponse::Accepted().json(job),
+            Err(e) => Self::backfill_error_response(e),
+        }
+    }
+
+    pub async fn backfills_ctrl(
         path: web::Path<PathParams>,
+        service: web::Data<Arc<GetLogsService>>,
+    ) -> impl Responder {
+        match service.list(path.id).await {
+            Ok(jobs) => HttpResponse::Ok().json(jobs),
+            Err(e) => Self::backfill_error_response(e),
+        }
+    }
+
+    pub async fn backfill_status_ctrl(
+        path: web::Path<BackfillPathParams>,
+        service: web::Data<Arc<GetLogsService>>,
+    ) -> impl Responder {
+        match service.status(path.id, path.job_id).await {
+            Ok(job) => HttpResponse::Ok().json(job),
+            Err(e) => Self::backfill_error_response(e),
+        }
+    }
+
+    async fn control_backfill(path: BackfillPathParams, service: &GetLogsService, state: JobState) -> HttpResponse {
+        match service.control(path.id, path.job_id, state).await {
+            Ok(job) => HttpResponse::Ok().json(job),
+            Err(e) => Self::backfill_error_response(e),
+        }
+    }
+
+    pub async fn pause_backfill_ctrl(
+        path: web::Path<BackfillPathParams>,
+        service: web::Data<Arc<GetLogsService>>,
+    ) -> impl Responder {
+        Self::control_backfill(path.into_inner(), &service, JobState::Paused).await
+    }
+
+    pub async fn resume_backfill_ctrl(
+        path: web::Path<BackfillPathParams>,
+        service: web::Data<Arc<GetLogsService>>,
+    ) -> impl Responder {
+        Self::control_backfill(path.into_inner(), &service, JobState::Running).await
+    }
+
+    pub async fn cancel_backfill_ctrl(
+        path: web::Path<BackfillPathParams>,
+        service: web::Data<Arc<GetLogsService>>,
+    ) -> impl Responder {
+        Self::control_backfill(path.into_inner(), &service, JobState::Cancelled).await
+    }
+
+    pub async fn listen_contract_events_ctrl(
+        path: web::Path<ChainPathParams>,
         request: web::Json<ListenContractEventsCtrl>,
         service: web::Data<Arc<ListenContractEventsService>>,
     ) -> impl Responder {
         let user_id = path.id;
-        let contract_address = request.address.clone();
-        let event_signature = request.event_signature.clone();
-        let webhook = request.webhook.clone();
+        let request = request.into_inner();
 
-        service.exec(user_id,contract_address,event_signature,webhook).await;
-        HttpResponse::Ok()
+        match service.exec(user_id,path.chain_id,request.subscription,request.delivery).await {
+            Ok(()) => HttpResponse::Ok().finish(),
+            Err(ListenContractEventsError::AbiError(e)) => AbiController::error_response(e),
+            Err(e) => HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() })),
+        }
     }
 
     pub async fn call_functions_ctrl(
-        path: web::Path<PathParams>,
+        path: web::Path<ChainPathParams>,
         request: web::Json<CallFunctionsCtrl>,
         service: web::Data<Arc<CallFunctionsService>>
     ) -> impl Responder {
-        let id = path.id.clone();
-        let functions_name = request.functions_name.clone();
+        let id = path.id;
+        let chain_id = path.chain_id;
+        let calls = request
+            .functions_name
+            .iter()
+            .cloned()
+            .map(FunctionCall::new)
+            .chain(request.calls.iter().cloned())
+            .collect();
         let abi = request.abi.clone();
         let address = request.address.clone();
+        let options = request.options.clone();
 
-        let service_response = service.exec(id, address,functions_name,abi).await;
-        // let service_response = web::Json(service_response);
-        HttpResponse::Ok().json(service_response)
+        match service.exec(id, chain_id, address,calls,abi,options).await {
+            Ok(service_response) => HttpResponse::Ok().json(service_response),
+            Err(e) => {
+                let body = serde_json::json!({ "error": e.to_string() });
+                match e {
+                    CallFunctionsError::NotFound(_, _) => HttpResponse::NotFound().json(body),
+                    CallFunctionsError::InvalidAddress(_, _) => HttpResponse::BadRequest().json(body),
+                    CallFunctionsError::InvalidQuorum(_, _) => HttpResponse::BadRequest().json(body),
+                    CallFunctionsError::AbiError(e) => AbiController::error_response(e),
+                    CallFunctionsError::BlockError(e) => Self::block_error_response(e, body),
+                }
+            }
+        }
+    }
+
+    pub async fn batch_call_ctrl(
+        path: web::Path<ChainPathParams>,
+        request: web::Json<BatchCallCtrl>,
+        service: web::Data<Arc<BatchCallService>>
+    ) -> impl Responder {
+        let request = request.into_inner();
+
+        match service.exec(path.id, path.chain_id, request.calls, request.abi, request.chunk_size, request.block).await {
+            Ok(response) => HttpResponse::Ok().json(response),
+            Err(e) => {
+                let body = serde_json::json!({ "error": e.to_string() });
+                match e {
+                    BatchCallError::NotFound(..) => HttpResponse::NotFound().json(body),
+                    BatchCallError::InvalidAbi(_) => HttpResponse::BadRequest().json(body),
+                    BatchCallError::BlockError(e) => Self::block_error_response(e, body),
+                }
+            }
+        }
     }
 
     pub async fn listen_deploy_erc20_contracts_ctrl(
-        path: web::Path<PathParams>,
+        path: web::Path<ChainPathParams>,
         request: web::Json<ListenDeployErc20ContractsCtrl>,
         service: web::Data<Arc<ListenDeployErc20ContractsService>>
     ) -> impl Responder {
-        let id = path.id.clone();
+        let id = path.id;
+        let chain_id = path.chain_id;
         let webhook = request.webhook.clone();
 
-        service.exec(id,webhook).await;
+        service.exec(id,chain_id,webhook,request.delivery,request.analysis.clone()).await;
         HttpResponse::Ok()
     }
 
@@ -112,21 +566,83 @@ impl EthersController {
         request: web::Json<ApplyRpcCtrl>,
         service: web::Data<Arc<ApplyRpcService>>,
     ) -> impl Responder {
-        let endpoint = request.endpoint.clone();
+        let endpoints = request.endpoints();
         let user_id = path.id;
 
-        service.exec(user_id, endpoint).await;
-        HttpResponse::Ok()
+        match service.exec(user_id, endpoints).await {
+            Ok(chain_id) => HttpResponse::Ok().json(serde_json::json!({ "chain_id": chain_id })),
+            Err(e) => Self::apply_rpc_error_response(e),
+        }
+    }
+
+    pub async fn replace_rpc_ctrl(
+        path: web::Path<ChainPathParams>,
+        request: web::Json<ApplyRpcCtrl>,
+        service: web::Data<Arc<ApplyRpcService>>,
+    ) -> impl Responder {
+        let endpoints = request.endpoints();
+
+        match service.replace(path.id, path.chain_id, endpoints).await {
+            Ok(_) => HttpResponse::Ok().finish(),
+            Err(e) => Self::apply_rpc_error_response(e),
+        }
+    }
+
+    pub async fn remove_rpc_ctrl(
+        path: web::Path<ChainPathParams>,
+        service: web::Data<Arc<ApplyRpcService>>,
+    ) -> impl Responder {
+        match service.remove(path.id, path.chain_id).await {
+            Ok(_) => HttpResponse::Ok().finish(),
+            Err(e) => Self::apply_rpc_error_response(e),
+        }
+    }
+
+    pub async fn connection_status_ctrl(
+        path: web::Path<ChainPathParams>,
+        service: web::Data<Arc<GetConnectionStatusService>>,
+    ) -> impl Responder {
+        match service.exec(path.id, path.chain_id).await {
+            Some(status) => HttpResponse::Ok().json(status),
+            None => HttpResponse::NotFound().json(serde_json::json!({ "error": "NOT_FOUND" })),
+        }
+    }
+
+    pub async fn connections_ctrl(
+        path: web::Path<PathParams>,
+        service: web::Data<Arc<GetConnectionStatusService>>,
+    ) -> impl Responder {
+        HttpResponse::Ok().json(service.exec_by_user(path.id).await)
     }
 
     pub fn routes(self) -> HashMap<String, Route> {
         let mut routes = HashMap::new();
 
+        routes.insert(String::from("ethers/decode"), web::post().to(Self::decode_ctrl));
         routes.insert(String::from("ethers/{id}/apply_rpc"), web::post().to(Self::apply_rpc_ctrl));
-        routes.insert(String::from("ethers/{id}/call_functions"), web::post().to(Self::call_functions_ctrl));
-        routes.insert(String::from("ethers/{id}/get_logs"), web::post().to(Self::get_logs_ctrl));
-        routes.insert(String::from("ethers/{id}/listen_deploy_erc20"), web::post().to(Self::listen_deploy_erc20_contracts_ctrl));
-        routes.insert(String::from("ethers/{id}/listen_contract_events"), web::post().to(Self::listen_contract_events_ctrl));
+        routes.insert(String::from("ethers/{id}/connections"), web::get().to(Self::connections_ctrl));
+        routes.insert(String::from("ethers/{id}/{chain_id}/connection_status"), web::get().to(Self::connection_status_ctrl));
+        routes.insert(String::from("ethers/{id}/{chain_id}/replace_rpc"), web::put().to(Self::replace_rpc_ctrl));
+        routes.insert(String::from("ethers/{id}/{chain_id}/remove_rpc"), web::delete().to(Self::remove_rpc_ctrl));
+        routes.insert(String::from("ethers/{id}/{chain_id}/call_functions"), web::post().to(Self::call_functions_ctrl));
+        routes.insert(String::from("ethers/{id}/{chain_id}/batch_call"), web::post().to(Self::batch_call_ctrl));
+        routes.insert(String::from("ethers/{id}/{chain_id}/addresses/{address}/inspect"), web::get().to(Self::inspect_address_ctrl));
+        routes.insert(String::from("ethers/{id}/{chain_id}/simulate"), web::post().to(Self::simulate_ctrl));
+        routes.insert(String::from("ethers/{id}/{chain_id}/tokens/{address}/erc20"), web::get().to(Self::token_erc20_ctrl));
+        routes.insert(String::from("ethers/{id}/{chain_id}/tokens/{address}/erc721/{token_id}"), web::get().to(Self::token_erc721_ctrl));
+        routes.insert(String::from("ethers/{id}/{chain_id}/tokens/{address}/erc1155/balances"), web::post().to(Self::token_erc1155_balances_ctrl));
+        routes.insert(String::from("ethers/{id}/{chain_id}/tokens/{address}/analysis"), web::get().to(Self::token_analysis_ctrl));
+        // Exemplo: ?interfaces=erc721,erc721_metadata,0x2a55205a
+        routes.insert(String::from("ethers/{id}/{chain_id}/tokens/{address}/interfaces"), web::get().to(Self::token_interfaces_ctrl));
+        routes.insert(String::from("ethers/{id}/{chain_id}/get_logs"), web::post().to(Self::get_logs_ctrl));
+        routes.insert(String::from("ethers/{id}/{chain_id}/logs_backfill"), web::post().to(Self::logs_backfill_ctrl));
+        routes.insert(String::from("ethers/{id}/backfills"), web::get().to(Self::backfills_ctrl));
+        routes.insert(String::from("ethers/{id}/backfills/{job_id}"), web::get().to(Self::backfill_status_ctrl));
+        routes.insert(String::from("ethers/{id}/backfills/{job_id}/pause"), web::post().to(Self::pause_backfill_ctrl));
+        routes.insert(String::from("ethers/{id}/backfills/{job_id}/resume"), web::post().to(Self::resume_backfill_ctrl));
+        routes.insert(String::from("ethers/{id}/backfills/{job_id}/cancel"), web::post().to(Self::cancel_backfill_ctrl));
+        routes.insert(String::from("ethers/{id}/{chain_id}/listen_deploy_erc20"), web::post().to(Self::listen_deploy_erc20_contracts_ctrl));
+        routes.insert(String::from("ethers/{id}/{chain_id}/listen_contract_events"), web::post().to(Self::listen_contract_events_ctrl));
 
         routes
     }
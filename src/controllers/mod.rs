@@ -1,2 +1,3 @@
+pub mod abi_controller;
 pub mod ethers_controller;
-pub  mod elastic_controller;
\ No newline at end of file
+pub  mod elastic_controller;
@@ -1,30 +1,42 @@
 mod controllers;
 pub mod http_client;
 mod repositories;
+pub mod rpc_transport;
 mod services;
 mod utils;
 use crate::controllers::ethers_controller::EthersController;
 use crate::repositories::elastic_repository::ElasticRepository;
 use crate::repositories::ethers_repository::EthersRepository;
+use crate::repositories::backfill_repository::BackfillRepository;
 use crate::repositories::redis_repository::RedisRepository;
 use crate::services::ethers::apply_rpc_service::ApplyRpcService;
 
 use dotenv::dotenv;
 use services::elastic::{
-    get_labels_service::GetLabelsService, get_transactions_service::GetTransactionsService,
+    get_blocks_service::GetBlocksService, get_labels_service::GetLabelsService,
+    get_transactions_service::GetTransactionsService,
 };
 use std::env;
 use std::sync::Arc;
 
+use crate::controllers::abi_controller::AbiController;
 use crate::controllers::elastic_controller::ElasticController;
 use crate::services::elastic::get_erc20_contracts_service::GetErc20ContractsService;
+use crate::services::ethers::abi_registry_service::AbiRegistryService;
+use crate::services::ethers::batch_call_service::BatchCallService;
 use crate::services::ethers::call_functions_service::CallFunctionsService;
+use crate::services::ethers::decode_service::DecodeService;
+use crate::services::ethers::get_connection_status_service::GetConnectionStatusService;
 use crate::services::ethers::get_logs_service::GetLogsService;
 use crate::services::ethers::listen_contract_event_service::ListenContractEventsService;
 use crate::services::ethers::listen_deploy_erc20_contracts_service::ListenDeployErc20ContractsService;
+use crate::services::ethers::rollback_reorg_service::RollbackReorgService;
+use crate::services::ethers::address_inspection_service::AddressInspectionService;
+use crate::services::ethers::simulate_service::SimulateService;
+use crate::services::ethers::token_analysis_service::TokenAnalysisService;
+use crate::services::ethers::token_service::TokenService;
 use actix_web::{middleware::Logger, web, App, HttpServer};
 use http_client::HttpClient;
-use redis::{Commands, FromRedisValue};
 use tokio::sync::RwLock;
 
 #[actix_web::main]
@@ -46,40 +58,122 @@ async fn main() {
     ));
     let ethers_repository = Arc::new(RwLock::new(EthersRepository::new()));
 
+    let backfill_repository = Arc::new(BackfillRepository::new(redis_repository.clone()));
+
+    let rollback_reorg_service = Arc::new(RollbackReorgService::new(
+        ethers_repository.clone(),
+        elastic_repository.clone(),
+        backfill_repository.clone(),
+    ));
+
     let apply_rpc_service = Arc::new(ApplyRpcService::new(
         ethers_repository.clone(),
         redis_repository.clone(),
+        rollback_reorg_service,
     ));
 
-    let listen_contract_events_service =
-        Arc::new(ListenContractEventsService::new(ethers_repository.clone()));
+    match apply_rpc_service.restore_connections().await {
+        Ok(report) => {
+            log::info!("{} conexões restauradas do Redis", report.restored.len());
+            for pending in &report.pending {
+                log::warn!(
+                    "Conexão {} ({:?}) indisponível, reconectando em segundo plano: {}",
+                    pending.key,
+                    pending.endpoints,
+                    pending.error
+                );
+            }
+            for failure in &report.failed {
+                log::warn!(
+                    "Falha ao restaurar conexão {} ({:?}): {}",
+                    failure.key,
+                    failure.endpoints,
+                    failure.error
+                );
+            }
+        }
+        Err(e) => log::error!("Falha ao ler conexões do Redis: {}", e),
+    }
+
+    let abi_registry_service = Arc::new(AbiRegistryService::new(redis_repository.clone()));
+
+    if let Err(e) = abi_registry_service.seed().await {
+        log::error!("Falha ao registrar as ABIs embutidas no Redis: {}", e);
+    }
+
+    let listen_contract_events_service = Arc::new(ListenContractEventsService::new(
+        ethers_repository.clone(),
+        abi_registry_service.clone(),
+    ));
 
     let get_erc20_contracts = Arc::new(GetErc20ContractsService::new(elastic_repository.clone()));
     let get_labels_service = Arc::new(GetLabelsService::new(elastic_repository.clone()));
 
     let get_transactions_service =
         Arc::new(GetTransactionsService::new(elastic_repository.clone()));
+    let get_blocks_service = Arc::new(GetBlocksService::new(elastic_repository.clone()));
 
     let get_logs_service = Arc::new(GetLogsService::new(
         ethers_repository.clone(),
         elastic_repository.clone(),
+        backfill_repository.clone(),
+        HttpClient::new(),
     ));
 
+    match get_logs_service.resume_jobs().await {
+        Ok(resumed) => log::info!("{} jobs de backfill retomados do Redis", resumed),
+        Err(e) => log::error!("Falha ao retomar os jobs de backfill: {}", e),
+    }
+
+    let token_analysis_service = Arc::new(TokenAnalysisService::new(ethers_repository.clone()));
+
     let listen_deploy_erc20_contracts_service = Arc::new(ListenDeployErc20ContractsService::new(
         ethers_repository.clone(),
         HttpClient::new(),
+        token_analysis_service.clone(),
     ));
 
-    let call_functions_service = Arc::new(CallFunctionsService::new(ethers_repository.clone()));
+    let call_functions_service = Arc::new(CallFunctionsService::new(
+        ethers_repository.clone(),
+        abi_registry_service.clone(),
+    ));
+
+    let batch_call_service = Arc::new(BatchCallService::new(
+        ethers_repository.clone(),
+        abi_registry_service.clone(),
+    ));
+
+    let decode_service = Arc::new(DecodeService::new(abi_registry_service.clone()));
+
+    let simulate_service = Arc::new(SimulateService::new(
+        ethers_repository.clone(),
+        abi_registry_service.clone(),
+    ));
+
+    let address_inspection_service = Arc::new(AddressInspectionService::new(ethers_repository.clone()));
+
+    let token_service = Arc::new(TokenService::new(ethers_repository.clone()));
+
+    let get_connection_status_service =
+        Arc::new(GetConnectionStatusService::new(ethers_repository.clone()));
 
     HttpServer::new(move || {
         let mut app = App::new().wrap(Logger::default());
 
         app = app.app_data(web::Data::new(apply_rpc_service.clone()));
+        app = app.app_data(web::Data::new(abi_registry_service.clone()));
         app = app.app_data(web::Data::new(get_erc20_contracts.clone()));
         app = app.app_data(web::Data::new(call_functions_service.clone()));
+        app = app.app_data(web::Data::new(batch_call_service.clone()));
+        app = app.app_data(web::Data::new(decode_service.clone()));
+        app = app.app_data(web::Data::new(simulate_service.clone()));
+        app = app.app_data(web::Data::new(address_inspection_service.clone()));
+        app = app.app_data(web::Data::new(token_service.clone()));
+        app = app.app_data(web::Data::new(token_analysis_service.clone()));
+        app = app.app_data(web::Data::new(get_connection_status_service.clone()));
         app = app.app_data(web::Data::new(get_labels_service.clone()));
         app = app.app_data(web::Data::new(get_transactions_service.clone()));
+        app = app.app_data(web::Data::new(get_blocks_service.clone()));
         app = app.app_data(web::Data::new(get_logs_service.clone()));
         app = app.app_data(web::Data::new(listen_contract_events_service.clone()));
         app = app.app_data(web::Data::new(
@@ -88,6 +182,7 @@ async fn main() {
 
         let ethers_controller = EthersController::new();
         let elastic_controller = ElasticController::new();
+        let abi_controller = AbiController::new();
 
         for (endpoint, route) in ethers_controller.routes() {
             app = app.route(&endpoint, route);
@@ -97,6 +192,10 @@ async fn main() {
             app = app.route(&endpoint, route);
         }
 
+        for (endpoint, route) in abi_controller.routes() {
+            app = app.route(&endpoint, route);
+        }
+
         app
     })
     .bind("0.0.0.0:8080")
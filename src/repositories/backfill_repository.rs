@@ -0,0 +1,489 @@
+use crate::repositories::redis_repository::RedisRepository;
+use ethers::types::{Address, H256};
+use redis::{AsyncCommands, RedisError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::{watch, Mutex as AsyncMutex};
+
+/// Hash do Redis com o estado dos jobs de backfill, por id.
+const JOBS_KEY: &str = "backfill_jobs";
+
+/// Contador usado para gerar os ids dos jobs.
+const JOB_ID_KEY: &str = "backfill_jobs:next_id";
+
+/// Hash do Redis com os blocos já processados por backfills, por `user_id:chain_id` (veja
+/// `BackfillJob::checkpoints_field`).
+const CHECKPOINTS_KEY: &str = "backfill_checkpoints";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Running,
+    Paused,
+    Cancelled,
+    Completed,
+    Failed,
+}
+
+impl JobState {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobState::Cancelled | JobState::Completed | JobState::Failed)
+    }
+}
+
+/// Conjunto compacto de blocos: intervalos fechados, ordenados e sem sobreposição, serializado
+/// como `[[inicio, fim], ...]`.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+#[serde(transparent)]
+pub struct BlockRanges(Vec<(u64, u64)>);
+
+impl BlockRanges {
+    pub fn insert(&mut self, from: u64, to: u64) {
+        self.0.push((from, to));
+        self.normalize();
+    }
+
+    pub fn extend(&mut self, other: &BlockRanges) {
+        self.0.extend_from_slice(&other.0);
+        self.normalize();
+    }
+
+    /// Ordena e une os intervalos sobrepostos ou adjacentes.
+    fn normalize(&mut self) {
+        self.0.sort_unstable();
+
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(self.0.len());
+        for (from, to) in self.0.drain(..) {
+            match merged.last_mut() {
+                Some(last) if from <= last.1.saturating_add(1) => last.1 = last.1.max(to),
+                _ => merged.push((from, to)),
+            }
+        }
+        self.0 = merged;
+    }
+
+    /// Remove os blocos `from..=to` do conjunto, dividindo os intervalos que os contêm.
+    pub fn remove(&mut self, from: u64, to: u64) {
+        let mut kept = Vec::with_capacity(self.0.len() + 1);
+
+        for &(start, end) in &self.0 {
+            if end < from || start > to {
+                kept.push((start, end));
+                continue;
+            }
+            if start < from {
+                kept.push((start, from - 1));
+            }
+            if end > to {
+                kept.push((to + 1, end));
+            }
+        }
+        self.0 = kept;
+    }
+
+    /// Intervalos de `from..=to` que não estão no conjunto.
+    pub fn missing(&self, from: u64, to: u64) -> BlockRanges {
+        let mut missing = Vec::new();
+        let mut next = from;
+
+        for &(start, end) in &self.0 {
+            if start > to || next > to {
+                break;
+            }
+            if end < next {
+                continue;
+            }
+            if start > next {
+                missing.push((next, start - 1));
+            }
+            match end.checked_add(1) {
+                Some(after) => next = after,
+                None => return BlockRanges(missing),
+            }
+        }
+        if next <= to {
+            missing.push((next, to));
+        }
+
+        BlockRanges(missing)
+    }
+
+    pub fn count(&self) -> u64 {
+        self.0
+            .iter()
+            .fold(0u64, |count, (from, to)| count.saturating_add((to - from).saturating_add(1)))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    /// Divide os intervalos em trechos de até `size` blocos.
+    pub fn into_chunks(self, size: u64) -> impl Iterator<Item = (u64, u64)> {
+        debug_assert!(size > 0, "trechos precisam ter ao menos um bloco");
+        self.0.into_iter().flat_map(move |(from, to)| {
+            (from..=to)
+                .step_by(size as usize)
+                .map(move |start| (start, start.saturating_add(size - 1).min(to)))
+        })
+    }
+}
+
+/// Destino dos logs de um backfill: o índice `logs` do Elasticsearch ou um webhook, que recebe um
+/// POST por intervalo consultado.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "target", rename_all = "snake_case")]
+pub enum LogDelivery {
+    Elasticsearch,
+    Webhook { url: String },
+}
+
+/// Filtro e destino de um backfill de logs. `topics` segue o `eth_getLogs`: uma posição por
+/// tópico, em que `null` aceita qualquer valor e uma lista aceita qualquer um dos valores.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LogBackfill {
+    #[serde(default)]
+    pub addresses: Vec<Address>,
+    #[serde(default)]
+    pub topics: Vec<Option<Vec<H256>>>,
+    pub delivery: LogDelivery,
+}
+
+/// O que o job processa: as transações de cada bloco (`blocks`, o padrão dos jobs gravados antes
+/// dos backfills de logs) ou os logs do intervalo que atendem ao filtro (`logs`).
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BackfillKind {
+    #[default]
+    Blocks,
+    Logs(LogBackfill),
+}
+
+/// Estado persistido de um job de backfill. `skipped` conta os blocos que já constavam nos
+/// checkpoints quando o job começou ou foi retomado, e não são processados de novo; `done` os
+/// processados com sucesso e `failed_blocks` os que falharam mesmo após as novas tentativas,
+/// contadas em `retries`. `logs` conta os logs entregues pelos backfills de logs. `active_ms` é o
+/// tempo em execução, sem as pausas.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BackfillJob {
+    pub id: u64,
+    pub user_id: i32,
+    pub chain_id: u64,
+    #[serde(default)]
+    pub kind: BackfillKind,
+    pub from_block: u64,
+    pub to_block: u64,
+    pub state: JobState,
+    #[serde(default)]
+    pub skipped: u64,
+    pub done: u64,
+    #[serde(default)]
+    pub failed_blocks: Vec<u64>,
+    #[serde(default)]
+    pub retries: u64,
+    #[serde(default)]
+    pub logs: u64,
+    pub created_at: u64,
+    pub updated_at: u64,
+    #[serde(default)]
+    pub active_ms: u64,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl BackfillJob {
+    pub fn new(id: u64, user_id: i32, chain_id: u64, kind: BackfillKind, from_block: u64, to_block: u64) -> Self {
+        let now = unix_now();
+
+        BackfillJob {
+            id,
+            user_id,
+            chain_id,
+            kind,
+            from_block,
+            to_block,
+            state: JobState::Running,
+            skipped: 0,
+            done: 0,
+            failed_blocks: Vec::new(),
+            retries: 0,
+            logs: 0,
+            created_at: now,
+            updated_at: now,
+            active_ms: 0,
+            error: None,
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        (self.to_block - self.from_block).saturating_add(1)
+    }
+
+    /// Campo dos checkpoints do job: `user_id:chain_id` para os backfills de blocos e, para os de
+    /// logs, um campo próprio por filtro e destino, já que cada combinação cobre blocos diferentes.
+    fn checkpoints_field(&self) -> String {
+        match &self.kind {
+            BackfillKind::Blocks => format!("{}:{}", self.user_id, self.chain_id),
+            BackfillKind::Logs(backfill) => {
+                let backfill = serde_json::to_vec(backfill).expect("LogBackfill é serializável");
+                let hash = ethers::utils::keccak256(backfill);
+                format!("{}:{}:logs:{}", self.user_id, self.chain_id, ethers::utils::hex::encode(&hash[..8]))
+            }
+        }
+    }
+}
+
+/// Job com os campos calculados para a API: `rate` em blocos por segundo de execução e
+/// `eta_seconds`, a estimativa para os blocos restantes enquanto o job está em execução.
+#[derive(Serialize)]
+pub struct BackfillStatus {
+    #[serde(flatten)]
+    pub job: BackfillJob,
+    pub total: u64,
+    pub failed: u64,
+    pub rate: f64,
+    pub eta_seconds: Option<u64>,
+}
+
+impl From<BackfillJob> for BackfillStatus {
+    fn from(job: BackfillJob) -> Self {
+        let total = job.total();
+        let failed = job.failed_blocks.len() as u64;
+        let processed = job.done + failed;
+        let remaining = total.saturating_sub(job.skipped + processed);
+        let rate = match job.active_ms {
+            0 => 0.0,
+            active_ms => processed as f64 * 1000.0 / active_ms as f64,
+        };
+        let eta_seconds = (job.state == JobState::Running && rate > 0.0)
+            .then(|| (remaining as f64 / rate).ceil() as u64);
+
+        BackfillStatus { job, total, failed, rate, eta_seconds }
+    }
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or_default()
+}
+
+/// Jobs de backfill: o estado e os checkpoints ficam no Redis, para consulta por qualquer
+/// instância e retomada após reinicializações, e os jobs em execução nesta instância recebem
+/// pausa, retomada e cancelamento por um canal `watch`.
+pub struct BackfillRepository {
+    redis_repository: Arc<RedisRepository>,
+    controls: Mutex<HashMap<u64, watch::Sender<JobState>>>,
+    checkpoints_lock: AsyncMutex<()>,
+}
+
+impl BackfillRepository {
+    pub fn new(redis_repository: Arc<RedisRepository>) -> Self {
+        BackfillRepository {
+            redis_repository,
+            controls: Mutex::new(HashMap::new()),
+            checkpoints_lock: AsyncMutex::new(()),
+        }
+    }
+
+    /// Blocos já processados por backfills do mesmo tipo que o job, do usuário na chain.
+    pub async fn checkpoints(&self, job: &BackfillJob) -> Result<BlockRanges, RedisError> {
+        let mut redis_conn = self.redis_repository.get_conn().await;
+        let value: Option<String> = redis_conn.hget(CHECKPOINTS_KEY, job.checkpoints_field()).await?;
+
+        Ok(value.and_then(|value| serde_json::from_str(&value).ok()).unwrap_or_default())
+    }
+
+    /// Acrescenta `completed` aos checkpoints do job. A leitura e a gravação são serializadas nesta
+    /// instância para que jobs simultâneos na mesma chain não percam blocos.
+    pub async fn add_checkpoints(&self, job: &BackfillJob, completed: &BlockRanges) -> Result<(), RedisError> {
+        let _guard = self.checkpoints_lock.lock().await;
+        let mut checkpoints = self.checkpoints(job).await?;
+        checkpoints.extend(completed);
+
+        let mut redis_conn = self.redis_repository.get_conn().await;
+        let value = serde_json::to_string(&checkpoints).expect("BlockRanges é serializável");
+        let _: i64 = redis_conn.hset(CHECKPOINTS_KEY, job.checkpoints_field(), value).await?;
+        Ok(())
+    }
+
+    /// Remove `removed` dos checkpoints de todos os backfills da chain, de qualquer usuário, para
+    /// que os blocos sejam processados de novo. Usado quando os documentos indexados a partir deles
+    /// são removidos do Elasticsearch em um reorg.
+    pub async fn remove_checkpoints(&self, chain_id: u64, removed: &BlockRanges) -> Result<(), RedisError> {
+        let _guard = self.checkpoints_lock.lock().await;
+        let mut redis_conn = self.redis_repository.get_conn().await;
+        let checkpoints: HashMap<String, String> = redis_conn.hgetall(CHECKPOINTS_KEY).await?;
+        let chain = chain_id.to_string();
+
+        for (field, value) in checkpoints {
+            if field.split(':').nth(1) != Some(chain.as_str()) {
+                continue;
+            }
+            let Ok(mut ranges) = serde_json::from_str::<BlockRanges>(&value) else {
+                continue;
+            };
+
+            let before = ranges.clone();
+            for &(from, to) in &removed.0 {
+                ranges.remove(from, to);
+            }
+            if ranges != before {
+                let value = serde_json::to_string(&ranges).expect("BlockRanges é serializável");
+                let _: i64 = redis_conn.hset(CHECKPOINTS_KEY, field, value).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn next_id(&self) -> Result<u64, RedisError> {
+        let mut redis_conn = self.redis_repository.get_conn().await;
+        redis_conn.incr(JOB_ID_KEY, 1).await
+    }
+
+    pub async fn save(&self, job: &BackfillJob) -> Result<(), RedisError> {
+        let mut redis_conn = self.redis_repository.get_conn().await;
+        let value = serde_json::to_string(job).expect("BackfillJob é serializável");
+        let _: i64 = redis_conn.hset(JOBS_KEY, job.id, value).await?;
+        Ok(())
+    }
+
+    pub async fn get(&self, id: u64) -> Result<Option<BackfillJob>, RedisError> {
+        let mut redis_conn = self.redis_repository.get_conn().await;
+        let value: Option<String> = redis_conn.hget(JOBS_KEY, id).await?;
+
+        Ok(value.and_then(|value| serde_json::from_str(&value).ok()))
+    }
+
+    /// Todos os jobs, de todos os usuários, ordenados pelo id.
+    pub async fn list_all(&self) -> Result<Vec<BackfillJob>, RedisError> {
+        let mut redis_conn = self.redis_repository.get_conn().await;
+        let values: Vec<String> = redis_conn.hvals(JOBS_KEY).await?;
+
+        let mut jobs: Vec<BackfillJob> = values
+            .iter()
+            .filter_map(|value| serde_json::from_str::<BackfillJob>(value).ok())
+            .collect();
+        jobs.sort_by_key(|job| job.id);
+
+        Ok(jobs)
+    }
+
+    pub async fn list(&self, user_id: i32) -> Result<Vec<BackfillJob>, RedisError> {
+        let mut jobs = self.list_all().await?;
+        jobs.retain(|job| job.user_id == user_id);
+        Ok(jobs)
+    }
+
+    /// Registra o job como em execução nesta instância e retorna o canal de controle.
+    pub fn register(&self, id: u64, state: JobState) -> watch::Receiver<JobState> {
+        let (sender, receiver) = watch::channel(state);
+        self.controls.lock().unwrap().insert(id, sender);
+        receiver
+    }
+
+    pub fn unregister(&self, id: u64) {
+        self.controls.lock().unwrap().remove(&id);
+    }
+
+    /// Envia o novo estado ao job. Retorna `false` se ele não está em execução nesta instância.
+    pub fn control(&self, id: u64, state: JobState) -> bool {
+        match self.controls.lock().unwrap().get(&id) {
+            Some(sender) => sender.send(state).is_ok(),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges(ranges: &[(u64, u64)]) -> BlockRanges {
+        let mut set = BlockRanges::default();
+        for &(from, to) in ranges {
+            set.insert(from, to);
+        }
+        set
+    }
+
+    #[test]
+    fn insert_merges_overlapping_and_adjacent_ranges() {
+        let set = ranges(&[(10, 20), (15, 30), (31, 40), (50, 60), (5, 9)]);
+        assert_eq!(set, BlockRanges(vec![(5, 40), (50, 60)]));
+        assert_eq!(set.count(), 36 + 11);
+    }
+
+    #[test]
+    fn insert_keeps_ranges_separated_by_one_block() {
+        let set = ranges(&[(1, 5), (7, 9)]);
+        assert_eq!(set, BlockRanges(vec![(1, 5), (7, 9)]));
+        assert_eq!(set.missing(1, 9), BlockRanges(vec![(6, 6)]));
+    }
+
+    #[test]
+    fn extend_merges_both_sets() {
+        let mut set = ranges(&[(1, 3), (10, 12)]);
+        set.extend(&ranges(&[(4, 9), (20, 20)]));
+        assert_eq!(set, BlockRanges(vec![(1, 12), (20, 20)]));
+    }
+
+    #[test]
+    fn missing_returns_the_gaps_inside_the_interval() {
+        let set = ranges(&[(10, 20), (30, 40)]);
+
+        assert_eq!(set.missing(0, 50), BlockRanges(vec![(0, 9), (21, 29), (41, 50)]));
+        assert_eq!(set.missing(15, 35), BlockRanges(vec![(21, 29)]));
+        assert_eq!(set.missing(10, 20), BlockRanges::default());
+        assert_eq!(set.missing(20, 30), BlockRanges(vec![(21, 29)]));
+        assert_eq!(set.missing(21, 29), BlockRanges(vec![(21, 29)]));
+        assert_eq!(set.missing(41, 41), BlockRanges(vec![(41, 41)]));
+        assert_eq!(BlockRanges::default().missing(7, 7), BlockRanges(vec![(7, 7)]));
+    }
+
+    #[test]
+    fn missing_and_count_handle_u64_max() {
+        let set = ranges(&[(u64::MAX - 5, u64::MAX)]);
+
+        assert_eq!(set.missing(u64::MAX - 10, u64::MAX), BlockRanges(vec![(u64::MAX - 10, u64::MAX - 6)]));
+        assert_eq!(set.missing(u64::MAX - 2, u64::MAX), BlockRanges::default());
+        assert_eq!(set.count(), 6);
+        assert_eq!(ranges(&[(0, u64::MAX)]).count(), u64::MAX);
+
+        let merged = ranges(&[(u64::MAX - 5, u64::MAX), (u64::MAX - 10, u64::MAX - 6)]);
+        assert_eq!(merged, BlockRanges(vec![(u64::MAX - 10, u64::MAX)]));
+    }
+
+    #[test]
+    fn remove_splits_the_containing_ranges() {
+        let mut set = ranges(&[(1, 10), (20, 30)]);
+        set.remove(5, 5);
+        set.remove(18, 22);
+        set.remove(30, 40);
+        assert_eq!(set, BlockRanges(vec![(1, 4), (6, 10), (23, 29)]));
+
+        set.remove(0, u64::MAX);
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn into_chunks_covers_every_block_once() {
+        let set = ranges(&[(1, 5), (10, 10), (20, 26)]);
+        let chunks: Vec<_> = set.clone().into_chunks(3).collect();
+        assert_eq!(chunks, vec![(1, 3), (4, 5), (10, 10), (20, 22), (23, 25), (26, 26)]);
+
+        let blocks: Vec<_> = set.into_chunks(1).map(|(from, _)| from).collect();
+        assert_eq!(blocks, vec![1, 2, 3, 4, 5, 10, 20, 21, 22, 23, 24, 25, 26]);
+
+        let top: Vec<_> = ranges(&[(u64::MAX - 2, u64::MAX)]).into_chunks(2).collect();
+        assert_eq!(top, vec![(u64::MAX - 2, u64::MAX - 1), (u64::MAX, u64::MAX)]);
+    }
+}
@@ -0,0 +1,497 @@
+use ethers::types::{Block, H256};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::ops::RangeInclusive;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+/// Quantidade de blocos mantidos para consumidores mais lentos antes que passem a perder blocos.
+pub const BLOCK_HUB_CAPACITY: usize = 128;
+
+/// Quantidade de blocos recentes cujo hash é mantido para detectar reorganizações da chain.
+/// Reorgs mais profundos que a janela não podem ser resolvidos e são apenas registrados em log.
+pub const REORG_WINDOW: usize = 128;
+
+/// Bloco que fez parte da chain canônica publicada pelo hub e foi substituído em um reorg.
+#[derive(Clone, Debug, Serialize)]
+pub struct OrphanedBlock {
+    pub number: u64,
+    pub hash: H256,
+}
+
+#[derive(Clone)]
+enum HubMessage {
+    Block(Box<Block<H256>>),
+    Reorged(Vec<OrphanedBlock>),
+}
+
+/// Distribui os blocos de uma conexão para qualquer número de consumidores.
+///
+/// O `ConnectionSupervisor` publica cada bloco recebido, em alturas contíguas, e cada consumidor
+/// (detecção de deploys, indexação, alertas etc.) obtém sua própria inscrição com `subscribe`. Para
+/// cancelar a inscrição basta descartar a `BlockSubscription`. O hub sobrevive às reconexões e à
+/// troca de endpoints, então as inscrições continuam válidas enquanto a conexão existir.
+///
+/// O hub também guarda o número e o hash dos últimos `REORG_WINDOW` blocos publicados, a chain
+/// canônica vista pelos consumidores. Quando o supervisor detecta um reorg, `retract` remove os
+/// blocos órfãos dessa janela e avisa os inscritos com `BlockEvent::Reorged` antes que os blocos
+/// da nova chain sejam publicados.
+#[derive(Clone)]
+pub struct BlockHub {
+    sender: broadcast::Sender<HubMessage>,
+    canonical: Arc<Mutex<VecDeque<(u64, H256)>>>,
+}
+
+impl BlockHub {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(BLOCK_HUB_CAPACITY);
+        BlockHub {
+            sender,
+            canonical: Arc::new(Mutex::new(VecDeque::with_capacity(REORG_WINDOW))),
+        }
+    }
+
+    /// Publica o bloco para todos os inscritos e o registra na janela canônica. Sem inscritos o
+    /// bloco é simplesmente descartado.
+    pub fn publish(&self, block: Block<H256>) {
+        if let (Some(number), Some(hash)) = (block.number, block.hash) {
+            let mut canonical = self.canonical.lock().unwrap();
+            canonical.push_back((number.as_u64(), hash));
+            if canonical.len() > REORG_WINDOW {
+                canonical.pop_front();
+            }
+        }
+
+        let _ = self.sender.send(HubMessage::Block(Box::new(block)));
+    }
+
+    /// Remove da janela canônica os blocos acima de `ancestor` e notifica os inscritos.
+    /// Retorna os blocos órfãos, do mais baixo para o mais alto.
+    pub fn retract(&self, ancestor: u64) -> Vec<OrphanedBlock> {
+        let mut orphans = Vec::new();
+
+        {
+            let mut canonical = self.canonical.lock().unwrap();
+            while let Some((number, hash)) = canonical.back().copied() {
+                if number <= ancestor {
+                    break;
+                }
+                canonical.pop_back();
+                orphans.push(OrphanedBlock { number, hash });
+            }
+        }
+
+        orphans.reverse();
+
+        if !orphans.is_empty() {
+            let _ = self.sender.send(HubMessage::Reorged(orphans.clone()));
+        }
+
+        orphans
+    }
+
+    /// Altura do último bloco publicado.
+    pub fn head(&self) -> Option<u64> {
+        self.canonical.lock().unwrap().back().map(|(number, _)| *number)
+    }
+
+    /// Hash publicado na altura `number`, se ela ainda estiver na janela canônica.
+    pub fn canonical_hash(&self, number: u64) -> Option<H256> {
+        self.canonical
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|(canonical_number, _)| *canonical_number == number)
+            .map(|(_, hash)| *hash)
+    }
+
+    pub fn subscribe(&self) -> BlockSubscription {
+        BlockSubscription {
+            receiver: self.sender.subscribe(),
+            last_block: None,
+            pending: None,
+            lagged: false,
+        }
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+}
+
+impl Default for BlockHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub enum BlockEvent {
+    Block(Box<Block<H256>>),
+    /// Os blocos deixaram a chain canônica. Tudo o que o consumidor derivou deles deve ser
+    /// retratado; os blocos da nova chain chegam em seguida como `BlockEvent::Block`.
+    Reorged(Vec<OrphanedBlock>),
+    /// O consumidor ficou mais de `BLOCK_HUB_CAPACITY` blocos atrás e as alturas do intervalo foram
+    /// descartadas do hub. Cabe ao consumidor buscá-las via RPC, se precisar delas.
+    Lagged(RangeInclusive<u64>),
+}
+
+/// Inscrição de um consumidor no `BlockHub`.
+///
+/// Em vez de devolver o `RecvError::Lagged` do canal, que só informa a quantidade de blocos perdidos,
+/// a inscrição informa exatamente quais alturas o consumidor deixou de receber e em seguida continua
+/// a entrega a partir do bloco mais antigo ainda disponível no hub. Reorgs recebidos durante um
+/// atraso também são repassados, já que o consumidor pode ter entregue algo a partir dos blocos
+/// órfãos antes de ficar para trás.
+pub struct BlockSubscription {
+    receiver: broadcast::Receiver<HubMessage>,
+    last_block: Option<u64>,
+    pending: Option<Box<Block<H256>>>,
+    lagged: bool,
+}
+
+impl BlockSubscription {
+    /// Aguarda o próximo evento. Retorna `None` quando a conexão é removida.
+    pub async fn recv(&mut self) -> Option<BlockEvent> {
+        if let Some(block) = self.pending.take() {
+            return Some(self.accept(block));
+        }
+
+        let block = loop {
+            match self.receiver.recv().await {
+                Ok(HubMessage::Block(block)) => break block,
+                Ok(HubMessage::Reorged(orphans)) => {
+                    if let Some(first) = orphans.first() {
+                        let ancestor = first.number.saturating_sub(1);
+                        self.last_block = self.last_block.map(|last| last.min(ancestor));
+                    }
+                    return Some(BlockEvent::Reorged(orphans));
+                }
+                // O atraso é guardado até o próximo bloco, mesmo que um reorg seja repassado antes.
+                Err(RecvError::Lagged(_)) => self.lagged = true,
+                Err(RecvError::Closed) => return None,
+            }
+        };
+
+        let number = block.number.map(|number| number.as_u64());
+        let lagged = std::mem::take(&mut self.lagged);
+
+        match (lagged, self.last_block, number) {
+            (true, Some(last), Some(number)) if number > last + 1 => {
+                self.pending = Some(block);
+                self.last_block = Some(number - 1);
+                Some(BlockEvent::Lagged(last + 1..=number - 1))
+            }
+            _ => Some(self.accept(block)),
+        }
+    }
+
+    fn accept(&mut self, block: Box<Block<H256>>) -> BlockEvent {
+        if let Some(number) = block.number {
+            self.last_block = Some(number.as_u64());
+        }
+
+        BlockEvent::Block(block)
+    }
+}
+
+/// Registro do que um consumidor entregou a partir de cada bloco recente, para que as entregas
+/// feitas a partir de blocos órfãos possam ser retratadas quando chegar um `BlockEvent::Reorged`.
+/// Entradas com mais de `REORG_WINDOW` blocos de profundidade são descartadas.
+pub struct DeliveryJournal<T> {
+    entries: VecDeque<(u64, H256, T)>,
+}
+
+impl<T> DeliveryJournal<T> {
+    pub fn new() -> Self {
+        DeliveryJournal { entries: VecDeque::new() }
+    }
+
+    pub fn record(&mut self, number: u64, hash: H256, item: T) {
+        self.entries.push_back((number, hash, item));
+
+        let oldest = number.saturating_sub(REORG_WINDOW as u64);
+        while self.entries.front().is_some_and(|(entry, _, _)| *entry < oldest) {
+            self.entries.pop_front();
+        }
+    }
+
+    /// Remove e retorna as entregas feitas a partir dos blocos órfãos.
+    pub fn retract(&mut self, orphans: &[OrphanedBlock]) -> Vec<T> {
+        let (retracted, kept): (VecDeque<_>, VecDeque<_>) = self
+            .entries
+            .drain(..)
+            .partition(|(_, hash, _)| orphans.iter().any(|orphan| orphan.hash == *hash));
+
+        self.entries = kept;
+        retracted.into_iter().map(|(_, _, item)| item).collect()
+    }
+}
+
+impl<T> Default for DeliveryJournal<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Fila de entregas aguardando confirmações.
+///
+/// Um item do bloco `number` é liberado quando o bloco tem `confirmations` de profundidade, ou seja,
+/// quando a altura publicada pelo hub chega a `number + confirmations - 1`. Com `confirmations`
+/// igual a 0 ou 1 os itens são liberados assim que a altura do bloco é conhecida.
+pub struct ConfirmationQueue<T> {
+    confirmations: u64,
+    head: Option<u64>,
+    pending: VecDeque<(u64, H256, T)>,
+}
+
+impl<T> ConfirmationQueue<T> {
+    pub fn new(confirmations: u64) -> Self {
+        ConfirmationQueue {
+            confirmations,
+            head: None,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Atualiza a altura atual da chain. A altura só retrocede via `retract`, em um reorg.
+    pub fn set_head(&mut self, head: u64) {
+        self.head = Some(self.head.map_or(head, |current| current.max(head)));
+    }
+
+    pub fn push(&mut self, number: u64, hash: H256, item: T) {
+        self.pending.push_back((number, hash, item));
+    }
+
+    /// Remove e retorna os itens que já atingiram a profundidade exigida.
+    pub fn take_confirmed(&mut self) -> Vec<(u64, H256, T)> {
+        let Some(head) = self.head else {
+            return Vec::new();
+        };
+        let depth = self.confirmations.saturating_sub(1);
+
+        let (confirmed, pending): (VecDeque<_>, VecDeque<_>) = self
+            .pending
+            .drain(..)
+            .partition(|(number, _, _)| number + depth <= head);
+
+        self.pending = pending;
+        confirmed.into_iter().collect()
+    }
+
+    /// Descarta os itens de blocos órfãos, que nunca serão confirmados.
+    pub fn retract(&mut self, orphans: &[OrphanedBlock]) {
+        self.pending
+            .retain(|(_, hash, _)| !orphans.iter().any(|orphan| orphan.hash == *hash));
+
+        if let Some(first) = orphans.first() {
+            self.head = self.head.map(|head| head.min(first.number.saturating_sub(1)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(number: u64, fork: u64) -> Block<H256> {
+        Block {
+            number: Some(number.into()),
+            hash: Some(H256::from_low_u64_be(fork << 32 | number)),
+            ..Default::default()
+        }
+    }
+
+    fn publish(hub: &BlockHub, numbers: RangeInclusive<u64>, fork: u64) {
+        for number in numbers {
+            hub.publish(block(number, fork));
+        }
+    }
+
+    async fn next_block(subscription: &mut BlockSubscription) -> u64 {
+        match subscription.recv().await {
+            Some(BlockEvent::Block(block)) => block.number.unwrap().as_u64(),
+            _ => panic!("esperado BlockEvent::Block"),
+        }
+    }
+
+    async fn next_lagged(subscription: &mut BlockSubscription) -> RangeInclusive<u64> {
+        match subscription.recv().await {
+            Some(BlockEvent::Lagged(missed)) => missed,
+            _ => panic!("esperado BlockEvent::Lagged"),
+        }
+    }
+
+    async fn next_reorged(subscription: &mut BlockSubscription) -> Vec<u64> {
+        match subscription.recv().await {
+            Some(BlockEvent::Reorged(orphans)) => orphans.iter().map(|orphan| orphan.number).collect(),
+            _ => panic!("esperado BlockEvent::Reorged"),
+        }
+    }
+
+    #[tokio::test]
+    async fn delivers_blocks_in_order() {
+        let hub = BlockHub::new();
+        let mut subscription = hub.subscribe();
+
+        publish(&hub, 1..=3, 0);
+
+        for number in 1..=3 {
+            assert_eq!(next_block(&mut subscription).await, number);
+        }
+        assert_eq!(hub.head(), Some(3));
+    }
+
+    #[tokio::test]
+    async fn reorg_is_announced_before_the_new_chain() {
+        let hub = BlockHub::new();
+        let mut subscription = hub.subscribe();
+
+        publish(&hub, 1..=5, 0);
+        for number in 1..=5 {
+            assert_eq!(next_block(&mut subscription).await, number);
+        }
+
+        let orphans = hub.retract(3);
+        assert_eq!(orphans.iter().map(|orphan| orphan.number).collect::<Vec<_>>(), vec![4, 5]);
+        publish(&hub, 4..=6, 1);
+
+        assert_eq!(next_reorged(&mut subscription).await, vec![4, 5]);
+        for number in 4..=6 {
+            assert_eq!(next_block(&mut subscription).await, number);
+        }
+        assert_eq!(hub.canonical_hash(4), Some(block(4, 1).hash.unwrap()));
+    }
+
+    #[tokio::test]
+    async fn lag_reports_the_missed_heights_then_resumes() {
+        let hub = BlockHub::new();
+        let mut subscription = hub.subscribe();
+
+        publish(&hub, 1..=1, 0);
+        assert_eq!(next_block(&mut subscription).await, 1);
+
+        let last = 1 + BLOCK_HUB_CAPACITY as u64 + 10;
+        publish(&hub, 2..=last, 0);
+
+        // O hub mantém apenas os últimos `BLOCK_HUB_CAPACITY` blocos.
+        let first_kept = last - BLOCK_HUB_CAPACITY as u64 + 1;
+        assert_eq!(next_lagged(&mut subscription).await, 2..=first_kept - 1);
+        for number in first_kept..=last {
+            assert_eq!(next_block(&mut subscription).await, number);
+        }
+    }
+
+    #[tokio::test]
+    async fn gap_without_lag_is_delivered_as_is() {
+        let hub = BlockHub::new();
+        let mut subscription = hub.subscribe();
+
+        publish(&hub, 1..=1, 0);
+        publish(&hub, 5..=5, 0);
+
+        assert_eq!(next_block(&mut subscription).await, 1);
+        assert_eq!(next_block(&mut subscription).await, 5);
+    }
+
+    #[tokio::test]
+    async fn reorg_received_while_lagged_is_forwarded() {
+        let hub = BlockHub::new();
+        let mut subscription = hub.subscribe();
+
+        publish(&hub, 1..=3, 0);
+        for number in 1..=3 {
+            assert_eq!(next_block(&mut subscription).await, number);
+        }
+
+        // Depois do reorg são publicados blocos suficientes para que ele seja a mensagem mais
+        // antiga ainda no canal quando o consumidor voltar a ler.
+        publish(&hub, 4..=200, 0);
+        hub.retract(190);
+        let last = 190 + BLOCK_HUB_CAPACITY as u64 - 1;
+        publish(&hub, 191..=last, 1);
+
+        assert_eq!(next_reorged(&mut subscription).await, (191..=200).collect::<Vec<_>>());
+        assert_eq!(next_lagged(&mut subscription).await, 4..=190);
+        for number in 191..=last {
+            assert_eq!(next_block(&mut subscription).await, number);
+        }
+    }
+
+    #[tokio::test]
+    async fn reorg_below_the_last_block_lowers_the_gap_start() {
+        let hub = BlockHub::new();
+        let mut subscription = hub.subscribe();
+
+        publish(&hub, 1..=10, 0);
+        for number in 1..=10 {
+            assert_eq!(next_block(&mut subscription).await, number);
+        }
+
+        hub.retract(5);
+        let last = 5 + BLOCK_HUB_CAPACITY as u64 + 20;
+        publish(&hub, 6..=last, 1);
+
+        // O reorg foi descartado do canal junto com os blocos; as alturas perdidas partem do
+        // último bloco entregue.
+        let first_kept = last - BLOCK_HUB_CAPACITY as u64 + 1;
+        assert_eq!(next_lagged(&mut subscription).await, 11..=first_kept - 1);
+        assert_eq!(next_block(&mut subscription).await, first_kept);
+    }
+
+    #[test]
+    fn journal_retracts_only_orphaned_deliveries() {
+        let mut journal = DeliveryJournal::new();
+        journal.record(1, block(1, 0).hash.unwrap(), "a");
+        journal.record(2, block(2, 0).hash.unwrap(), "b");
+        journal.record(2, block(2, 0).hash.unwrap(), "c");
+
+        let orphans = [OrphanedBlock { number: 2, hash: block(2, 0).hash.unwrap() }];
+        assert_eq!(journal.retract(&orphans), vec!["b", "c"]);
+        assert!(journal.retract(&orphans).is_empty());
+
+        let orphans = [OrphanedBlock { number: 1, hash: block(1, 0).hash.unwrap() }];
+        assert_eq!(journal.retract(&orphans), vec!["a"]);
+    }
+
+    #[test]
+    fn journal_drops_entries_beyond_the_reorg_window() {
+        let mut journal = DeliveryJournal::new();
+        journal.record(1, block(1, 0).hash.unwrap(), "old");
+        let recent = 2 + REORG_WINDOW as u64;
+        journal.record(recent, block(recent, 0).hash.unwrap(), "recent");
+
+        let orphans = [OrphanedBlock { number: 1, hash: block(1, 0).hash.unwrap() }];
+        assert!(journal.retract(&orphans).is_empty());
+    }
+
+    #[test]
+    fn queue_releases_items_at_the_required_depth() {
+        let mut queue = ConfirmationQueue::new(3);
+        queue.push(10, block(10, 0).hash.unwrap(), "a");
+
+        assert!(queue.take_confirmed().is_empty());
+        queue.set_head(11);
+        assert!(queue.take_confirmed().is_empty());
+        queue.set_head(12);
+        let confirmed: Vec<_> = queue.take_confirmed().into_iter().map(|(_, _, item)| item).collect();
+        assert_eq!(confirmed, vec!["a"]);
+    }
+
+    #[test]
+    fn queue_reorg_discards_orphans_and_lowers_the_head() {
+        let mut queue = ConfirmationQueue::new(2);
+        queue.set_head(10);
+        queue.push(10, block(10, 0).hash.unwrap(), "orphan");
+        queue.push(9, block(9, 0).hash.unwrap(), "kept");
+
+        queue.retract(&[OrphanedBlock { number: 10, hash: block(10, 0).hash.unwrap() }]);
+
+        // A altura volta para 9, então o item do bloco 9 ainda precisa de mais um bloco.
+        assert!(queue.take_confirmed().is_empty());
+        queue.set_head(10);
+        let confirmed: Vec<_> = queue.take_confirmed().into_iter().map(|(_, _, item)| item).collect();
+        assert_eq!(confirmed, vec!["kept"]);
+    }
+}
@@ -1,6 +1,6 @@
 use elasticsearch::{
-    BulkParts, CountParts, Elasticsearch, Error as ElasticsearchError, IndexParts, ScrollParts,
-    SearchParts,
+    BulkParts, CountParts, DeleteByQueryParts, Elasticsearch, Error as ElasticsearchError,
+    IndexParts, ScrollParts, SearchParts, UpdateByQueryParts,
 };
 use ethers::prelude::Transaction;
 use serde::{Deserialize, Serialize};
@@ -86,39 +86,37 @@ impl ElasticRepository {
         Ok(())
     }
 
-    /// Indexa um conjunto de documentos no Elasticsearch.
+    /// Indexa um conjunto de documentos com ids definidos pelo chamador, substituindo os documentos
+    /// já indexados com o mesmo id. Permite reindexar o mesmo conteúdo sem duplicá-lo.
     ///
     /// # Argumentos
     ///
     /// * `index` - Nome do índice onde os documentos serão armazenados
-    /// * `documents` - Vetor de documentos a ser indexado (deve implementar Serialize)
+    /// * `documents` - Pares de id e documento a ser indexado (deve implementar Serialize)
     ///
-    /// # Exemplo
-    ///
-    /// ```rust
-    /// use serde_json::json;
+    /// # Retorno
     ///
-    /// let es_service = ElasticsearchService::new("http://localhost:9200");
-    /// ```
-    pub async fn index_bulk_documents<T: Serialize>(
+    /// Retorna erro se a requisição falhar ou se algum documento for rejeitado.
+    pub async fn index_bulk_documents_with_ids<T: Serialize>(
         &self,
         index: &str,
-        documents: Vec<T>,
-    ) -> Result<(), ElasticsearchError> {
+        documents: Vec<(String, T)>,
+    ) -> Result<(), ElasticRepositoryError> {
         let mut bulk_body = Vec::new();
 
-        for doc in documents {
+        for (id, doc) in documents {
             bulk_body.push(json!({
                 "index": {
                     "_index": index,
+                    "_id": id,
                 }
             }));
 
-            bulk_body.push(serde_json::to_value(doc)?);
+            bulk_body.push(serde_json::to_value(doc).map_err(|e| ElasticRepositoryError::ResponseError(e.to_string()))?);
         }
 
-        self.client
-            .clone()
+        let response = self
+            .client
             .bulk(BulkParts::None)
             .body(
                 bulk_body
@@ -129,6 +127,22 @@ impl ElasticRepository {
             .send()
             .await?;
 
+        let status = response.status_code();
+        if !status.is_success() {
+            return Err(ElasticRepositoryError::ResponseError(format!(
+                "Falha ao indexar documentos. Status: {}",
+                status
+            )));
+        }
+
+        let response_body = response.json::<Value>().await?;
+        if response_body["errors"].as_bool().unwrap_or(false) {
+            return Err(ElasticRepositoryError::ResponseError(format!(
+                "Documentos rejeitados pelo índice {}",
+                index
+            )));
+        }
+
         Ok(())
     }
 
@@ -220,6 +234,135 @@ impl ElasticRepository {
         Ok(hits)
     }
 
+    /// Remove os documentos que atendem à query.
+    ///
+    /// # Argumentos
+    ///
+    /// * `index` - Nome do índice de onde os documentos serão removidos
+    /// * `query` - Query em formato JSON que seleciona os documentos
+    ///
+    /// # Retorno
+    ///
+    /// Retorna a quantidade de documentos removidos. Um índice inexistente não é tratado como erro.
+    ///
+    /// # Exemplo
+    ///
+    /// ```rust
+    /// let removidos = es_service.delete_by_query(
+    ///     "transactions",
+    ///     &json!({ "query": { "term": { "chain_id": 1 } } })
+    /// ).await?;
+    /// ```
+    pub async fn delete_by_query(
+        &self,
+        index: &str,
+        query: &Value,
+    ) -> Result<u64, ElasticRepositoryError> {
+        let response = self
+            .client
+            .delete_by_query(DeleteByQueryParts::Index(&[index]))
+            .body(query)
+            .send()
+            .await?;
+
+        let status = response.status_code();
+        if status.as_u16() == 404 {
+            return Ok(0);
+        }
+        if !status.is_success() {
+            return Err(ElasticRepositoryError::ResponseError(format!(
+                "Falha ao remover documentos. Status: {}",
+                status
+            )));
+        }
+
+        let response_body = response.json::<Value>().await?;
+        Ok(response_body["deleted"].as_u64().unwrap_or(0))
+    }
+
+    /// Atualiza os documentos que atendem à query com o script do corpo.
+    ///
+    /// # Argumentos
+    ///
+    /// * `index` - Nome do índice dos documentos
+    /// * `body` - Corpo em formato JSON com a `query` que seleciona os documentos e o `script`
+    ///
+    /// # Retorno
+    ///
+    /// Retorna a quantidade de documentos atualizados. Um índice inexistente não é tratado como erro.
+    ///
+    /// # Exemplo
+    ///
+    /// ```rust
+    /// let atualizados = es_service.update_by_query(
+    ///     "transactions",
+    ///     &json!({
+    ///         "query": { "term": { "chain_id": 1 } },
+    ///         "script": { "source": "ctx._source.blockTimestamp = params.timestamp", "params": { "timestamp": 0 } }
+    ///     })
+    /// ).await?;
+    /// ```
+    pub async fn update_by_query(
+        &self,
+        index: &str,
+        body: &Value,
+    ) -> Result<u64, ElasticRepositoryError> {
+        let response = self
+            .client
+            .update_by_query(UpdateByQueryParts::Index(&[index]))
+            .conflicts(elasticsearch::params::Conflicts::Proceed)
+            .body(body)
+            .send()
+            .await?;
+
+        let status = response.status_code();
+        if status.as_u16() == 404 {
+            return Ok(0);
+        }
+        if !status.is_success() {
+            return Err(ElasticRepositoryError::ResponseError(format!(
+                "Falha ao atualizar documentos. Status: {}",
+                status
+            )));
+        }
+
+        let response_body = response.json::<Value>().await?;
+        Ok(response_body["updated"].as_u64().unwrap_or(0))
+    }
+
+    /// Conta os documentos que atendem à query.
+    ///
+    /// # Argumentos
+    ///
+    /// * `index` - Nome do índice dos documentos
+    /// * `query` - Query em formato JSON que seleciona os documentos
+    ///
+    /// # Retorno
+    ///
+    /// Retorna a quantidade de documentos. Um índice inexistente conta como vazio.
+    pub async fn count(&self, index: &str, query: &Value) -> Result<u64, ElasticRepositoryError> {
+        let response = self
+            .client
+            .count(CountParts::Index(&[index]))
+            .body(query)
+            .send()
+            .await?;
+
+        let status = response.status_code();
+        if status.as_u16() == 404 {
+            return Ok(0);
+        }
+        if !status.is_success() {
+            return Err(ElasticRepositoryError::ResponseError(format!(
+                "Falha na contagem. Status: {}",
+                status
+            )));
+        }
+
+        let response_body = response.json::<Value>().await?;
+        Ok(response_body["count"].as_u64().unwrap_or(0))
+    }
+
     pub async fn index_documents_count(&self, index: &str, query: &Value) -> u64 {
         let response = self
             .client
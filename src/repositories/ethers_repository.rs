@@ -1,37 +1,59 @@
-use ethers::middleware::Middleware;
-use ethers::providers::{Provider, Ws};
-use ethers::types::{Block, H256};
-use futures::stream::StreamExt;
+use crate::repositories::block_hub::{BlockHub, BlockSubscription};
+use crate::rpc_transport::{RpcPool, RpcProvider};
 use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::spawn;
-use tokio::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
 
+/// Saúde de uma conexão, atualizada pelo `ConnectionSupervisor` a cada bloco recebido e a cada
+/// queda ou reconexão.
+#[derive(Clone, Default)]
+pub struct ConnectionHealth {
+    pub last_block: Option<u64>,
+    pub last_block_at: Option<SystemTime>,
+    pub reconnects: u32,
+    pub last_error: Option<String>,
+}
+
+/// Conexões são identificadas pelo par (`user_id`, `chain_id`), permitindo que um mesmo usuário
+/// acompanhe várias chains ao mesmo tempo.
 pub struct EthersRepository {
-    connections: HashMap<i32, Arc<Provider<Ws>>>,
-    block_listeners: HashMap<i32, Receiver<Block<H256>>>,
+    connections: HashMap<(i32, u64), watch::Sender<Arc<RpcProvider>>>,
+    pools: HashMap<(i32, u64), RpcPool>,
+    block_hubs: HashMap<(i32, u64), BlockHub>,
+    supervisors: HashMap<(i32, u64), JoinHandle<()>>,
+    health: HashMap<(i32, u64), Arc<Mutex<ConnectionHealth>>>,
+    tasks: HashMap<(i32, u64), Vec<JoinHandle<()>>>,
 }
 
 impl EthersRepository {
     pub fn new() -> Self {
         EthersRepository {
             connections: HashMap::new(),
-            block_listeners: HashMap::new(),
+            pools: HashMap::new(),
+            block_hubs: HashMap::new(),
+            supervisors: HashMap::new(),
+            health: HashMap::new(),
+            tasks: HashMap::new(),
         }
     }
 
-    /// Retorna o recebedor (Receiver) de blocos correspondente ao usuário especificado pelo `user_id`.
+    /// Cria uma nova inscrição no stream de blocos do usuário `user_id` na chain `chain_id`.
     ///
-    /// Para ouvir os eventos disparados, você pode usar o `Receiver` retornado por esta função para
-    /// consumir os blocos conforme forem sendo enviados.
+    /// Cada chamada retorna uma `BlockSubscription` independente, então qualquer número de consumidores
+    /// pode ouvir a mesma conexão. Para cancelar a inscrição basta descartar a `BlockSubscription`.
     ///
     /// Exemplo:
     ///
     /// ```rust
-    /// if let Some(mut receiver) = get_block_listener(user_id) {
+    /// if let Some(mut subscription) = subscribe_blocks(user_id, chain_id) {
     ///     tokio::spawn(async move {
-    ///         while let Ok(block) = receiver.recv().await {
-    ///             println!("Recebi um bloco: {:?}", block);
+    ///         while let Some(event) = subscription.recv().await {
+    ///             match event {
+    ///                 BlockEvent::Block(block) => println!("Recebi um bloco: {:?}", block),
+    ///                 BlockEvent::Lagged(missed) => println!("Blocos perdidos: {:?}", missed),
+    ///             }
     ///         }
     ///     });
     /// } else {
@@ -39,73 +61,116 @@ impl EthersRepository {
     /// }
     /// ```
     ///
-    /// - A função retorna `Some(receiver)` se o `user_id` estiver registrado com um listener.
+    /// - A função retorna `Some(subscription)` se o `user_id` possuir conexão na chain.
     /// - Caso contrário, retorna `None`, indicando que o `user_id` não possui um listener configurado.
-    pub fn get_block_listener(&mut self, user_id: i32) -> Option<Receiver<Block<H256>>> {
-        self.block_listeners.remove(&user_id)
+    /// - Um consumidor que ficar mais de `BLOCK_HUB_CAPACITY` blocos atrás recebe `BlockEvent::Lagged`
+    ///   com as alturas perdidas e continua a partir do bloco mais antigo ainda disponível.
+    pub fn subscribe_blocks(&self, user_id: i32, chain_id: u64) -> Option<BlockSubscription> {
+        self.block_hubs.get(&(user_id, chain_id)).map(|hub| hub.subscribe())
     }
 
-    /// Essa função registra e armazena um `Provider<Ws>` (conexão WebSocket) associado a um usuário específico.
+    /// Quantidade de consumidores inscritos no stream de blocos da conexão.
+    pub fn get_block_subscribers(&self, user_id: i32, chain_id: u64) -> usize {
+        self.block_hubs
+            .get(&(user_id, chain_id))
+            .map(|hub| hub.subscriber_count())
+            .unwrap_or(0)
+    }
+
+    /// Essa função registra e armazena o `RpcPool` (conexões WebSocket, HTTP ou IPC) associado a um usuário específico.
     ///
     /// **Passo a passo**:
-    /// 1. A conexão `provider` é encapsulada dentro de um `Arc`, que é um ponteiro inteligente
-    ///    usado para permitir compartilhamento seguro de dados entre threads.
-    /// 2. A conexão encapsulada (`Arc<Provider<Ws>>`) é inserida no mapa `connections`,
-    ///    associando-a ao identificador do usuário `user_id`.
+    /// 1. O pool é armazenado no mapa `pools`, na chave (`user_id`, chain do pool), ficando disponível
+    ///    para leituras com failover e quórum.
+    /// 2. Se o usuário ainda não possui conexão nessa chain, um canal `watch` é criado com o provider primário
+    ///    do pool como valor inicial e inserido no mapa `connections`.
+    /// 3. Se o usuário já possui conexão nessa chain, o novo provider primário substitui o anterior no mesmo canal, notificando
+    ///    todos que estejam observando a conexão via `watch_connection` (por exemplo, assinaturas de logs
+    ///    que precisam ser refeitas após uma reconexão ou failover).
     ///
     /// Esse método é útil para gerenciar múltiplas conexões de usuários, permitindo que cada um
-    /// tenha sua própria instância de `Provider<Ws>` armazenada e pronta para uso.
-    pub fn apply_connection(&mut self, user_id: i32, provider: Provider<Ws>) {
-        let provider = Arc::new(provider);
-        self.connections.insert(user_id, provider);
+    /// tenha seus próprios providers armazenados e prontos para uso.
+    pub fn apply_connection(&mut self, user_id: i32, pool: RpcPool) {
+        let key = (user_id, pool.chain_id());
+        let provider = pool.primary();
+        self.pools.insert(key, pool);
+
+        match self.connections.get(&key) {
+            Some(sender) => {
+                sender.send_replace(provider);
+            }
+            None => {
+                let (sender, _) = watch::channel(provider);
+                self.connections.insert(key, sender);
+            }
+        }
+    }
+
+    /// Substitui o pool da conexão mantendo o provider primário, sem notificar os observadores de
+    /// `watch_connection`. Usado pelo `ConnectionSupervisor` ao devolver endpoints reconectados ao
+    /// pool. Retorna `false`, sem alterar nada, se o primário registrado não for mais o do `pool`
+    /// (a conexão foi substituída ou removida nesse meio tempo).
+    pub fn update_pool(&mut self, user_id: i32, pool: RpcPool) -> bool {
+        match self.pools.get_mut(&(user_id, pool.chain_id())) {
+            Some(current) if Arc::ptr_eq(&current.primary(), &pool.primary()) => {
+                *current = pool;
+                true
+            }
+            _ => false,
+        }
     }
 
-    /// Essa função aplica um "listener" de blocos para o usuário especificado, utilizando um `Provider<Ws>` como fonte.
+    /// Retorna o `BlockHub` da conexão, criando-o na primeira chamada.
     ///
-    /// - Um canal de broadcast é criado com capacidade de 10 mensagens, onde múltiplos receptores podem subscrever para receber blocos.
-    /// - Um clone do canal de transmissão (`tx`) é criado para ser usado dentro do escopo da tarefa assíncrona.
+    /// O hub sobrevive à troca de supervisor (novo `apply_rpc` ou `replace_rpc` na mesma chain),
+    /// de modo que quem já consome os blocos continua recebendo após a troca de endpoints.
+    pub fn apply_block_hub(&mut self, user_id: i32, chain_id: u64) -> BlockHub {
+        self.block_hubs.entry((user_id, chain_id)).or_default().clone()
+    }
+
+    /// Retorna a saúde da conexão, compartilhada com o `ConnectionSupervisor`, criando-a se necessário.
+    pub fn apply_health(&mut self, user_id: i32, chain_id: u64) -> Arc<Mutex<ConnectionHealth>> {
+        self.health.entry((user_id, chain_id)).or_default().clone()
+    }
+
+    pub fn get_health(&self, user_id: i32, chain_id: u64) -> Option<ConnectionHealth> {
+        self.health
+            .get(&(user_id, chain_id))
+            .map(|health| health.lock().unwrap().clone())
+    }
+
+    /// Registra uma task que depende da conexão (listeners de eventos, deploys etc.), para que seja
+    /// abortada quando a conexão for removida.
+    pub fn apply_task(&mut self, user_id: i32, chain_id: u64, handle: JoinHandle<()>) {
+        let tasks = self.tasks.entry((user_id, chain_id)).or_default();
+        tasks.retain(|task| !task.is_finished());
+        tasks.push(handle);
+    }
+
+    /// Registra a task do `ConnectionSupervisor` do usuário, abortando a anterior caso exista.
+    pub fn apply_supervisor(&mut self, user_id: i32, chain_id: u64, handle: JoinHandle<()>) {
+        if let Some(previous) = self.supervisors.insert((user_id, chain_id), handle) {
+            previous.abort();
+        }
+    }
+
+    /// Retorna um `watch::Receiver` que sempre contém o provider atual do usuário.
     ///
-    /// **Passo a passo do funcionamento**:
-    /// 1. Um clone do `Provider<Ws>` (`block_subscriber`) é usado para assinar os blocos em tempo real.
-    /// 2. Uma nova tarefa assíncrona é criada com `spawn`. Dentro dela:
-    ///     - O stream de blocos é iniciado usando o método `subscribe_blocks()` do provider.
-    ///     - O método `for_each` percorre os blocos recebidos.
-    ///     - Para cada bloco:
-    ///         * Um clone do `tx` é criado (`tx_clone_inner`) para garantir que a transmissão dentro do escopo seja possível.
-    ///         * O bloco é transmitido para todos que estejam "ouvindo" o canal de broadcast.
-    /// 3. No final, o receptor (`rx1`) do canal de broadcast é armazenado no mapa `block_listeners` para o usuário correspondente.
-    ///    Isso permite que múltiplos consumidores recebam os blocos em paralelo.
-    pub async fn apply_block_listener(&mut self, user_id: i32, provider: Provider<Ws>) {
-        let block_subscriber = provider.clone();
-
-        let (tx, rx1) = tokio::sync::mpsc::channel(20);
-        let tx_clone = tx.clone(); // Faz o primeiro clone
-
-        spawn(async move {
-            let stream = block_subscriber.subscribe_blocks().await.unwrap();
-
-            stream
-                .for_each(move |block| {
-                    let tx_clone_inner = tx_clone.clone(); // Criamos o clone de tx_clone dentro do escopo do for_each
-                    async move {
-                        tx_clone_inner.send(block).await.unwrap();
-                    }
-                })
-                .await;
-        });
-
-        self.block_listeners.insert(user_id, rx1);
-    }
-
-    /// Retorna a conexão WebSocket (`Arc<Provider<Ws>>`) associada ao `user_id` fornecido.
+    /// Útil para tasks de longa duração: `changed()` resolve sempre que a conexão é substituída
+    /// (reconexão ou novo `apply_rpc`), e retorna erro quando a conexão deixa de existir.
+    pub fn watch_connection(&self, user_id: i32, chain_id: u64) -> Option<watch::Receiver<Arc<RpcProvider>>> {
+        self.connections.get(&(user_id, chain_id)).map(|sender| sender.subscribe())
+    }
+
+    /// Retorna a conexão (`Arc<RpcProvider>`) associada ao `user_id` na chain `chain_id`.
     ///
-    /// Essa conexão pode ser utilizada para interagir com um nó Ethereum via WebSocket, permitindo
+    /// Essa conexão pode ser utilizada para interagir com um nó Ethereum via WebSocket, HTTP ou IPC, permitindo
     /// realizar chamadas ou escutar eventos relacionados a esse nó.
     ///
     /// Exemplo:
     ///
     /// ```rust
-    /// if let Some(provider) = get_connection(user_id) {
+    /// if let Some(provider) = get_connection(user_id, chain_id) {
     ///     // Usa a conexão para fazer chamadas, por exemplo:
     ///     let balance = provider.get_balance("endereco_ethereum", None).await.unwrap();
     ///     println!("Saldo: {}", balance);
@@ -114,12 +179,61 @@ impl EthersRepository {
     /// }
     /// ```
     ///
-    /// - A função retorna `Some(Arc<Provider<Ws>>)` se o `user_id` estiver associado a uma conexão existente.
+    /// - A função retorna `Some(Arc<RpcProvider>)` se o `user_id` estiver associado a uma conexão existente na chain.
     /// - Caso contrário, retorna `None`, indicando que o `user_id` não possui uma conexão registrada.
     ///
     /// **Nota:** A conexão é encapsulada em um `Arc` (`Atomic Reference Counter`), permitindo que ela seja
     /// compartilhada de forma segura entre várias threads ou tasks assíncronas sem a necessidade de clones caros.
-    pub fn get_connection(&self, user_id: i32) -> Option<Arc<Provider<Ws>>> {
-        self.connections.get(&user_id).cloned()
+    pub fn get_connection(&self, user_id: i32, chain_id: u64) -> Option<Arc<RpcProvider>> {
+        self.connections
+            .get(&(user_id, chain_id))
+            .map(|sender| sender.borrow().clone())
+    }
+
+    /// Retorna todas as conexões do usuário, em ordem de prioridade, para leituras com failover ou quórum.
+    pub fn get_pool(&self, user_id: i32, chain_id: u64) -> Option<RpcPool> {
+        self.pools.get(&(user_id, chain_id)).cloned()
+    }
+
+    /// Indica se a conexão existe, conectada ou ainda aguardando a primeira conexão do supervisor.
+    pub fn has_connection(&self, user_id: i32, chain_id: u64) -> bool {
+        let key = (user_id, chain_id);
+        self.pools.contains_key(&key) || self.supervisors.contains_key(&key)
+    }
+
+    /// Retorna as chains em que o usuário possui conexão.
+    pub fn get_chains(&self, user_id: i32) -> Vec<u64> {
+        let mut chains: Vec<u64> = self
+            .pools
+            .keys()
+            .filter(|(id, _)| *id == user_id)
+            .map(|(_, chain_id)| *chain_id)
+            .collect();
+        chains.sort();
+        chains
+    }
+
+    /// Remove a conexão do usuário na chain, encerrando tudo o que depende dela.
+    ///
+    /// **Passo a passo**:
+    /// 1. A task do `ConnectionSupervisor` é abortada, encerrando a fonte de blocos.
+    /// 2. As tasks registradas via `apply_task` são abortadas.
+    /// 3. O canal `watch` da conexão é descartado; observadores de `watch_connection` recebem erro em `changed()`.
+    /// 4. Pool, hub de blocos e saúde são removidos; as inscrições do hub recebem `None`.
+    ///
+    /// Retorna `false` se o usuário não possuía conexão na chain, nem uma aguardando a primeira
+    /// conexão do supervisor.
+    pub fn remove_connection(&mut self, user_id: i32, chain_id: u64) -> bool {
+        let key = (user_id, chain_id);
+        let supervised = self.supervisors.remove(&key).map(|supervisor| supervisor.abort()).is_some();
+
+        for task in self.tasks.remove(&key).unwrap_or_default() {
+            task.abort();
+        }
+
+        self.block_hubs.remove(&key);
+        self.health.remove(&key);
+        self.connections.remove(&key);
+        self.pools.remove(&key).is_some() || supervised
     }
 }
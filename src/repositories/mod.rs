@@ -1,3 +1,5 @@
+pub mod backfill_repository;
+pub mod block_hub;
 pub mod ethers_repository;
 pub mod redis_repository;
 pub mod elastic_repository;
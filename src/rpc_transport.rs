@@ -0,0 +1,503 @@
+use async_trait::async_trait;
+use ethers::providers::{
+    Http, Ipc, JsonRpcClient, Middleware, Provider, ProviderError, PubsubClient, Ws,
+};
+use ethers::types::U256;
+use futures::channel::mpsc::UnboundedReceiver;
+use futures::future::join_all;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::value::RawValue;
+use std::fmt::{Debug, Display};
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Tempo máximo aguardado na abertura de uma conexão com o nó.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Provider usado em todo o serviço, independente do transporte escolhido pelo usuário.
+pub type RpcProvider = Provider<RpcTransport>;
+
+/// Transporte JSON-RPC escolhido a partir do esquema do endpoint:
+///
+/// - `ws://` / `wss://` → WebSocket, com suporte a assinaturas (`eth_subscribe`).
+/// - `http://` / `https://` → HTTP, sem assinaturas; blocos e logs devem ser obtidos por polling.
+/// - qualquer outro valor é tratado como caminho de um socket IPC, com suporte a assinaturas.
+#[derive(Debug, Clone)]
+pub enum RpcTransport {
+    Ws(Ws),
+    Http(Http),
+    Ipc(Ipc),
+}
+
+impl RpcTransport {
+    pub async fn connect(endpoint: &str) -> Result<Self, ProviderError> {
+        if endpoint.starts_with("ws://") || endpoint.starts_with("wss://") {
+            Ok(RpcTransport::Ws(Ws::connect(endpoint).await?))
+        } else if endpoint.starts_with("http://") || endpoint.starts_with("https://") {
+            let http = Http::from_str(endpoint)
+                .map_err(|e| ProviderError::CustomError(format!("Endpoint inválido: {}", e)))?;
+            Ok(RpcTransport::Http(http))
+        } else {
+            Ok(RpcTransport::Ipc(Ipc::connect(endpoint).await?))
+        }
+    }
+
+    /// Abre o transporte e valida que o nó responde a um `eth_blockNumber` antes de devolver o
+    /// provider. Sem essa verificação um endpoint HTTP inválido só falharia na primeira chamada.
+    pub async fn connect_provider(endpoint: &str) -> Result<RpcProvider, ProviderError> {
+        let connect = async {
+            let provider = Provider::new(Self::connect(endpoint).await?);
+            provider.get_block_number().await?;
+            Ok(provider)
+        };
+
+        tokio::time::timeout(CONNECTION_TIMEOUT, connect)
+            .await
+            .unwrap_or_else(|_| {
+                Err(ProviderError::CustomError(format!(
+                    "Tempo esgotado ao conectar no endpoint {}",
+                    endpoint
+                )))
+            })
+    }
+
+    /// Indica se o transporte suporta `eth_subscribe`. Quando `false`, os listeners devem usar polling.
+    pub fn supports_pubsub(&self) -> bool {
+        !matches!(self, RpcTransport::Http(_))
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for RpcTransport {
+    type Error = ProviderError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        match self {
+            RpcTransport::Ws(ws) => Ok(ws.request(method, params).await?),
+            RpcTransport::Http(http) => Ok(http.request(method, params).await?),
+            RpcTransport::Ipc(ipc) => Ok(ipc.request(method, params).await?),
+        }
+    }
+}
+
+impl PubsubClient for RpcTransport {
+    type NotificationStream = UnboundedReceiver<Box<RawValue>>;
+
+    fn subscribe<T: Into<U256>>(&self, id: T) -> Result<Self::NotificationStream, Self::Error> {
+        match self {
+            RpcTransport::Ws(ws) => Ok(ws.subscribe(id)?),
+            RpcTransport::Ipc(ipc) => Ok(ipc.subscribe(id)?),
+            RpcTransport::Http(_) => Err(ProviderError::UnsupportedRPC),
+        }
+    }
+
+    fn unsubscribe<T: Into<U256>>(&self, id: T) -> Result<(), Self::Error> {
+        match self {
+            RpcTransport::Ws(ws) => Ok(ws.unsubscribe(id)?),
+            RpcTransport::Ipc(ipc) => Ok(ipc.unsubscribe(id)?),
+            RpcTransport::Http(_) => Err(ProviderError::UnsupportedRPC),
+        }
+    }
+}
+
+/// Por quanto tempo um endpoint que falhou fica no fim da ordem de failover.
+const FAILURE_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Latência acima da qual um endpoint saudável é tentado depois dos endpoints rápidos no failover.
+const SLOW_ENDPOINT_LATENCY: Duration = Duration::from_secs(2);
+
+/// Saúde de um endpoint do pool, atualizada a cada chamada feita via `RpcPool::failover`.
+#[derive(Clone, Default, Debug)]
+pub struct EndpointHealth {
+    pub latency: Option<Duration>,
+    pub last_error: Option<String>,
+    pub failed_at: Option<Instant>,
+    pub failures: u32,
+}
+
+impl EndpointHealth {
+    fn recently_failed(&self) -> bool {
+        self.failed_at.is_some_and(|at| at.elapsed() < FAILURE_COOLDOWN)
+    }
+
+    fn is_slow(&self) -> bool {
+        self.latency.is_some_and(|latency| latency > SLOW_ENDPOINT_LATENCY)
+    }
+}
+
+#[derive(Clone)]
+struct PoolEntry {
+    /// Posição do endpoint na lista configurada pelo usuário.
+    priority: usize,
+    endpoint: String,
+    provider: Arc<RpcProvider>,
+    health: Arc<Mutex<EndpointHealth>>,
+}
+
+/// Conjunto ordenado de conexões de um usuário, do endpoint de maior prioridade para o de menor.
+///
+/// O primeiro provider conectado é o primário: ele alimenta as assinaturas de blocos e logs e é o
+/// primeiro a ser usado nas leituras. Os demais servem de failover para leituras e de votantes nas
+/// leituras por quórum. Todos os providers do pool pertencem à mesma chain (`eth_chainId`).
+///
+/// Endpoints que não conectaram ficam em `unavailable`; o `ConnectionSupervisor` tenta conectá-los
+/// de novo periodicamente e os devolve ao pool via `with_endpoint`.
+#[derive(Clone)]
+pub struct RpcPool {
+    chain_id: u64,
+    providers: Vec<PoolEntry>,
+    unavailable: Vec<(usize, String)>,
+}
+
+impl RpcPool {
+    /// Conecta todos os endpoints em paralelo, preservando a ordem de prioridade, e detecta a chain
+    /// de cada um via `eth_chainId`. A chain do primeiro endpoint conectado define a chain do pool;
+    /// endpoints que falharem ou que pertençam a outra chain ficam em `unavailable`. O erro só é
+    /// retornado se nenhum endpoint conectar.
+    pub async fn connect(endpoints: &[String]) -> Result<Self, ProviderError> {
+        let results = join_all(endpoints.iter().map(|endpoint| Self::connect_endpoint(endpoint))).await;
+
+        let mut chain = None;
+        let mut providers = Vec::new();
+        let mut unavailable = Vec::new();
+        let mut last_error = None;
+
+        for (priority, (endpoint, result)) in endpoints.iter().zip(results).enumerate() {
+            match result {
+                Ok((chain_id, provider)) if *chain.get_or_insert(chain_id) == chain_id => {
+                    providers.push(PoolEntry {
+                        priority,
+                        endpoint: endpoint.clone(),
+                        provider: Arc::new(provider),
+                        health: Default::default(),
+                    })
+                }
+                Ok((chain_id, _)) => {
+                    log::warn!(
+                        "Endpoint {} ignorado: chain {} diferente da chain {:?} do pool",
+                        endpoint,
+                        chain_id,
+                        chain
+                    );
+                    unavailable.push((priority, endpoint.clone()));
+                }
+                Err(e) => {
+                    log::warn!("Endpoint {} indisponível: {}", endpoint, e);
+                    unavailable.push((priority, endpoint.clone()));
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        match chain {
+            Some(chain_id) => Ok(RpcPool { chain_id, providers, unavailable }),
+            None => Err(last_error.unwrap_or_else(|| {
+                ProviderError::CustomError("Nenhum endpoint informado".to_string())
+            })),
+        }
+    }
+
+    async fn connect_endpoint(endpoint: &str) -> Result<(u64, RpcProvider), ProviderError> {
+        let provider = RpcTransport::connect_provider(endpoint).await?;
+        let chain_id = provider.get_chainid().await?.as_u64();
+        Ok((chain_id, provider))
+    }
+
+    /// Tenta conectar os endpoints indisponíveis. Retorna um novo pool com os que conectaram na
+    /// chain do pool, inseridos pela prioridade original, ou `None` se nenhum conectou. O primário
+    /// não muda, para não trocar a fonte de blocos do `ConnectionSupervisor`.
+    pub async fn with_recovered(&self) -> Option<Self> {
+        let results = join_all(self.unavailable.iter().map(|(_, endpoint)| Self::connect_endpoint(endpoint))).await;
+        let mut pool = self.clone();
+        let mut recovered = false;
+
+        pool.unavailable.clear();
+
+        for ((priority, endpoint), result) in self.unavailable.iter().zip(results) {
+            match result {
+                Ok((chain_id, provider)) if chain_id == self.chain_id => {
+                    log::info!("Endpoint {} reconectado na chain {}", endpoint, chain_id);
+                    let position = pool.providers[1..]
+                        .iter()
+                        .position(|entry| entry.priority > *priority)
+                        .map(|position| position + 1)
+                        .unwrap_or(pool.providers.len());
+                    pool.providers.insert(position, PoolEntry {
+                        priority: *priority,
+                        endpoint: endpoint.clone(),
+                        provider: Arc::new(provider),
+                        health: Default::default(),
+                    });
+                    recovered = true;
+                }
+                Ok((chain_id, _)) => {
+                    log::warn!("Endpoint {} responde pela chain {} em vez de {}", endpoint, chain_id, self.chain_id);
+                    pool.unavailable.push((*priority, endpoint.clone()));
+                }
+                Err(e) => {
+                    log::debug!("Endpoint {} continua indisponível: {}", endpoint, e);
+                    pool.unavailable.push((*priority, endpoint.clone()));
+                }
+            }
+        }
+
+        recovered.then_some(pool)
+    }
+
+    pub fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    pub fn primary(&self) -> Arc<RpcProvider> {
+        self.providers[0].provider.clone()
+    }
+
+    pub fn primary_endpoint(&self) -> &str {
+        &self.providers[0].endpoint
+    }
+
+    /// Retorna os pares (endpoint, provider) em ordem de prioridade.
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &Arc<RpcProvider>)> {
+        self.providers
+            .iter()
+            .map(|entry| (entry.endpoint.as_str(), &entry.provider))
+    }
+
+    pub fn providers(&self) -> impl Iterator<Item = &Arc<RpcProvider>> {
+        self.providers.iter().map(|entry| &entry.provider)
+    }
+
+    /// Endpoints configurados que não estão conectados, em ordem de prioridade.
+    pub fn unavailable(&self) -> impl Iterator<Item = &str> {
+        self.unavailable.iter().map(|(_, endpoint)| endpoint.as_str())
+    }
+
+    /// Retorna a saúde registrada para o endpoint, se ele estiver conectado.
+    pub fn health(&self, endpoint: &str) -> Option<EndpointHealth> {
+        self.providers
+            .iter()
+            .find(|entry| entry.endpoint == endpoint)
+            .map(|entry| entry.health.lock().unwrap().clone())
+    }
+
+    pub fn size(&self) -> usize {
+        self.providers.len()
+    }
+
+    /// Ordem de tentativa do failover: endpoints sem falha nos últimos `FAILURE_COOLDOWN` antes dos
+    /// que falharam, e, entre esses, os com latência até `SLOW_ENDPOINT_LATENCY` antes dos lentos.
+    /// Empates seguem a ordem de prioridade.
+    fn failover_order(&self) -> Vec<&PoolEntry> {
+        let mut entries: Vec<&PoolEntry> = self.providers.iter().collect();
+        entries.sort_by_cached_key(|entry| {
+            let health = entry.health.lock().unwrap();
+            (health.recently_failed(), health.is_slow())
+        });
+        entries
+    }
+
+    /// Executa `operation` nos providers do pool, na ordem de `failover_order`, até o primeiro
+    /// sucesso. A latência ou o erro de cada tentativa fica registrado na saúde do endpoint.
+    /// Retorna o último erro se todos falharem.
+    pub async fn failover<T, F, Fut>(&self, operation: F) -> Result<T, ProviderError>
+    where
+        F: Fn(Arc<RpcProvider>) -> Fut,
+        Fut: Future<Output = Result<T, ProviderError>>,
+    {
+        self.failover_with(operation, |_| true).await
+    }
+
+    /// Como `failover`, mas só tenta o próximo provider quando `retriable` aceitar o erro. Os demais
+    /// erros (reverts, por exemplo, que seriam iguais em qualquer nó) são retornados na hora e
+    /// contam como resposta do endpoint na saúde.
+    pub async fn failover_with<T, E, F, Fut, R>(&self, operation: F, retriable: R) -> Result<T, E>
+    where
+        E: Display,
+        F: Fn(Arc<RpcProvider>) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        R: Fn(&E) -> bool,
+    {
+        let mut last_error = None;
+
+        for entry in self.failover_order() {
+            let started_at = Instant::now();
+            let result = operation(entry.provider.clone()).await;
+
+            match result {
+                Err(e) if retriable(&e) => {
+                    log::warn!("Falha no endpoint {}, tentando o próximo: {}", entry.endpoint, e);
+                    entry.record_failure(&e);
+                    last_error = Some(e);
+                }
+                result => {
+                    entry.record_success(started_at);
+                    return result;
+                }
+            }
+        }
+
+        Err(last_error.expect("RpcPool nunca é vazio"))
+    }
+
+    /// Executa `operation` em todos os providers do pool em paralelo, retornando os resultados em
+    /// ordem de prioridade. Como em `failover_with`, os erros aceitos por `failure` ficam registrados
+    /// na saúde do endpoint e os demais resultados contam como resposta.
+    pub async fn broadcast<T, E, F, Fut, R>(&self, operation: F, failure: R) -> Vec<Result<T, E>>
+    where
+        E: Display,
+        F: Fn(Arc<RpcProvider>) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        R: Fn(&E) -> bool,
+    {
+        let calls = self.providers.iter().map(|entry| {
+            let call = operation(entry.provider.clone());
+            let failure = &failure;
+            async move {
+                let started_at = Instant::now();
+                let result = call.await;
+                match &result {
+                    Err(e) if failure(e) => entry.record_failure(e),
+                    _ => entry.record_success(started_at),
+                }
+                result
+            }
+        });
+
+        join_all(calls).await
+    }
+}
+
+impl PoolEntry {
+    fn record_success(&self, started_at: Instant) {
+        let mut health = self.health.lock().unwrap();
+        health.latency = Some(started_at.elapsed());
+        health.failed_at = None;
+    }
+
+    fn record_failure(&self, error: &impl Display) {
+        let mut health = self.health.lock().unwrap();
+        health.last_error = Some(error.to_string());
+        health.failed_at = Some(Instant::now());
+        health.failures += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(endpoints: &[&str]) -> RpcPool {
+        let providers = endpoints
+            .iter()
+            .enumerate()
+            .map(|(priority, endpoint)| PoolEntry {
+                priority,
+                endpoint: endpoint.to_string(),
+                provider: Arc::new(Provider::new(RpcTransport::Http(Http::from_str(endpoint).unwrap()))),
+                health: Default::default(),
+            })
+            .collect();
+
+        RpcPool { chain_id: 1, providers, unavailable: Vec::new() }
+    }
+
+    fn endpoint_of(pool: &RpcPool, provider: &Arc<RpcProvider>) -> String {
+        pool.entries()
+            .find(|(_, candidate)| Arc::ptr_eq(candidate, provider))
+            .map(|(endpoint, _)| endpoint.to_string())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn failover_moves_failed_endpoints_to_the_end() {
+        let pool = pool(&["http://a.test", "http://b.test", "http://c.test"]);
+        let failing = "http://a.test".to_string();
+
+        let attempts = Mutex::new(Vec::new());
+        let result = pool
+            .failover(|provider| {
+                let endpoint = endpoint_of(&pool, &provider);
+                attempts.lock().unwrap().push(endpoint.clone());
+                let failing = failing.clone();
+                async move {
+                    if endpoint == failing {
+                        return Err(ProviderError::CustomError("falhou".to_string()));
+                    }
+                    Ok(endpoint)
+                }
+            })
+            .await;
+        assert_eq!(result.unwrap(), "http://b.test");
+        assert_eq!(*attempts.lock().unwrap(), ["http://a.test", "http://b.test"]);
+
+        let health = pool.health("http://a.test").unwrap();
+        assert_eq!(health.failures, 1);
+        assert_eq!(health.last_error.as_deref(), Some("custom error: falhou"));
+        assert!(pool.health("http://b.test").unwrap().latency.is_some());
+
+        let order: Vec<&str> = pool.failover_order().iter().map(|entry| entry.endpoint.as_str()).collect();
+        assert_eq!(order, ["http://b.test", "http://c.test", "http://a.test"]);
+        assert_eq!(pool.primary_endpoint(), "http://a.test");
+    }
+
+    #[tokio::test]
+    async fn failover_with_returns_non_retriable_errors_immediately() {
+        let pool = pool(&["http://a.test", "http://b.test"]);
+        let attempts = Mutex::new(0);
+
+        let result: Result<(), String> = pool
+            .failover_with(
+                |_| {
+                    *attempts.lock().unwrap() += 1;
+                    async { Err("revert".to_string()) }
+                },
+                |error| error != "revert",
+            )
+            .await;
+
+        assert_eq!(result, Err("revert".to_string()));
+        assert_eq!(*attempts.lock().unwrap(), 1);
+        assert_eq!(pool.health("http://a.test").unwrap().failures, 0);
+    }
+
+    #[tokio::test]
+    async fn broadcast_records_failures_of_each_endpoint() {
+        let pool = pool(&["http://a.test", "http://b.test"]);
+
+        let results: Vec<Result<String, String>> = pool
+            .broadcast(
+                |provider| {
+                    let endpoint = endpoint_of(&pool, &provider);
+                    async move {
+                        if endpoint == "http://b.test" {
+                            return Err("timeout".to_string());
+                        }
+                        Ok(endpoint)
+                    }
+                },
+                |_| true,
+            )
+            .await;
+
+        assert_eq!(results, [Ok("http://a.test".to_string()), Err("timeout".to_string())]);
+        assert!(pool.health("http://a.test").unwrap().latency.is_some());
+        assert_eq!(pool.health("http://b.test").unwrap().failures, 1);
+    }
+
+    #[test]
+    fn slow_endpoints_are_tried_after_fast_ones() {
+        let pool = pool(&["http://a.test", "http://b.test"]);
+        pool.providers[0].health.lock().unwrap().latency = Some(SLOW_ENDPOINT_LATENCY * 2);
+        pool.providers[1].health.lock().unwrap().latency = Some(Duration::from_millis(50));
+
+        let order: Vec<&str> = pool.failover_order().iter().map(|entry| entry.endpoint.as_str()).collect();
+        assert_eq!(order, ["http://b.test", "http://a.test"]);
+    }
+}
@@ -0,0 +1,99 @@
+use crate::repositories::elastic_repository::{ElasticRepository, ElasticRepositoryError, SearchResult};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum GetBlocksError {
+    #[error("Consulta inválida: {0}")]
+    InvalidQuery(String),
+    #[error(transparent)]
+    ElasticError(#[from] ElasticRepositoryError),
+}
+
+/// Filtros da consulta ao índice `blocks`: um número, um hash ou um intervalo de tempo
+/// (`from_timestamp` e/ou `to_timestamp`, em segundos, inclusivos). `chain_id` restringe a uma
+/// chain; `cursor` continua uma consulta anterior.
+#[derive(Deserialize)]
+pub struct BlocksQuery {
+    pub chain_id: Option<u64>,
+    pub number: Option<u64>,
+    pub hash: Option<String>,
+    pub from_timestamp: Option<u64>,
+    pub to_timestamp: Option<u64>,
+    pub cursor: Option<String>,
+}
+
+pub struct GetBlocksService {
+    elastic_repository: Arc<ElasticRepository>,
+}
+
+impl GetBlocksService {
+    pub fn new(elastic_repository: Arc<ElasticRepository>) -> Self {
+        GetBlocksService { elastic_repository }
+    }
+
+    fn build_query(query: &BlocksQuery) -> Result<Value, GetBlocksError> {
+        let time_range = query.from_timestamp.is_some() || query.to_timestamp.is_some();
+        let criteria = [query.number.is_some(), query.hash.is_some(), time_range];
+
+        if criteria.iter().filter(|criterion| **criterion).count() != 1 {
+            return Err(GetBlocksError::InvalidQuery(
+                "informe apenas um entre `number`, `hash` e `from_timestamp`/`to_timestamp`".to_string(),
+            ));
+        }
+
+        if let (Some(from), Some(to)) = (query.from_timestamp, query.to_timestamp) {
+            if from > to {
+                return Err(GetBlocksError::InvalidQuery(format!(
+                    "from_timestamp {} maior que to_timestamp {}",
+                    from, to
+                )));
+            }
+        }
+
+        let mut must = Vec::new();
+
+        if let Some(number) = query.number {
+            must.push(json!({ "term": { "number": number } }));
+        }
+        if let Some(hash) = &query.hash {
+            must.push(json!({ "match": { "hash": hash } }));
+        }
+        if time_range {
+            let mut range = serde_json::Map::new();
+            if let Some(from) = query.from_timestamp {
+                range.insert("gte".to_string(), json!(from));
+            }
+            if let Some(to) = query.to_timestamp {
+                range.insert("lte".to_string(), json!(to));
+            }
+            must.push(json!({ "range": { "timestamp": range } }));
+        }
+        if let Some(chain_id) = query.chain_id {
+            must.push(json!({ "term": { "chain_id": chain_id } }));
+        }
+
+        Ok(json!({
+            "bool": {
+                "must": must
+            }
+        }))
+    }
+
+    pub async fn exec(&self, query: BlocksQuery) -> Result<SearchResult<Value>, GetBlocksError> {
+        // Com `cursor`, a consulta já está no scroll do Elasticsearch e os filtros são ignorados.
+        let search = match query.cursor {
+            Some(_) => None,
+            None => Some(Self::build_query(&query)?),
+        };
+
+        let result = self
+            .elastic_repository
+            .search_with_pagination::<Value>("blocks", search, 1000, query.cursor)
+            .await?;
+
+        Ok(result)
+    }
+}
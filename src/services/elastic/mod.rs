@@ -1,3 +1,4 @@
+pub mod get_blocks_service;
 pub mod get_erc20_contracts_service;
 pub mod get_labels_service;
 pub mod get_transactions_service;
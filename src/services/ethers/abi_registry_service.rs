@@ -0,0 +1,225 @@
+use crate::repositories::redis_repository::RedisRepository;
+use crate::utils::abi_utils::AbiUtils;
+use ethers::abi::Abi;
+use ethers::types::Address;
+use redis::{AsyncCommands, RedisError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Hash do Redis com as ABIs registradas.
+const ABIS_KEY: &str = "abis";
+
+#[derive(Error, Debug)]
+pub enum AbiRegistryError {
+    #[error("ABI inválida: {0}")]
+    InvalidAbi(String),
+    #[error("Nenhuma ABI registrada para {0}")]
+    NotFound(String),
+    #[error("Erro no Redis: {0}")]
+    RedisError(#[from] RedisError),
+}
+
+/// Identificação de uma ABI no registro: um nome livre (`{"id": "erc20"}`) ou o contrato em que
+/// ela está implantada (`{"chain_id": 1, "address": "0x..."}`).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(untagged)]
+pub enum AbiKey {
+    Id { id: String },
+    Contract { chain_id: u64, address: Address },
+}
+
+impl AbiKey {
+    /// Campo do hash `abis`: `id:<nome>` ou `contract:<chain_id>:<endereço>`.
+    fn field(&self) -> String {
+        match self {
+            AbiKey::Id { id } => format!("id:{}", id),
+            AbiKey::Contract { chain_id, address } => format!("contract:{}:{:?}", chain_id, address),
+        }
+    }
+
+    fn parse_field(field: &str) -> Option<Self> {
+        match field.split_once(':')? {
+            ("id", id) => Some(AbiKey::Id { id: id.to_string() }),
+            ("contract", contract) => {
+                let (chain_id, address) = contract.split_once(':')?;
+                Some(AbiKey::Contract {
+                    chain_id: chain_id.parse().ok()?,
+                    address: address.parse().ok()?,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for AbiKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AbiKey::Id { id } => write!(f, "o id {}", id),
+            AbiKey::Contract { chain_id, address } => write!(f, "o contrato {:?} na chain {}", address, chain_id),
+        }
+    }
+}
+
+/// Origem da ABI de uma requisição: o JSON da ABI (`abi`) ou o nome de uma ABI registrada
+/// (`abi_id`). Sem nenhum dos dois, a ABI registrada para o contrato chamado é usada.
+#[derive(Deserialize, Clone, Default)]
+pub struct AbiSource {
+    #[serde(default)]
+    pub abi: Option<String>,
+    #[serde(default)]
+    pub abi_id: Option<String>,
+}
+
+impl AbiSource {
+    pub fn is_empty(&self) -> bool {
+        self.abi.is_none() && self.abi_id.is_none()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RegisterAbi {
+    #[serde(flatten)]
+    pub key: AbiKey,
+    pub abi: String,
+}
+
+pub struct AbiRegistryService {
+    redis_repository: Arc<RedisRepository>,
+}
+
+impl AbiRegistryService {
+    pub fn new(redis_repository: Arc<RedisRepository>) -> Self {
+        AbiRegistryService { redis_repository }
+    }
+
+    fn parse(abi: &str) -> Result<Abi, AbiRegistryError> {
+        serde_json::from_str(abi).map_err(|e| AbiRegistryError::InvalidAbi(e.to_string()))
+    }
+
+    /// Grava as ABIs embutidas de `AbiUtils` no registro. Elas são regravadas a cada
+    /// inicialização, para que correções nas ABIs embutidas cheguem às instâncias existentes; ABIs
+    /// próprias devem ser registradas com outros nomes.
+    pub async fn seed(&self) -> Result<(), AbiRegistryError> {
+        let mut redis_conn = self.redis_repository.get_conn().await;
+        let builtin = [
+            ("erc20", AbiUtils::erc20_abi()),
+            ("erc721", AbiUtils::erc721_abi()),
+            ("erc1155", AbiUtils::erc1155_abi()),
+            ("erc165", AbiUtils::erc165_abi()),
+        ];
+
+        for (id, abi) in builtin {
+            let key = AbiKey::Id { id: id.to_string() };
+            let _: i64 = redis_conn.hset(ABIS_KEY, key.field(), abi).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Registra ou substitui a ABI. O JSON é validado antes de ser gravado.
+    pub async fn register(&self, request: RegisterAbi) -> Result<(), AbiRegistryError> {
+        Self::parse(&request.abi)?;
+
+        let mut redis_conn = self.redis_repository.get_conn().await;
+        let _: i64 = redis_conn.hset(ABIS_KEY, request.key.field(), request.abi).await?;
+        Ok(())
+    }
+
+    pub async fn get(&self, key: &AbiKey) -> Result<Option<String>, AbiRegistryError> {
+        let mut redis_conn = self.redis_repository.get_conn().await;
+        Ok(redis_conn.hget(ABIS_KEY, key.field()).await?)
+    }
+
+    /// Lista as chaves das ABIs registradas, sem o conteúdo.
+    pub async fn list(&self) -> Result<Vec<AbiKey>, AbiRegistryError> {
+        let mut redis_conn = self.redis_repository.get_conn().await;
+        let fields: Vec<String> = redis_conn.hkeys(ABIS_KEY).await?;
+
+        Ok(fields.iter().filter_map(|field| AbiKey::parse_field(field)).collect())
+    }
+
+    /// Remove a ABI. Retorna `false` se ela não estava registrada.
+    pub async fn remove(&self, key: &AbiKey) -> Result<bool, AbiRegistryError> {
+        let mut redis_conn = self.redis_repository.get_conn().await;
+        let removed: i64 = redis_conn.hdel(ABIS_KEY, key.field()).await?;
+        Ok(removed > 0)
+    }
+
+    async fn get_abi(&self, key: &AbiKey) -> Result<Abi, AbiRegistryError> {
+        match self.get(key).await? {
+            Some(abi) => Self::parse(&abi),
+            None => Err(AbiRegistryError::NotFound(key.to_string())),
+        }
+    }
+
+    /// Obtém a ABI de uma requisição: o JSON informado, a ABI registrada com `abi_id` ou, sem
+    /// nenhum dos dois, a ABI registrada para `address` na chain.
+    pub async fn resolve(&self, source: &AbiSource, chain_id: u64, address: Option<Address>) -> Result<Abi, AbiRegistryError> {
+        if let Some(abi) = &source.abi {
+            return Self::parse(abi);
+        }
+
+        let key = match (&source.abi_id, address) {
+            (Some(id), _) => AbiKey::Id { id: id.clone() },
+            (None, Some(address)) => AbiKey::Contract { chain_id, address },
+            (None, None) => return Err(AbiRegistryError::InvalidAbi("Informe `abi` ou `abi_id`".to_string())),
+        };
+
+        self.get_abi(&key).await
+    }
+
+    /// Como `resolve`, mas retorna `None` quando a requisição não informa ABI e o contrato não tem
+    /// ABI registrada. Usado onde a ABI é opcional, como na decodificação de eventos dos listeners.
+    pub async fn resolve_optional(
+        &self,
+        source: &AbiSource,
+        chain_id: u64,
+        address: Option<Address>,
+    ) -> Result<Option<Abi>, AbiRegistryError> {
+        if source.is_empty() && address.is_none() {
+            return Ok(None);
+        }
+
+        match self.resolve(source, chain_id, address).await {
+            Err(AbiRegistryError::NotFound(_)) if source.is_empty() => Ok(None),
+            result => result.map(Some),
+        }
+    }
+
+    /// Resolve as ABIs de várias chamadas consultando cada ABI registrada uma única vez.
+    pub async fn resolve_many(
+        &self,
+        requests: &[(AbiSource, Option<Address>)],
+        chain_id: u64,
+    ) -> Vec<Result<Abi, String>> {
+        let mut cache: HashMap<AbiKey, Result<Abi, String>> = HashMap::new();
+        let mut results = Vec::with_capacity(requests.len());
+
+        for (source, address) in requests {
+            let key = match (&source.abi, &source.abi_id, address) {
+                (Some(abi), _, _) => {
+                    results.push(Self::parse(abi).map_err(|e| e.to_string()));
+                    continue;
+                }
+                (None, Some(id), _) => AbiKey::Id { id: id.clone() },
+                (None, None, Some(address)) => AbiKey::Contract { chain_id, address: *address },
+                (None, None, None) => {
+                    results.push(Err("Nenhuma ABI informada para a chamada".to_string()));
+                    continue;
+                }
+            };
+
+            if !cache.contains_key(&key) {
+                let abi = self.get_abi(&key).await.map_err(|e| e.to_string());
+                cache.insert(key.clone(), abi);
+            }
+
+            results.push(cache[&key].clone());
+        }
+
+        results
+    }
+}
@@ -0,0 +1,282 @@
+use crate::repositories::ethers_repository::EthersRepository;
+use crate::rpc_transport::RpcPool;
+use crate::services::ethers::call_functions_service::{BlockError, BlockRef, CallFunctionsService};
+use ethers::providers::{Middleware, ProviderError};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{Address, BlockId, Bytes, TransactionRequest, H256};
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::RwLock;
+
+/// Slot `bytes32(uint256(keccak256("eip1967.proxy.implementation")) - 1)`.
+const EIP1967_IMPLEMENTATION_SLOT: &str = "0x360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc";
+
+/// Slot `bytes32(uint256(keccak256("eip1967.proxy.admin")) - 1)`.
+const EIP1967_ADMIN_SLOT: &str = "0xb53127684a568b3173ae13b9f8a6016e243e63b6e8ee1178d6a717850b5d6103";
+
+/// Slot `bytes32(uint256(keccak256("eip1967.proxy.beacon")) - 1)`.
+const EIP1967_BEACON_SLOT: &str = "0xa3f0ad74e5423aebfd80d3ef4346578335a9a72aeaee59ff6cb3582b35133d50";
+
+/// Slot `keccak256("PROXIABLE")` do EIP-1822 (UUPS).
+const EIP1822_PROXIABLE_SLOT: &str = "0xc5f16f0fcc639fa48a6947836d9850f504798523bf8c9a3a87d5876cf622bcf7";
+
+/// Slot `keccak256("org.zeppelinos.proxy.implementation")` dos proxies legados do OpenZeppelin.
+const OZ_LEGACY_IMPLEMENTATION_SLOT: &str = "0x7050c9e0f4ca769c69bd3a8ef740bc37934f8e2c036e5a723fd8ee048ed3f8c3";
+
+/// Slot `keccak256("org.zeppelinos.proxy.admin")` dos proxies legados do OpenZeppelin.
+const OZ_LEGACY_ADMIN_SLOT: &str = "0x10d6a54a4754c8869d6886b5f5d7fbfa5b4522237ea5c60d11bc4e7a1ff9390b";
+
+/// Seletor de `implementation()`, exposto pelos beacons do EIP-1967.
+const BEACON_IMPLEMENTATION_SELECTOR: [u8; 4] = [0x5c, 0x60, 0xda, 0x1b];
+
+/// Código do EIP-1167 antes e depois do endereço da implementação.
+const EIP1167_PREFIX: &str = "363d3d373d3d3d363d73";
+const EIP1167_SUFFIX: &str = "5af43d82803e903d91602b57fd5bf3";
+
+/// Profundidade máxima da cadeia de proxies seguida a partir do endereço consultado.
+const MAX_PROXY_DEPTH: usize = 5;
+
+#[derive(Error, Debug)]
+pub enum AddressInspectionError {
+    #[error("Nenhuma conexão configurada para o user_id {0} na chain {1}")]
+    NotFound(i32, u64),
+    #[error("Endereço inválido {0}: {1}")]
+    InvalidAddress(String, String),
+    #[error(transparent)]
+    BlockError(#[from] BlockError),
+    #[error("Erro ao consultar o endereço: {0}")]
+    RpcError(#[from] ProviderError),
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProxyKind {
+    Eip1967,
+    Eip1967Beacon,
+    Eip1822,
+    OpenZeppelinLegacy,
+    Eip1167,
+}
+
+impl ProxyKind {
+    /// Proxies cuja implementação pode ser trocada. O EIP-1167 tem a implementação fixa no código.
+    pub fn upgradeable(&self) -> bool {
+        *self != ProxyKind::Eip1167
+    }
+}
+
+/// Um elo da cadeia de proxies: o proxy em `address` delega para `implementation`.
+#[derive(Serialize, Clone)]
+pub struct ProxyLink {
+    pub address: Address,
+    pub kind: ProxyKind,
+    pub implementation: Address,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub admin: Option<Address>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub beacon: Option<Address>,
+}
+
+/// Cadeia de proxies a partir de um endereço. `truncated` indica que a busca parou em
+/// `MAX_PROXY_DEPTH` elos e o último `implementation` ainda é um proxy.
+pub struct ProxyChain {
+    pub is_contract: bool,
+    pub links: Vec<ProxyLink>,
+    pub truncated: bool,
+}
+
+/// Resultado da inspeção. `proxies` é a cadeia a partir do endereço consultado e `implementation`
+/// o último contrato dela, que não é um proxy. Se a cadeia passar de `MAX_PROXY_DEPTH` elos,
+/// `truncated` é `true` e `implementation` é `null`, já que a implementação final não foi alcançada.
+#[derive(Serialize)]
+pub struct AddressInspection {
+    pub address: Address,
+    pub is_contract: bool,
+    pub upgradeable: bool,
+    pub implementation: Option<Address>,
+    pub proxies: Vec<ProxyLink>,
+    pub truncated: bool,
+    pub block: BlockRef,
+}
+
+/// Inspeciona endereços: detecta proxies pelos slots padronizados (EIP-1967, EIP-1822 e os
+/// legados do OpenZeppelin) e pelo código do EIP-1167, resolvendo a cadeia até a implementação.
+pub struct AddressInspectionService {
+    repository: Arc<RwLock<EthersRepository>>,
+}
+
+impl AddressInspectionService {
+    pub fn new(repository: Arc<RwLock<EthersRepository>>) -> Self {
+        AddressInspectionService { repository }
+    }
+
+    async fn get_pool(&self, user_id: i32, chain_id: u64) -> Result<RpcPool, AddressInspectionError> {
+        let lock = self.repository.read().await;
+        lock.get_pool(user_id, chain_id).ok_or(AddressInspectionError::NotFound(user_id, chain_id))
+    }
+
+    fn slot(slot: &str) -> H256 {
+        slot.parse().expect("slot de proxy é válido")
+    }
+
+    /// Endereço gravado nos 20 bytes menos significativos do slot; slot vazio resulta em `None`.
+    fn slot_address(value: H256) -> Option<Address> {
+        let address = Address::from_slice(&value.as_bytes()[12..]);
+        (!address.is_zero()).then_some(address)
+    }
+
+    async fn read_slot(pool: &RpcPool, block: BlockId, address: Address, slot: &str) -> Result<Option<Address>, ProviderError> {
+        let slot = Self::slot(slot);
+        let value = pool
+            .failover(|provider| async move { provider.get_storage_at(address, slot, Some(block)).await })
+            .await?;
+
+        Ok(Self::slot_address(value))
+    }
+
+    async fn get_code(pool: &RpcPool, block: BlockId, address: Address) -> Result<Bytes, ProviderError> {
+        pool.failover(|provider| async move { provider.get_code(address, Some(block)).await })
+            .await
+    }
+
+    /// Implementação embutida no código de um proxy mínimo do EIP-1167.
+    fn eip1167_implementation(code: &[u8]) -> Option<Address> {
+        let code = ethers::utils::hex::encode(code);
+        let implementation = code.strip_prefix(EIP1167_PREFIX)?.strip_suffix(EIP1167_SUFFIX)?;
+        if implementation.len() != 40 {
+            return None;
+        }
+
+        implementation.parse().ok()
+    }
+
+    async fn beacon_implementation(pool: &RpcPool, block: BlockId, beacon: Address) -> Option<Address> {
+        let tx: TypedTransaction = TransactionRequest::new()
+            .to(beacon)
+            .data(BEACON_IMPLEMENTATION_SELECTOR.to_vec())
+            .into();
+
+        let output = pool
+            .failover(|provider| {
+                let tx = tx.clone();
+                async move { provider.call(&tx, Some(block)).await }
+            })
+            .await
+            .map_err(|e| log::warn!("Erro ao consultar a implementação do beacon {:?}: {}", beacon, e))
+            .ok()?;
+
+        (output.len() == 32).then(|| Self::slot_address(H256::from_slice(&output))).flatten()
+    }
+
+    /// Identifica o proxy em `address` a partir do código já obtido. Retorna `None` se o endereço
+    /// não for um proxy reconhecido.
+    async fn detect(pool: &RpcPool, block: BlockId, address: Address, code: &[u8]) -> Result<Option<ProxyLink>, ProviderError> {
+        if let Some(implementation) = Self::eip1167_implementation(code) {
+            return Ok(Some(ProxyLink { address, kind: ProxyKind::Eip1167, implementation, admin: None, beacon: None }));
+        }
+
+        let (implementation, admin, beacon, proxiable, legacy_implementation, legacy_admin) = futures::try_join!(
+            Self::read_slot(pool, block, address, EIP1967_IMPLEMENTATION_SLOT),
+            Self::read_slot(pool, block, address, EIP1967_ADMIN_SLOT),
+            Self::read_slot(pool, block, address, EIP1967_BEACON_SLOT),
+            Self::read_slot(pool, block, address, EIP1822_PROXIABLE_SLOT),
+            Self::read_slot(pool, block, address, OZ_LEGACY_IMPLEMENTATION_SLOT),
+            Self::read_slot(pool, block, address, OZ_LEGACY_ADMIN_SLOT),
+        )?;
+
+        let link = |kind, implementation, admin, beacon| ProxyLink { address, kind, implementation, admin, beacon };
+
+        if let Some(implementation) = implementation {
+            return Ok(Some(link(ProxyKind::Eip1967, implementation, admin, None)));
+        }
+        if let Some(beacon) = beacon {
+            if let Some(implementation) = Self::beacon_implementation(pool, block, beacon).await {
+                return Ok(Some(link(ProxyKind::Eip1967Beacon, implementation, admin, Some(beacon))));
+            }
+        }
+        if let Some(implementation) = proxiable {
+            return Ok(Some(link(ProxyKind::Eip1822, implementation, None, None)));
+        }
+        if let Some(implementation) = legacy_implementation {
+            return Ok(Some(link(ProxyKind::OpenZeppelinLegacy, implementation, legacy_admin, None)));
+        }
+
+        Ok(None)
+    }
+
+    /// Segue a cadeia de proxies a partir de `address` até um contrato que não é proxy, limitada a
+    /// `MAX_PROXY_DEPTH` elos. Retorna também se `address` tem código e se a cadeia foi truncada
+    /// pelo limite.
+    pub async fn proxy_chain(pool: &RpcPool, block: BlockId, address: Address) -> Result<ProxyChain, ProviderError> {
+        let mut links: Vec<ProxyLink> = Vec::new();
+        let mut current = address;
+        let mut is_contract = false;
+        let mut truncated = false;
+
+        loop {
+            let code = Self::get_code(pool, block, current).await?;
+            if current == address {
+                is_contract = !code.is_empty();
+            }
+            if code.is_empty() {
+                break;
+            }
+
+            let Some(link) = Self::detect(pool, block, current, &code).await? else {
+                break;
+            };
+
+            // No limite, o elo só é usado para saber se a implementação final ficou de fora.
+            if links.len() == MAX_PROXY_DEPTH {
+                truncated = true;
+                break;
+            }
+
+            let implementation = link.implementation;
+            links.push(link);
+
+            // Um proxy que aponta para um elo anterior da cadeia encerra a busca.
+            if links.iter().any(|link| link.address == implementation) {
+                break;
+            }
+            current = implementation;
+        }
+
+        Ok(ProxyChain { is_contract, links, truncated })
+    }
+
+    pub async fn exec(
+        &self,
+        user_id: i32,
+        chain_id: u64,
+        address: String,
+        block: Option<Value>,
+    ) -> Result<AddressInspection, AddressInspectionError> {
+        let pool = self.get_pool(user_id, chain_id).await?;
+        let address = address
+            .parse::<Address>()
+            .map_err(|e| AddressInspectionError::InvalidAddress(address.clone(), e.to_string()))?;
+        let resolved = CallFunctionsService::resolve_block(&pool, block.as_ref()).await?;
+
+        let chain = Self::proxy_chain(&pool, resolved.id, address).await?;
+        if chain.truncated {
+            log::warn!("Cadeia de proxies de {:?} passa de {} elos, truncada", address, MAX_PROXY_DEPTH);
+        }
+
+        Ok(AddressInspection {
+            address,
+            is_contract: chain.is_contract,
+            upgradeable: chain.links.iter().any(|link| link.kind.upgradeable()),
+            implementation: chain
+                .links
+                .last()
+                .filter(|_| !chain.truncated)
+                .map(|link| link.implementation),
+            proxies: chain.links,
+            truncated: chain.truncated,
+            block: resolved.block,
+        })
+    }
+}
@@ -1,42 +1,291 @@
 use crate::repositories::ethers_repository::EthersRepository;
 use crate::repositories::redis_repository::RedisRepository;
-use ethers::prelude::{Provider, Ws};
-use ethers::providers::Middleware;
-use redis::AsyncCommands;
-use std::sync::{Arc};
+use crate::services::ethers::connection_supervisor::ConnectionSupervisor;
+use crate::services::ethers::rollback_reorg_service::RollbackReorgService;
+use crate::rpc_transport::RpcPool;
+use ethers::providers::ProviderError;
+use futures::future::join_all;
+use redis::{AsyncCommands, RedisError};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
 use tokio::sync::RwLock;
 
+#[derive(Error, Debug)]
+pub enum ApplyRpcError {
+    #[error("Erro ao conectar nos endpoints {0:?}: {1}")]
+    ConnectionError(Vec<String>, ProviderError),
+    #[error("Nenhum endpoint informado")]
+    NoEndpoints,
+    #[error("Nenhuma conexão configurada para o user_id {0} na chain {1}")]
+    NotFound(i32, u64),
+    #[error("Os endpoints respondem pela chain {found}, mas a conexão é da chain {expected}")]
+    ChainMismatch { expected: u64, found: u64 },
+    #[error("Erro no Redis: {0}")]
+    RedisError(#[from] RedisError),
+}
+
+#[derive(Serialize)]
+pub struct RestoreFailure {
+    pub key: String,
+    pub endpoints: Vec<String>,
+    pub error: String,
+}
+
+#[derive(Serialize)]
+pub struct RestoredConnection {
+    pub user_id: i32,
+    pub chain_id: u64,
+}
+
+/// Resultado da reidratação das conexões salvas no Redis. Em `pending` ficam as conexões que não
+/// conectaram na inicialização e seguem sendo tentadas pelo `ConnectionSupervisor`; em `failed`, as
+/// entradas que não podem ser restauradas (chave inválida ou sem `chain_id`).
+#[derive(Serialize, Default)]
+pub struct RestoreReport {
+    pub restored: Vec<RestoredConnection>,
+    pub pending: Vec<RestoreFailure>,
+    pub failed: Vec<RestoreFailure>,
+}
+
+enum RestoreOutcome {
+    Restored(RestoredConnection),
+    Pending(String),
+}
+
 #[derive(Clone)]
 pub struct ApplyRpcService {
     repository:Arc<RwLock<EthersRepository>>,
     redis_repository:Arc<RedisRepository>,
+    rollback_reorg_service:Arc<RollbackReorgService>,
 }
 
 impl ApplyRpcService {
-    pub fn new(ethers_repository:Arc<RwLock<EthersRepository>>, redis_repository: Arc<RedisRepository>) -> Self {
+    pub fn new(
+        ethers_repository:Arc<RwLock<EthersRepository>>,
+        redis_repository: Arc<RedisRepository>,
+        rollback_reorg_service: Arc<RollbackReorgService>,
+    ) -> Self {
         ApplyRpcService {
             repository : ethers_repository,
-            redis_repository
+            redis_repository,
+            rollback_reorg_service,
+        }
+    }
+
+    /// Registra os endpoints do usuário, em ordem de prioridade. O primeiro endpoint que conectar
+    /// passa a ser o primário; os demais ficam disponíveis para failover e leituras por quórum.
+    ///
+    /// A chain é detectada via `eth_chainId` e retornada: a conexão passa a ser identificada por
+    /// (`user_id`, `chain_id`), de modo que registrar endpoints de outra chain cria uma nova conexão
+    /// em vez de substituir a existente.
+    pub async fn exec(&self, user_id:i32, endpoints:Vec<String>) -> Result<u64, ApplyRpcError> {
+        if endpoints.is_empty() {
+            return Err(ApplyRpcError::NoEndpoints);
         }
+
+        let chain_id = self.start_connection(user_id, endpoints.clone()).await?;
+        self.save_connection(user_id, chain_id, &endpoints).await?;
+
+        Ok(chain_id)
+    }
+
+    /// Substitui os endpoints de uma conexão existente. Os novos endpoints precisam responder pela
+    /// mesma chain; consumidores de blocos e assinaturas de logs seguem ativos e passam a usar o novo
+    /// provider primário.
+    pub async fn replace(&self, user_id: i32, chain_id: u64, endpoints: Vec<String>) -> Result<(), ApplyRpcError> {
+        if endpoints.is_empty() {
+            return Err(ApplyRpcError::NoEndpoints);
+        }
+
+        if !self.repository.read().await.has_connection(user_id, chain_id) {
+            return Err(ApplyRpcError::NotFound(user_id, chain_id));
+        }
+
+        let pool = RpcPool::connect(&endpoints)
+            .await
+            .map_err(|e| ApplyRpcError::ConnectionError(endpoints.clone(), e))?;
+
+        if pool.chain_id() != chain_id {
+            return Err(ApplyRpcError::ChainMismatch { expected: chain_id, found: pool.chain_id() });
+        }
+
+        self.supervise(user_id, pool, endpoints.clone()).await;
+        self.save_connection(user_id, chain_id, &endpoints).await
+    }
+
+    /// Remove a conexão do usuário na chain, encerrando provider, listener de blocos e as tasks que
+    /// dependem dela, e apaga o registro no Redis para que não seja restaurada.
+    pub async fn remove(&self, user_id: i32, chain_id: u64) -> Result<(), ApplyRpcError> {
+        if !self.repository.write().await.remove_connection(user_id, chain_id) {
+            return Err(ApplyRpcError::NotFound(user_id, chain_id));
+        }
+
+        let mut redis_conn = self.redis_repository.get_conn().await;
+        let _: i64 = redis_conn.hdel("connections", Self::connection_key(user_id, chain_id)).await?;
+
+        Ok(())
+    }
+
+    fn connection_key(user_id: i32, chain_id: u64) -> String {
+        format!("{}:{}", user_id, chain_id)
+    }
+
+    async fn save_connection(&self, user_id: i32, chain_id: u64, endpoints: &[String]) -> Result<(), ApplyRpcError> {
+        let mut redis_conn = self.redis_repository.get_conn().await;
+        let value = serde_json::to_string(endpoints).expect("Vec<String> é sempre serializável");
+        let _: i64 = redis_conn.hset("connections", Self::connection_key(user_id, chain_id), value).await?;
+        Ok(())
+    }
+
+    /// Interpreta a chave do hash `connections`: `user_id:chain_id` ou, para entradas gravadas antes
+    /// do suporte a múltiplas chains, apenas `user_id`.
+    fn parse_connection_key(key: &str) -> Result<(i32, Option<u64>), String> {
+        let (user_id, chain_id) = match key.split_once(':') {
+            Some((user_id, chain_id)) => (user_id, Some(chain_id)),
+            None => (key, None),
+        };
+
+        let user_id = user_id.parse::<i32>().map_err(|e| format!("user_id inválido: {}", e))?;
+        let chain_id = chain_id
+            .map(|chain_id| chain_id.parse::<u64>().map_err(|e| format!("chain_id inválido: {}", e)))
+            .transpose()?;
+
+        Ok((user_id, chain_id))
     }
 
-    pub async fn exec(&self, user_id:i32, endpoint:String){
-        self.start_connection(user_id, endpoint.clone()).await;
-        self.start_block_listener(user_id, endpoint.clone()).await;
+    /// Interpreta o valor salvo no hash `connections`: uma lista JSON de endpoints ou, para
+    /// entradas gravadas antes do suporte a múltiplos endpoints, um único endpoint.
+    fn parse_endpoints(value: &str) -> Vec<String> {
+        serde_json::from_str(value).unwrap_or_else(|_| vec![value.to_string()])
     }
 
-    async fn start_connection(&self, user_id: i32, endpoint: String){
+    /// Reconstrói, a partir do hash `connections` do Redis, a conexão supervisionada de
+    /// cada usuário registrado anteriormente via `apply_rpc`.
+    ///
+    /// **Passo a passo**:
+    /// 1. Todas as entradas `user_id:chain_id -> endpoints` são lidas do Redis.
+    /// 2. As conexões são abertas em paralelo, cada uma limitada pelo timeout de `RpcTransport::connect_provider`.
+    /// 3. Entradas antigas, sem `chain_id` na chave, ou cujos endpoints passaram a responder por outra
+    ///    chain, são regravadas com a chain detectada.
+    /// 4. Conexões cujos endpoints falharem ou esgotarem o timeout ficam com o `ConnectionSupervisor`,
+    ///    que tenta conectá-las com backoff até conseguir (veja `supervise_pending`); os demais
+    ///    usuários seguem utilizáveis. Entradas antigas, sem `chain_id` na chave, não têm como ser
+    ///    identificadas sem conectar e são apenas reportadas, permanecendo no Redis para a próxima
+    ///    inicialização.
+    pub async fn restore_connections(&self) -> Result<RestoreReport, ApplyRpcError> {
         let mut redis_conn = self.redis_repository.get_conn().await;
+        let connections: HashMap<String, String> = redis_conn.hgetall("connections").await?;
 
-        let provider = Provider::<Ws>::connect(&endpoint).await.expect("ERRCON 500");
-        self.repository.write().await.apply_connection(user_id, provider);
+        let tasks = connections.into_iter().map(|(key, value)| async move {
+            let endpoints = Self::parse_endpoints(&value);
+            let result = self.restore_connection(&key, endpoints.clone()).await;
+            (key, endpoints, result)
+        });
 
-        let _: i64 = redis_conn.hset("connections",user_id.to_string(),&endpoint).await.expect("ae");
+        let mut report = RestoreReport::default();
+
+        for (key, endpoints, result) in join_all(tasks).await {
+            match result {
+                Ok(RestoreOutcome::Restored(restored)) => report.restored.push(restored),
+                Ok(RestoreOutcome::Pending(error)) => report.pending.push(RestoreFailure { key, endpoints, error }),
+                Err(error) => report.failed.push(RestoreFailure { key, endpoints, error }),
+            }
+        }
+
+        Ok(report)
     }
 
-    async fn start_block_listener(&self, user_id:i32, endpoint: String){
-        let provider = Provider::<Ws>::connect(&endpoint).await.expect("ERRCON 500");
-        self.repository.write().await.apply_block_listener(user_id, provider).await;
+    async fn restore_connection(&self, key: &str, endpoints: Vec<String>) -> Result<RestoreOutcome, String> {
+        let (user_id, expected_chain_id) = Self::parse_connection_key(key)?;
+        let chain_id = match (self.start_connection(user_id, endpoints.clone()).await, expected_chain_id) {
+            (Ok(chain_id), _) => chain_id,
+            (Err(e), Some(expected)) => {
+                self.supervise_pending(user_id, expected, endpoints, e.to_string()).await;
+                return Ok(RestoreOutcome::Pending(e.to_string()));
+            }
+            (Err(e), None) => return Err(e.to_string()),
+        };
+
+        if expected_chain_id != Some(chain_id) {
+            if let Some(expected) = expected_chain_id {
+                log::warn!("Endpoints de {} agora respondem pela chain {} em vez de {}", key, chain_id, expected);
+            }
+
+            self.save_connection(user_id, chain_id, &endpoints).await.map_err(|e| e.to_string())?;
+            let mut redis_conn = self.redis_repository.get_conn().await;
+            let _: i64 = redis_conn.hdel("connections", key).await.map_err(|e| e.to_string())?;
+        }
+
+        Ok(RestoreOutcome::Restored(RestoredConnection { user_id, chain_id }))
     }
 
+    /// Abre as conexões do usuário, com o transporte escolhido pelo esquema de cada endpoint, e
+    /// entrega sua supervisão ao `ConnectionSupervisor`, que passa a manter a fonte de blocos e a
+    /// reconectar (ou trocar de endpoint) quando a conexão primária cair.
+    async fn start_connection(&self, user_id: i32, endpoints: Vec<String>) -> Result<u64, ApplyRpcError> {
+        let pool = RpcPool::connect(&endpoints)
+            .await
+            .map_err(|e| ApplyRpcError::ConnectionError(endpoints.clone(), e))?;
+        let chain_id = pool.chain_id();
+
+        self.supervise(user_id, pool, endpoints).await;
+
+        Ok(chain_id)
+    }
+
+    /// Registra o pool no repositório e inicia seu `ConnectionSupervisor`, substituindo o anterior
+    /// da mesma chain, se houver. Em uma conexão nova também inicia o rollback de reorgs no
+    /// Elasticsearch, que acompanha a conexão até ela ser removida.
+    async fn supervise(&self, user_id: i32, pool: RpcPool, endpoints: Vec<String>) {
+        let chain_id = pool.chain_id();
+        let provider = pool.primary();
+
+        let mut repository = self.repository.write().await;
+        let is_new = !repository.has_connection(user_id, chain_id);
+        let block_hub = repository.apply_block_hub(user_id, chain_id);
+        let health = repository.apply_health(user_id, chain_id);
+
+        let supervisor = ConnectionSupervisor::new(
+            self.repository.clone(),
+            user_id,
+            chain_id,
+            endpoints,
+            block_hub,
+            health,
+        );
+
+        repository.apply_connection(user_id, pool);
+        repository.apply_supervisor(user_id, chain_id, tokio::spawn(supervisor.run(provider)));
+        drop(repository);
+
+        if is_new {
+            self.rollback_reorg_service.exec(user_id, chain_id).await;
+        }
+    }
+
+    /// Entrega ao `ConnectionSupervisor` uma conexão que não conectou, para que ele a reconecte com
+    /// backoff. O hub de blocos e o rollback de reorgs são criados desde já, de modo que a conexão
+    /// fica completa assim que o supervisor conseguir o primeiro pool.
+    async fn supervise_pending(&self, user_id: i32, chain_id: u64, endpoints: Vec<String>, error: String) {
+        let mut repository = self.repository.write().await;
+        if repository.has_connection(user_id, chain_id) {
+            return;
+        }
+
+        let supervisor = ConnectionSupervisor::new(
+            self.repository.clone(),
+            user_id,
+            chain_id,
+            endpoints,
+            repository.apply_block_hub(user_id, chain_id),
+            repository.apply_health(user_id, chain_id),
+        );
+
+        repository.apply_supervisor(user_id, chain_id, tokio::spawn(supervisor.run_disconnected(error)));
+        drop(repository);
+
+        self.rollback_reorg_service.exec(user_id, chain_id).await;
+    }
 }
@@ -0,0 +1,311 @@
+use ethers::abi::{Abi, Function, Token};
+use ethers::providers::{Middleware, ProviderError};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{Address, BlockId, BlockNumber, Bytes, TransactionRequest};
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::RwLock;
+
+use crate::repositories::ethers_repository::EthersRepository;
+use crate::rpc_transport::RpcPool;
+use crate::services::ethers::abi_registry_service::{AbiRegistryService, AbiSource};
+use crate::services::ethers::call_functions_service::{BlockError, BlockRef, CallError, CallFunctionsService, FunctionCall};
+use crate::utils::abi_utils::AbiUtils;
+use crate::utils::ethers_utils::EthersUtils;
+
+/// Endereço do Multicall3, o mesmo em todas as chains em que foi implantado.
+pub const MULTICALL3_ADDRESS: &str = "0xcA11bde05977b3631167028862bE2a173976CA11";
+
+/// Quantidade padrão de chamadas por `aggregate3`.
+const DEFAULT_CHUNK_SIZE: usize = 100;
+
+/// Quantidade de `eth_call` simultâneos, tanto de lotes do Multicall3 quanto de chamadas individuais.
+const MAX_CONCURRENT_CALLS: usize = 16;
+
+#[derive(Error, Debug)]
+pub enum BatchCallError {
+    #[error("Nenhuma conexão configurada para o user_id {0} na chain {1}")]
+    NotFound(i32, u64),
+    #[error("ABI inválida: {0}")]
+    InvalidAbi(String),
+    #[error(transparent)]
+    BlockError(#[from] BlockError),
+}
+
+/// Leitura de um contrato no lote. A ABI da chamada (`abi` ou `abi_id`) substitui a ABI padrão do
+/// lote; sem nenhuma das duas, é usada a ABI registrada para o contrato.
+#[derive(Deserialize, Clone)]
+pub struct BatchCall {
+    pub address: String,
+    #[serde(flatten)]
+    pub call: FunctionCall,
+    #[serde(flatten)]
+    pub abi: AbiSource,
+}
+
+#[derive(Serialize)]
+pub struct BatchCallResult {
+    pub address: String,
+    pub key: String,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<CallError>,
+}
+
+#[derive(Serialize)]
+pub struct BatchCallResponse {
+    pub block: BlockRef,
+    pub multicall: bool,
+    pub results: Vec<BatchCallResult>,
+}
+
+/// Chamada já codificada, pronta para ser enviada via Multicall3 ou `eth_call` individual.
+struct EncodedCall {
+    target: Address,
+    abi: Abi,
+    function: Function,
+    calldata: Bytes,
+}
+
+pub struct BatchCallService {
+    repository: Arc<RwLock<EthersRepository>>,
+    abi_registry: Arc<AbiRegistryService>,
+}
+
+impl BatchCallService {
+    pub fn new(repository: Arc<RwLock<EthersRepository>>, abi_registry: Arc<AbiRegistryService>) -> Self {
+        BatchCallService { repository, abi_registry }
+    }
+
+    fn encode(target: Address, abi: Abi, batch_call: &BatchCall) -> Result<EncodedCall, CallError> {
+        let (function, tokens) = CallFunctionsService::encode_call(&abi, &batch_call.call)?;
+        let calldata = function.encode_input(&tokens).map_err(|e| CallError::InvalidArguments {
+            message: format!("Erro ao codificar argumentos: {}", e),
+        })?;
+
+        Ok(EncodedCall {
+            target,
+            abi,
+            function,
+            calldata: calldata.into(),
+        })
+    }
+
+    /// Codifica as chamadas do lote. A ABI de cada chamada vem da própria chamada, da ABI padrão do
+    /// lote ou do registro de ABIs pelo endereço do contrato, nessa ordem.
+    async fn encode_all(&self, chain_id: u64, default_abi: &AbiSource, calls: &[BatchCall]) -> Vec<Result<EncodedCall, CallError>> {
+        let targets: Vec<Result<Address, CallError>> = calls
+            .iter()
+            .map(|call| {
+                call.address.parse::<Address>().map_err(|e| CallError::InvalidArguments {
+                    message: format!("Endereço inválido {}: {}", call.address, e),
+                })
+            })
+            .collect();
+
+        let sources: Vec<(AbiSource, Option<Address>)> = calls
+            .iter()
+            .zip(&targets)
+            .map(|(call, target)| {
+                let source = if call.abi.is_empty() { default_abi } else { &call.abi };
+                (source.clone(), target.as_ref().ok().copied())
+            })
+            .collect();
+
+        let abis = self.abi_registry.resolve_many(&sources, chain_id).await;
+
+        calls
+            .iter()
+            .zip(targets)
+            .zip(abis)
+            .map(|((call, target), abi)| {
+                let abi = abi.map_err(|message| CallError::InvalidAbi { message })?;
+                Self::encode(target?, abi, call)
+            })
+            .collect()
+    }
+
+    fn decode(function: &Function, data: &[u8]) -> Result<Value, CallError> {
+        let tokens = function.decode_output(data).map_err(|e| CallError::InvalidOutput {
+            message: format!("Erro ao decodificar retorno: {}", e),
+        })?;
+
+        Ok(EthersUtils::outputs_to_json(&function.outputs, tokens))
+    }
+
+    async fn eth_call(pool: &RpcPool, to: Address, data: Bytes, block: BlockId) -> Result<Bytes, ProviderError> {
+        let tx: TypedTransaction = TransactionRequest::new().to(to).data(data).into();
+
+        pool.failover(|provider| {
+            let tx = tx.clone();
+            async move { provider.call(&tx, Some(block)).await }
+        })
+        .await
+    }
+
+    /// Verifica se o Multicall3 existe no bloco consultado; em blocos antigos ele pode ainda não
+    /// ter sido implantado.
+    async fn has_multicall3(pool: &RpcPool, multicall: Address, block: BlockId) -> bool {
+        let block = match block {
+            BlockId::Number(BlockNumber::Pending) => None,
+            block => Some(block),
+        };
+
+        pool.failover(|provider| async move { provider.get_code(multicall, block).await })
+            .await
+            .map(|code| !code.is_empty())
+            .unwrap_or(false)
+    }
+
+    async fn call_individually(pool: &RpcPool, call: &EncodedCall, block: BlockId) -> Result<Value, CallError> {
+        let data = Self::eth_call(pool, call.target, call.calldata.clone(), block)
+            .await
+            .map_err(|e| CallError::from_provider_error(Some(&call.abi), &e))?;
+
+        Self::decode(&call.function, &data)
+    }
+
+    /// Envia um lote via `aggregate3` com `allowFailure`, de modo que a falha de uma chamada não
+    /// invalida as demais. Se o próprio `aggregate3` falhar (limite de gas, erro de RPC), o lote é
+    /// refeito com chamadas individuais.
+    async fn call_chunk(
+        pool: &RpcPool,
+        multicall: Address,
+        aggregate3: &Function,
+        chunk: &[&EncodedCall],
+        block: BlockId,
+    ) -> Vec<Result<Value, CallError>> {
+        let calls = chunk
+            .iter()
+            .map(|call| {
+                Token::Tuple(vec![
+                    Token::Address(call.target),
+                    Token::Bool(true),
+                    Token::Bytes(call.calldata.to_vec()),
+                ])
+            })
+            .collect();
+
+        let response = match aggregate3.encode_input(&[Token::Array(calls)]) {
+            Ok(data) => Self::eth_call(pool, multicall, data.into(), block)
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|data| aggregate3.decode_output(&data).map_err(|e| e.to_string())),
+            Err(e) => Err(e.to_string()),
+        };
+
+        let results = match response.map(|mut tokens| tokens.pop()) {
+            Ok(Some(Token::Array(results))) if results.len() == chunk.len() => results,
+            Ok(_) => {
+                log::warn!("Resposta inesperada do aggregate3, refazendo o lote com chamadas individuais");
+                return Self::call_all_individually(pool, chunk, block).await;
+            }
+            Err(e) => {
+                log::warn!("Falha no aggregate3 ({}), refazendo o lote com chamadas individuais", e);
+                return Self::call_all_individually(pool, chunk, block).await;
+            }
+        };
+
+        results
+            .into_iter()
+            .zip(chunk)
+            .map(|(result, call)| match result {
+                Token::Tuple(fields) => match fields.as_slice() {
+                    [Token::Bool(true), Token::Bytes(data)] => Self::decode(&call.function, data),
+                    [Token::Bool(false), Token::Bytes(data)] => Err(CallError::revert(Some(&call.abi), data.clone().into())),
+                    _ => Err(CallError::InvalidOutput { message: "Resultado inválido do aggregate3".to_string() }),
+                },
+                _ => Err(CallError::InvalidOutput { message: "Resultado inválido do aggregate3".to_string() }),
+            })
+            .collect()
+    }
+
+    async fn call_all_individually(pool: &RpcPool, calls: &[&EncodedCall], block: BlockId) -> Vec<Result<Value, CallError>> {
+        stream::iter(calls)
+            .map(|call| Self::call_individually(pool, call, block))
+            .buffered(MAX_CONCURRENT_CALLS)
+            .collect()
+            .await
+    }
+
+    /// Executa as leituras em lote, preservando a ordem das chamadas na resposta.
+    ///
+    /// **Passo a passo**:
+    /// 1. Cada chamada é codificada com a sua ABI, a ABI padrão do lote ou a ABI registrada para o
+    ///    contrato. Chamadas inválidas retornam erro individualmente, sem afetar as demais.
+    /// 2. Se o Multicall3 estiver implantado na chain, as chamadas válidas são agrupadas em lotes de
+    ///    `chunk_size` e enviadas via `aggregate3`.
+    /// 3. Caso contrário, cada chamada é feita com um `eth_call` próprio, em paralelo.
+    ///
+    /// Todas as chamadas são executadas no mesmo bloco (veja `CallFunctionsService::resolve_block`).
+    pub async fn exec(
+        &self,
+        user_id: i32,
+        chain_id: u64,
+        calls: Vec<BatchCall>,
+        abi: AbiSource,
+        chunk_size: Option<usize>,
+        block: Option<Value>,
+    ) -> Result<BatchCallResponse, BatchCallError> {
+        let pool = self
+            .repository
+            .read()
+            .await
+            .get_pool(user_id, chain_id)
+            .ok_or(BatchCallError::NotFound(user_id, chain_id))?;
+
+        if let Some(default_abi) = &abi.abi {
+            serde_json::from_str::<Abi>(default_abi).map_err(|e| BatchCallError::InvalidAbi(e.to_string()))?;
+        }
+
+        let resolved = CallFunctionsService::resolve_block(&pool, block.as_ref()).await?;
+
+        let encoded = self.encode_all(chain_id, &abi, &calls).await;
+        let valid: Vec<&EncodedCall> = encoded.iter().filter_map(|call| call.as_ref().ok()).collect();
+
+        let multicall: Address = MULTICALL3_ADDRESS.parse().expect("endereço do Multicall3 é válido");
+        let use_multicall = !valid.is_empty() && Self::has_multicall3(&pool, multicall, resolved.id).await;
+
+        let mut responses = if use_multicall {
+            let aggregate3 = serde_json::from_str::<Abi>(AbiUtils::multicall3_abi())
+                .expect("ABI do Multicall3 é válida")
+                .function("aggregate3")
+                .expect("ABI do Multicall3 contém aggregate3")
+                .clone();
+            let chunk_size = chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE).max(1);
+
+            stream::iter(valid.chunks(chunk_size))
+                .map(|chunk| Self::call_chunk(&pool, multicall, &aggregate3, chunk, resolved.id))
+                .buffered(MAX_CONCURRENT_CALLS)
+                .collect::<Vec<_>>()
+                .await
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+        } else {
+            Self::call_all_individually(&pool, &valid, resolved.id).await
+        }
+        .into_iter();
+
+        let results = calls
+            .into_iter()
+            .zip(encoded)
+            .map(|(call, encoded)| {
+                let result = encoded.and_then(|_| responses.next().expect("uma resposta por chamada válida"));
+                let key = call.call.key();
+
+                match result {
+                    Ok(value) => BatchCallResult { address: call.address, key, success: true, value: Some(value), error: None },
+                    Err(error) => BatchCallResult { address: call.address, key, success: false, value: None, error: Some(error) },
+                }
+            })
+            .collect();
+
+        Ok(BatchCallResponse { block: resolved.block, multicall: use_multicall, results })
+    }
+}
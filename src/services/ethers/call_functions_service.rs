@@ -1,68 +1,425 @@
-use ethers::abi::{Abi, Token};
-use ethers::contract::{Contract, ContractInstance};
-use ethers::middleware::Middleware;
-use ethers::prelude::{Provider, Ws};
-use ethers::types::Address;
+use ethers::abi::{Abi, Function, Token};
+use ethers::contract::{Contract, ContractError, ContractInstance};
+use ethers::providers::{Middleware, ProviderError, RpcError};
+use ethers::types::{Address, BlockId, BlockNumber, Bytes, H256};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 use std::sync::Arc;
+use thiserror::Error;
 use tokio::sync::RwLock;
 
 use crate::repositories::ethers_repository::EthersRepository;
+use crate::rpc_transport::{RpcPool, RpcProvider};
+use crate::services::ethers::abi_registry_service::{AbiRegistryError, AbiRegistryService, AbiSource};
+use crate::services::ethers::address_inspection_service::AddressInspectionService;
 use crate::utils::ethers_utils::EthersUtils;
 
 pub struct CallFunctionsService {
     repository: Arc<RwLock<EthersRepository>>,
+    abi_registry: Arc<AbiRegistryService>,
+}
+
+#[derive(Error, Debug)]
+pub enum BlockError {
+    #[error("{0}")]
+    InvalidBlock(String),
+    #[error("Bloco {0:?} não encontrado")]
+    NotFound(BlockId),
+    #[error("Erro ao obter o bloco: {0}")]
+    RpcError(#[from] ProviderError),
+}
+
+#[derive(Error, Debug)]
+pub enum CallFunctionsError {
+    #[error("Nenhuma conexão configurada para o user_id {0} na chain {1}")]
+    NotFound(i32, u64),
+    #[error("Endereço inválido {0}: {1}")]
+    InvalidAddress(String, String),
+    #[error("Quórum {0} inválido: há {1} providers conectados")]
+    InvalidQuorum(usize, usize),
+    #[error(transparent)]
+    AbiError(#[from] AbiRegistryError),
+    #[error(transparent)]
+    BlockError(#[from] BlockError),
+}
+
+/// Motivo da falha de uma função, serializado com o tipo em `kind`.
+///
+/// - `invalid_abi`: a ABI da chamada não foi informada, não está registrada ou é inválida.
+/// - `unknown_function`: a função, ou uma sobrecarga com a quantidade de argumentos, não existe na ABI.
+/// - `invalid_arguments`: o endereço do contrato ou algum argumento não pôde ser convertido para o
+///   tipo da ABI.
+/// - `revert`: a execução reverteu; `reason` traz os dados decodificados quando o seletor é conhecido.
+/// - `invalid_output`: o retorno não corresponde às saídas da ABI (por exemplo, endereço sem código).
+/// - `rpc`: todos os providers falharam.
+/// - `no_quorum`: os providers não chegaram ao quórum pedido.
+#[derive(Serialize, Clone, Debug, PartialEq, Error)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CallError {
+    #[error("{message}")]
+    InvalidAbi { message: String },
+    #[error("{message}")]
+    UnknownFunction { message: String },
+    #[error("{message}")]
+    InvalidArguments { message: String },
+    #[error("Execução revertida: {message}")]
+    Revert {
+        message: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reason: Option<Value>,
+        data: Bytes,
+    },
+    #[error("{message}")]
+    InvalidOutput { message: String },
+    #[error("{message}")]
+    Rpc { message: String },
+    #[error("{message}")]
+    NoQuorum { message: String },
+}
+
+impl CallError {
+    /// Monta o erro de revert, decodificando os dados com `EthersUtils::decode_revert`.
+    pub fn revert(abi: Option<&Abi>, data: Bytes) -> Self {
+        let (message, reason) = match EthersUtils::decode_revert(abi, &data) {
+            Some((message, reason)) => (message, Some(reason)),
+            None if data.is_empty() => ("sem dados de revert".to_string(), None),
+            None => (format!("{}", data), None),
+        };
+
+        CallError::Revert { message, reason, data }
+    }
+
+    /// Classifica a falha de um `eth_call`. Nós que respondem ao revert com um erro JSON-RPC sem
+    /// dados (apenas "execution reverted") também resultam em `CallError::Revert`.
+    pub fn from_provider_error(abi: Option<&Abi>, error: &ProviderError) -> Self {
+        match error.as_error_response() {
+            Some(response) if response.message.contains("revert") => {
+                CallError::revert(abi, response.as_revert_data().unwrap_or_default())
+            }
+            _ => CallError::Rpc { message: error.to_string() },
+        }
+    }
+}
+
+/// Resultado de uma função: `{"value": ...}` ou `{"error": {"kind": ..., ...}}`.
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FunctionResult {
+    Value(Value),
+    Error(CallError),
+}
+
+impl From<Result<Value, CallError>> for FunctionResult {
+    fn from(result: Result<Value, CallError>) -> Self {
+        match result {
+            Ok(value) => FunctionResult::Value(value),
+            Err(error) => FunctionResult::Error(error),
+        }
+    }
+}
+
+/// Bloco em que as chamadas foram executadas. Em `pending` o hash não existe.
+#[derive(Serialize, Clone, Copy)]
+pub struct BlockRef {
+    pub number: Option<u64>,
+    pub hash: Option<H256>,
+}
+
+/// Bloco resolvido para execução: `id` fixa as chamadas no mesmo bloco em todos os providers.
+pub struct ResolvedBlock {
+    pub id: BlockId,
+    pub block: BlockRef,
+}
+
+/// Opções de leitura: quórum entre os providers do pool e bloco em que as chamadas são executadas.
+#[derive(Deserialize, Clone, Default)]
+pub struct CallOptions {
+    #[serde(default)]
+    pub quorum: Option<usize>,
+    #[serde(default)]
+    pub block: Option<Value>,
+}
+
+#[derive(Serialize)]
+pub struct CallFunctionsResponse {
+    pub block: BlockRef,
+    pub results: HashMap<String, FunctionResult>,
+}
+
+/// Chamada de uma função do contrato. Os argumentos, um array na ordem da ABI ou um objeto indexado
+/// pelos nomes das entradas, são convertidos para os tipos de entrada da função na ABI (veja
+/// `EthersUtils::params_from_json`). O resultado é retornado na chave
+/// `key` ou, se omitida, no nome da função.
+#[derive(Deserialize, Clone)]
+pub struct FunctionCall {
+    pub function: String,
+    #[serde(default)]
+    pub args: Value,
+    #[serde(default)]
+    pub key: Option<String>,
+}
+
+impl FunctionCall {
+    pub fn new(function: String) -> Self {
+        FunctionCall { function, args: Value::Null, key: None }
+    }
+
+    pub fn key(&self) -> String {
+        self.key.clone().unwrap_or_else(|| self.function.clone())
+    }
 }
 
 impl CallFunctionsService {
-    pub fn new(repository: Arc<RwLock<EthersRepository>>) -> Self {
-        CallFunctionsService { repository }
+    pub fn new(repository: Arc<RwLock<EthersRepository>>, abi_registry: Arc<AbiRegistryService>) -> Self {
+        CallFunctionsService { repository, abi_registry }
     }
 
-    async fn get_provider(&self, user_id: i32) -> Arc<Provider<Ws>> {
+    async fn get_pool(&self, user_id: i32, chain_id: u64) -> Result<RpcPool, CallFunctionsError> {
         let lock = self.repository.read().await;
-        lock.get_connection(user_id).expect("Erro na obtenção da conexão com o Provider")
+        lock.get_pool(user_id, chain_id).ok_or(CallFunctionsError::NotFound(user_id, chain_id))
+    }
+
+    /// Resolve o bloco pedido (ou `latest`) em número e hash. As chamadas são então fixadas nesse
+    /// número, ou no hash quando o bloco foi pedido por hash, de modo que failover e quórum leiam o
+    /// mesmo estado mesmo entre nós com alturas diferentes. `pending` não pode ser fixado e é usado
+    /// como tag.
+    pub async fn resolve_block(pool: &RpcPool, block: Option<&Value>) -> Result<ResolvedBlock, BlockError> {
+        let id = block
+            .map(EthersUtils::parse_block_id)
+            .transpose()
+            .map_err(BlockError::InvalidBlock)?
+            .unwrap_or(BlockId::Number(BlockNumber::Latest));
+
+        let header = pool
+            .failover(|provider| async move { provider.get_block(id).await })
+            .await?
+            .ok_or(BlockError::NotFound(id))?;
+
+        let number = header.number.map(|number| number.as_u64());
+
+        let pinned = match (id, number) {
+            (BlockId::Number(BlockNumber::Pending), _) | (_, None) => id,
+            (BlockId::Hash(hash), _) => BlockId::Hash(hash),
+            (_, Some(number)) => BlockId::Number(BlockNumber::Number(number.into())),
+        };
+
+        Ok(ResolvedBlock {
+            id: pinned,
+            block: BlockRef { number, hash: header.hash },
+        })
     }
 
     fn create_contract(
-        provider: Arc<Provider<Ws>>,
-        contract_address: String,
-        abi: String,
-    ) -> ContractInstance<Arc<Provider<Ws>>, Provider<Ws>> {
-        let parsed_abi: Abi = serde_json::from_str(&abi).expect("Erro ao parsear o ABI");
-        let token_address: Address = contract_address.parse().expect("Erro ao parsear Address");
-        Contract::new(token_address, parsed_abi, provider)
+        provider: Arc<RpcProvider>,
+        contract_address: Address,
+        abi: Abi,
+    ) -> ContractInstance<Arc<RpcProvider>, RpcProvider> {
+        Contract::new(contract_address, abi, provider)
+    }
+
+    /// Localiza a função na ABI, escolhendo entre sobrecargas pelo formato dos argumentos, e
+    /// converte os argumentos JSON nos tokens dos tipos de entrada.
+    pub fn encode_call(abi: &Abi, call: &FunctionCall) -> Result<(Function, Vec<Token>), CallError> {
+        let functions = abi
+            .functions_by_name(&call.function)
+            .map_err(|_| CallError::UnknownFunction {
+                message: format!("Função {} não encontrada na ABI", call.function),
+            })?;
+
+        let function = functions
+            .iter()
+            .find(|function| EthersUtils::params_match(&function.inputs, &call.args))
+            .ok_or_else(|| CallError::UnknownFunction {
+                message: format!("Nenhuma sobrecarga de {} recebe os argumentos {}", call.function, call.args),
+            })?;
+
+        let tokens = EthersUtils::params_from_json(&function.inputs, &call.args).map_err(|e| {
+            CallError::InvalidArguments { message: format!("{}: {}", call.function, e) }
+        })?;
+
+        Ok((function.clone(), tokens))
+    }
+
+    fn classify_error(abi: &Abi, error: ContractError<RpcProvider>) -> CallError {
+        match error {
+            ContractError::Revert(data) => CallError::revert(Some(abi), data),
+            ContractError::MiddlewareError { e } | ContractError::ProviderError { e } => {
+                CallError::from_provider_error(Some(abi), &e)
+            }
+            ContractError::AbiError(_) | ContractError::DetokenizationError(_) | ContractError::DecodingError(_) => {
+                CallError::InvalidOutput { message: error.to_string() }
+            }
+            error => CallError::Rpc { message: error.to_string() },
+        }
+    }
+
+    /// Executa a função e converte o retorno em JSON com `EthersUtils::outputs_to_json`.
+    async fn call(
+        contract: ContractInstance<Arc<RpcProvider>, RpcProvider>,
+        function: &Function,
+        tokens: Vec<Token>,
+        block: BlockId,
+    ) -> Result<Value, CallError> {
+        let result: Result<Token, _> = match contract.method_hash(function.short_signature(), tokens) {
+            Ok(method) => method.block(block).call().await,
+            Err(e) => Err(ContractError::AbiError(e)),
+        };
+        let token = result.map_err(|e| Self::classify_error(contract.abi(), e))?;
+
+        // Com mais de uma saída o ethers agrupa o retorno em uma tupla.
+        let tokens = match token {
+            Token::Tuple(tokens) if function.outputs.len() != 1 => tokens,
+            token => vec![token],
+        };
+
+        Ok(EthersUtils::outputs_to_json(&function.outputs, tokens))
     }
 
+    /// Executa a função via `RpcPool::failover_with`: se a falha for de transporte (erro de RPC),
+    /// tenta os demais providers na ordem de saúde do pool. Reverts e erros de ABI não geram
+    /// failover, pois o resultado seria o mesmo em qualquer nó.
     async fn get_call_response(
-        contract: ContractInstance<Arc<Provider<Ws>>, Provider<Ws>>,
-        function_name: &str,
-    ) -> Option<Token> {
-        match contract.method(&function_name, ()) {
-            Ok(method) => method.call().await.ok(),
-            Err(_) => None,
+        pool: &RpcPool,
+        contract_address: Address,
+        abi: &Abi,
+        function: &Function,
+        tokens: &[Token],
+        block: BlockId,
+    ) -> Result<Value, CallError> {
+        pool.failover_with(
+            |provider| {
+                let contract = Self::create_contract(provider, contract_address, abi.clone());
+                async move { Self::call(contract, function, tokens.to_vec(), block).await }
+            },
+            |error| matches!(error, CallError::Rpc { .. }),
+        )
+        .await
+    }
+
+    /// Executa a função em todos os providers do pool em paralelo e só retorna um resultado quando
+    /// ao menos `quorum` deles responderem exatamente o mesmo valor ou o mesmo revert. Falhas de
+    /// RPC não contam como voto e ficam registradas na saúde do endpoint.
+    async fn get_quorum_response(
+        pool: &RpcPool,
+        contract_address: Address,
+        abi: &Abi,
+        function: &Function,
+        tokens: &[Token],
+        quorum: usize,
+        block: BlockId,
+    ) -> Result<Value, CallError> {
+        let results = pool
+            .broadcast(
+                |provider| {
+                    let contract = Self::create_contract(provider, contract_address, abi.clone());
+                    async move { Self::call(contract, function, tokens.to_vec(), block).await }
+                },
+                |error| matches!(error, CallError::Rpc { .. }),
+            )
+            .await;
+
+        let mut votes: Vec<(Result<Value, CallError>, usize)> = Vec::new();
+        let mut responses = 0;
+
+        for result in results {
+            if matches!(result, Err(CallError::Rpc { .. })) {
+                continue;
+            }
+            responses += 1;
+            match votes.iter_mut().find(|(voted, _)| *voted == result) {
+                Some((_, count)) => *count += 1,
+                None => votes.push((result, 1)),
+            }
+        }
+
+        votes
+            .into_iter()
+            .find(|(_, count)| *count >= quorum)
+            .map(|(result, _)| result)
+            .unwrap_or_else(|| {
+                Err(CallError::NoQuorum {
+                    message: format!(
+                        "Quórum de {} não atingido: {} de {} providers responderam",
+                        quorum,
+                        responses,
+                        pool.size()
+                    ),
+                })
+            })
+    }
+
+    /// ABI registrada para a implementação de um proxy, da implementação final para o primeiro
+    /// proxy da cadeia.
+    async fn implementation_abi(&self, pool: &RpcPool, chain_id: u64, address: Address, block: BlockId) -> Option<Abi> {
+        let chain = AddressInspectionService::proxy_chain(pool, block, address)
+            .await
+            .map_err(|e| log::warn!("Erro ao resolver a implementação do proxy {:?}: {}", address, e))
+            .ok()?;
+
+        for link in chain.links.iter().rev() {
+            let source = AbiSource::default();
+            if let Ok(abi) = self.abi_registry.resolve(&source, chain_id, Some(link.implementation)).await {
+                return Some(abi);
+            }
         }
+
+        None
     }
 
+    /// Executa as funções no contrato. Entradas inválidas da requisição (conexão inexistente, ABI
+    /// ou endereço inválidos, bloco inexistente) retornam erro; falhas de cada função são
+    /// retornadas no resultado da própria função.
+    ///
+    /// A ABI pode ser informada na requisição, referenciada por `abi_id` ou, sem nenhum dos dois,
+    /// obtida do registro de ABIs pelo endereço do contrato. Se o contrato for um proxy sem ABI
+    /// registrada, é usada a ABI registrada para a sua implementação.
     pub async fn exec(
         &self,
         user_id: i32,
+        chain_id: u64,
         contract_address: String,
-        function_names: Vec<String>,
-        abi: String,
-    ) -> HashMap<String, Value> {
-        let provider = self.get_provider(user_id).await;
-        let contract = Self::create_contract(provider.clone(), contract_address, abi);
-        let mut functions_response: HashMap<String, Value> = HashMap::new();
-
-        for function_name in function_names {
-            if let Some(token) = Self::get_call_response(contract.clone(), &function_name).await {
-                let token_to_json = EthersUtils::token_to_json(token);
-                functions_response.insert(function_name, token_to_json);
-            }
+        calls: Vec<FunctionCall>,
+        abi: AbiSource,
+        options: CallOptions,
+    ) -> Result<CallFunctionsResponse, CallFunctionsError> {
+        let pool = self.get_pool(user_id, chain_id).await?;
+        let contract_address = contract_address
+            .parse::<Address>()
+            .map_err(|e| CallFunctionsError::InvalidAddress(contract_address.clone(), e.to_string()))?;
+        // Um quórum que o pool não consegue atingir falharia em todas as funções; rejeita antes de
+        // consultar os providers.
+        if let Some(quorum) = options.quorum.filter(|quorum| *quorum == 0 || *quorum > pool.size()) {
+            return Err(CallFunctionsError::InvalidQuorum(quorum, pool.size()));
         }
+        let resolved = Self::resolve_block(&pool, options.block.as_ref()).await?;
+        let abi = match self.abi_registry.resolve(&abi, chain_id, Some(contract_address)).await {
+            Err(AbiRegistryError::NotFound(key)) if abi.is_empty() => self
+                .implementation_abi(&pool, chain_id, contract_address, resolved.id)
+                .await
+                .ok_or(AbiRegistryError::NotFound(key))?,
+            result => result?,
+        };
+        let quorum = options.quorum;
+        let mut functions_response: HashMap<String, FunctionResult> = HashMap::new();
 
-        functions_response
+        for call in calls {
+            let response = match Self::encode_call(&abi, &call) {
+                Ok((function, tokens)) => match quorum {
+                    Some(quorum) => {
+                        Self::get_quorum_response(&pool, contract_address, &abi, &function, &tokens, quorum, resolved.id).await
+                    }
+                    None => Self::get_call_response(&pool, contract_address, &abi, &function, &tokens, resolved.id).await,
+                },
+                Err(error) => Err(error),
+            };
+
+            functions_response.insert(call.key(), response.into());
+        }
+
+        Ok(CallFunctionsResponse {
+            block: resolved.block,
+            results: functions_response,
+        })
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,362 @@
+use crate::repositories::block_hub::{BlockHub, REORG_WINDOW};
+use crate::repositories::ethers_repository::{ConnectionHealth, EthersRepository};
+use crate::rpc_transport::{RpcPool, RpcProvider};
+use ethers::prelude::{Block, BlockNumber, H256};
+use ethers::providers::{Middleware, ProviderError};
+use futures::stream::StreamExt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::sync::RwLock;
+
+/// Espera inicial entre tentativas de reconexão; dobra a cada falha até `MAX_BACKOFF`.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Tempo máximo sem receber um bloco antes de considerar a conexão travada.
+const BLOCK_STALL_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Intervalo entre consultas do bloco mais recente em transportes sem assinaturas.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(4);
+
+/// Quantidade de falhas consecutivas de polling até considerar a conexão perdida.
+const MAX_POLL_FAILURES: u32 = 5;
+
+/// Intervalo entre tentativas de reconectar os endpoints do pool que estão indisponíveis.
+const ENDPOINT_RECOVERY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Mantém viva a conexão de um usuário.
+///
+/// O supervisor é o dono da fonte de blocos do usuário: a assinatura `newHeads` em transportes
+/// com pubsub (WebSocket/IPC) ou o polling do bloco `latest` em HTTP. Os blocos são publicados no
+/// `BlockHub` da conexão, que os distribui para todos os consumidores inscritos, sempre em alturas
+/// contíguas (veja `deliver`). Quando o stream termina (queda do socket após o esgotamento das
+/// reconexões internas do `ethers`), fica `BLOCK_STALL_TIMEOUT` sem entregar blocos ou o polling
+/// falha `MAX_POLL_FAILURES` vezes seguidas, o supervisor:
+/// 1. Reconecta os endpoints do usuário com backoff exponencial, indefinidamente. O primeiro
+///    endpoint saudável, em ordem de prioridade, passa a ser o primário (failover).
+/// 2. Substitui o pool no `EthersRepository`, o que notifica as assinaturas de logs ativas
+///    (veja `EthersRepository::watch_connection`) para que refaçam o `subscribe_logs`.
+/// 3. Refaz a assinatura de blocos no novo primário, mantendo o mesmo hub para os consumidores.
+///    As alturas mineradas durante a queda são preenchidas no primeiro bloco recebido.
+///
+/// O supervisor também detecta reorganizações da chain comparando o `parent_hash` de cada bloco
+/// com os hashes já publicados no hub (veja `extend`) e, enquanto a conexão primária estiver de pé,
+/// tenta reconectar os endpoints de failover que ficaram de fora do pool (veja `recover_endpoints`).
+pub struct ConnectionSupervisor {
+    repository: Arc<RwLock<EthersRepository>>,
+    user_id: i32,
+    chain_id: u64,
+    endpoints: Vec<String>,
+    block_hub: BlockHub,
+    health: Arc<Mutex<ConnectionHealth>>,
+}
+
+impl ConnectionSupervisor {
+    pub fn new(
+        repository: Arc<RwLock<EthersRepository>>,
+        user_id: i32,
+        chain_id: u64,
+        endpoints: Vec<String>,
+        block_hub: BlockHub,
+        health: Arc<Mutex<ConnectionHealth>>,
+    ) -> Self {
+        ConnectionSupervisor {
+            repository,
+            user_id,
+            chain_id,
+            endpoints,
+            block_hub,
+            health,
+        }
+    }
+
+    pub async fn run(self, provider: Arc<RpcProvider>) {
+        let mut provider = provider;
+
+        loop {
+            let blocks = async {
+                if provider.as_ref().as_ref().supports_pubsub() {
+                    self.forward_blocks(&provider).await
+                } else {
+                    self.poll_blocks(&provider).await
+                }
+            };
+
+            let reason = tokio::select! {
+                reason = blocks => reason,
+                never = self.recover_endpoints() => match never {},
+            };
+
+            log::warn!(
+                "Conexão primária do user_id {} na chain {} perdida ({}), reconectando",
+                self.user_id,
+                self.chain_id,
+                reason
+            );
+            self.record_error(reason);
+
+            let pool = self.reconnect().await;
+            provider = pool.primary();
+            self.repository.write().await.apply_connection(self.user_id, pool);
+            self.health.lock().unwrap().reconnects += 1;
+
+            log::info!("user_id {} reconectado na chain {}", self.user_id, self.chain_id);
+        }
+    }
+
+    /// A cada `ENDPOINT_RECOVERY_INTERVAL`, tenta conectar os endpoints indisponíveis do pool e
+    /// registra no repositório o pool com os que voltaram. Nunca termina; é cancelada junto da
+    /// fonte de blocos em `run`.
+    async fn recover_endpoints(&self) -> std::convert::Infallible {
+        loop {
+            tokio::time::sleep(ENDPOINT_RECOVERY_INTERVAL).await;
+
+            let Some(pool) = self.repository.read().await.get_pool(self.user_id, self.chain_id) else {
+                continue;
+            };
+
+            if pool.unavailable().next().is_none() {
+                continue;
+            }
+
+            if let Some(pool) = pool.with_recovered().await {
+                if self.repository.write().await.update_pool(self.user_id, pool) {
+                    log::info!(
+                        "Endpoints reconectados ao pool do user_id {} na chain {}",
+                        self.user_id,
+                        self.chain_id
+                    );
+                }
+            }
+        }
+    }
+
+    /// Supervisiona uma conexão que ainda não conectou, como uma restaurada do Redis com os
+    /// endpoints fora do ar: reconecta com o mesmo backoff de uma queda, registra o pool no
+    /// repositório e segue como `run`.
+    pub async fn run_disconnected(self, error: String) {
+        self.record_error(error);
+
+        let pool = self.reconnect().await;
+        let provider = pool.primary();
+        self.repository.write().await.apply_connection(self.user_id, pool);
+
+        log::info!("user_id {} conectado na chain {}", self.user_id, self.chain_id);
+        self.run(provider).await
+    }
+
+    fn record_error(&self, error: String) {
+        self.health.lock().unwrap().last_error = Some(error);
+    }
+
+    /// Publica o bloco para os consumidores garantindo alturas contíguas.
+    ///
+    /// A altura do último bloco publicado fica no `BlockHub`, compartilhado entre reconexões e
+    /// trocas de supervisor. Se o bloco recebido pular alturas (o `newHeads` não reenvia os blocos
+    /// minerados durante uma queda, e alguns nós pulam alturas sob carga), as alturas faltantes são
+    /// buscadas via RPC e publicadas antes dele. Em caso de erro no preenchimento, nada além do
+    /// último bloco publicado é considerado entregue, então o próximo bloco recebido retoma o
+    /// preenchimento do mesmo ponto.
+    async fn deliver(&self, provider: &RpcProvider, block: Block<H256>) -> Result<(), ProviderError> {
+        let Some(number) = block.number.map(|number| number.as_u64()) else {
+            return Ok(());
+        };
+
+        if let Some(last) = self.block_hub.head().filter(|last| number > last + 1) {
+            log::warn!(
+                "Lacuna de blocos {}..={} na chain {} do user_id {}, preenchendo via RPC",
+                last + 1,
+                number - 1,
+                self.chain_id,
+                self.user_id
+            );
+
+            for missing in last + 1..number {
+                let block = provider.get_block(missing).await?.ok_or_else(|| {
+                    ProviderError::CustomError(format!("Bloco {} não encontrado", missing))
+                })?;
+                self.extend(provider, block).await?;
+            }
+        }
+
+        self.extend(provider, block).await
+    }
+
+    /// Publica o bloco se ele estender a chain canônica do hub; caso contrário, resolve o reorg.
+    ///
+    /// **Passo a passo**:
+    /// 1. Um bloco cujo hash já está publicado na mesma altura é ignorado.
+    /// 2. Partindo do bloco, os pais são buscados por hash até encontrar um ancestral presente na
+    ///    janela canônica do hub (ou até sair da janela, em um reorg mais profundo que `REORG_WINDOW`).
+    /// 3. Os blocos acima do ancestral são retratados via `BlockHub::retract`, o que notifica os
+    ///    consumidores com `BlockEvent::Reorged`.
+    /// 4. O novo ramo é publicado do bloco mais baixo para o mais alto.
+    async fn extend(&self, provider: &RpcProvider, block: Block<H256>) -> Result<(), ProviderError> {
+        let (Some(number), Some(hash)) = (block.number.map(|number| number.as_u64()), block.hash) else {
+            return Ok(());
+        };
+
+        if self.block_hub.canonical_hash(number) == Some(hash) {
+            log::debug!("Bloco {} já publicado para o user_id {}, ignorando", number, self.user_id);
+            return Ok(());
+        }
+
+        let head = self.block_hub.head();
+
+        if head.is_some_and(|head| number <= head) && self.block_hub.canonical_hash(number.saturating_sub(1)).is_none() {
+            log::warn!(
+                "Bloco {} ({:?}) abaixo da janela de reorg do user_id {} na chain {}, ignorando",
+                number,
+                hash,
+                self.user_id,
+                self.chain_id
+            );
+            return Ok(());
+        }
+
+        let mut branch = vec![block];
+
+        loop {
+            let tip = branch.last().expect("o ramo sempre contém o bloco recebido");
+            let tip_number = tip.number.map(|number| number.as_u64()).unwrap_or_default();
+
+            if tip_number == 0 {
+                break;
+            }
+
+            match self.block_hub.canonical_hash(tip_number - 1) {
+                Some(parent) if parent == tip.parent_hash => break,
+                Some(_) => {}
+                None => {
+                    if head.is_some_and(|head| tip_number - 1 <= head) {
+                        log::error!(
+                            "Reorg mais profundo que {} blocos na chain {} do user_id {}",
+                            REORG_WINDOW,
+                            self.chain_id,
+                            self.user_id
+                        );
+                    }
+                    break;
+                }
+            }
+
+            let parent_hash = tip.parent_hash;
+            let parent = provider.get_block(parent_hash).await?.ok_or_else(|| {
+                ProviderError::CustomError(format!("Bloco {:?} não encontrado", parent_hash))
+            })?;
+            branch.push(parent);
+        }
+
+        let ancestor = branch
+            .last()
+            .and_then(|block| block.number)
+            .map(|number| number.as_u64().saturating_sub(1))
+            .unwrap_or_default();
+
+        if head.is_some_and(|head| head > ancestor) {
+            let orphans = self.block_hub.retract(ancestor);
+            log::warn!(
+                "Reorg na chain {} do user_id {}: {} blocos órfãos acima do bloco {}",
+                self.chain_id,
+                self.user_id,
+                orphans.len(),
+                ancestor
+            );
+        }
+
+        for block in branch.into_iter().rev() {
+            self.publish(block);
+        }
+
+        Ok(())
+    }
+
+    /// Registra o bloco na saúde da conexão e o publica no hub.
+    fn publish(&self, block: Block<H256>) {
+        {
+            let mut health = self.health.lock().unwrap();
+            health.last_block = block.number.map(|number| number.as_u64());
+            health.last_block_at = Some(SystemTime::now());
+        }
+
+        self.block_hub.publish(block);
+    }
+
+    /// Repassa os blocos da assinatura até o stream terminar ou travar. Retorna o motivo da parada.
+    async fn forward_blocks(&self, provider: &RpcProvider) -> String {
+        let mut stream = match provider.subscribe_blocks().await {
+            Ok(stream) => stream,
+            Err(e) => return format!("Erro ao assinar blocos: {}", e),
+        };
+
+        loop {
+            match tokio::time::timeout(BLOCK_STALL_TIMEOUT, stream.next()).await {
+                Ok(Some(block)) => {
+                    if let Err(e) = self.deliver(provider, block).await {
+                        return format!("Erro ao preencher lacuna de blocos: {}", e);
+                    }
+                }
+                Ok(None) => return "Assinatura de blocos encerrada".to_string(),
+                Err(_) => {
+                    return format!("Nenhum bloco recebido em {}s", BLOCK_STALL_TIMEOUT.as_secs())
+                }
+            }
+        }
+    }
+
+    /// Consulta o bloco mais recente periodicamente e o publica (junto das alturas intermediárias,
+    /// via `deliver`) até que o polling falhe `MAX_POLL_FAILURES` vezes seguidas. Retorna o motivo
+    /// da parada.
+    async fn poll_blocks(&self, provider: &RpcProvider) -> String {
+        let mut failures = 0;
+        let mut last_error = String::new();
+
+        while failures < MAX_POLL_FAILURES {
+            tokio::time::sleep(POLL_INTERVAL).await;
+
+            let result = match provider.get_block(BlockNumber::Latest).await {
+                Ok(Some(block)) => self.deliver(provider, block).await,
+                Ok(None) => Ok(()),
+                Err(e) => Err(e),
+            };
+
+            match result {
+                Ok(()) => failures = 0,
+                Err(e) => {
+                    failures += 1;
+                    log::warn!("Erro no polling de blocos do user_id {}: {}", self.user_id, e);
+                    last_error = e.to_string();
+                }
+            }
+        }
+
+        format!("{} falhas seguidas no polling: {}", failures, last_error)
+    }
+
+    async fn reconnect(&self) -> RpcPool {
+        let mut backoff = INITIAL_BACKOFF;
+
+        loop {
+            tokio::time::sleep(backoff).await;
+
+            match RpcPool::connect(&self.endpoints).await {
+                Ok(pool) if pool.chain_id() == self.chain_id => return pool,
+                Ok(pool) => {
+                    let error = format!(
+                        "Endpoints agora respondem pela chain {} em vez de {}",
+                        pool.chain_id(),
+                        self.chain_id
+                    );
+                    log::error!("user_id {}: {}", self.user_id, error);
+                    self.record_error(error);
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+                Err(e) => {
+                    log::warn!("Falha ao reconectar user_id {} na chain {}: {}", self.user_id, self.chain_id, e);
+                    self.record_error(format!("Falha ao reconectar: {}", e));
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use crate::services::ethers::abi_registry_service::{AbiRegistryError, AbiRegistryService, AbiSource};
+use crate::utils::ethers_utils::EthersUtils;
+use ethers::types::{Address, Bytes, H256};
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum DecodeError {
+    #[error(transparent)]
+    AbiError(#[from] AbiRegistryError),
+    #[error("Informe `calldata` ou `topics` para decodificar")]
+    MissingInput,
+    #[error("{0}")]
+    DecodeFailed(String),
+}
+
+/// Dados a decodificar: o calldata de uma transação ou os tópicos e dados de um log. A ABI pode
+/// ser informada (`abi`), referenciada por `abi_id` ou obtida do registro pelo contrato (`chain_id`
+/// e `address`).
+#[derive(Deserialize)]
+pub struct DecodeRequest {
+    #[serde(flatten)]
+    pub abi: AbiSource,
+    #[serde(default)]
+    pub chain_id: Option<u64>,
+    #[serde(default)]
+    pub address: Option<Address>,
+    #[serde(default)]
+    pub calldata: Option<Bytes>,
+    #[serde(default)]
+    pub topics: Vec<H256>,
+    #[serde(default)]
+    pub data: Bytes,
+}
+
+pub struct DecodeService {
+    abi_registry: Arc<AbiRegistryService>,
+}
+
+impl DecodeService {
+    pub fn new(abi_registry: Arc<AbiRegistryService>) -> Self {
+        DecodeService { abi_registry }
+    }
+
+    /// Decodifica calldata ou log com a ABI da requisição, no mesmo formato JSON das respostas de
+    /// `call_functions` e dos webhooks de eventos.
+    pub async fn exec(&self, request: DecodeRequest) -> Result<Value, DecodeError> {
+        let contract = request.chain_id.zip(request.address);
+        let abi = self
+            .abi_registry
+            .resolve(&request.abi, request.chain_id.unwrap_or_default(), contract.map(|(_, address)| address))
+            .await?;
+
+        match (request.calldata, request.topics.is_empty()) {
+            (Some(calldata), _) => EthersUtils::decode_calldata(&abi, &calldata),
+            (None, false) => EthersUtils::decode_log(&abi, &request.topics, &request.data),
+            (None, true) => return Err(DecodeError::MissingInput),
+        }
+        .map_err(DecodeError::DecodeFailed)
+    }
+}
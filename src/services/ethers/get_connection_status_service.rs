@@ -0,0 +1,127 @@
+use crate::repositories::ethers_repository::EthersRepository;
+use crate::rpc_transport::RpcPool;
+use ethers::providers::Middleware;
+use futures::future::join_all;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Instant, UNIX_EPOCH};
+use tokio::sync::RwLock;
+
+#[derive(Serialize)]
+pub struct EndpointStatus {
+    endpoint: String,
+    primary: bool,
+    alive: bool,
+    latency_ms: Option<u128>,
+    head_block: Option<u64>,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ConnectionStatus {
+    user_id: i32,
+    chain_id: u64,
+    endpoint: String,
+    head_block: Option<u64>,
+    listener_block: Option<u64>,
+    listener_block_at: Option<u64>,
+    lag: Option<u64>,
+    reconnects: u32,
+    last_error: Option<String>,
+    block_subscribers: usize,
+    endpoints: Vec<EndpointStatus>,
+}
+
+pub struct GetConnectionStatusService {
+    repository: Arc<RwLock<EthersRepository>>,
+}
+
+impl GetConnectionStatusService {
+    pub fn new(repository: Arc<RwLock<EthersRepository>>) -> Self {
+        GetConnectionStatusService { repository }
+    }
+
+    /// Consulta `eth_blockNumber` em cada endpoint do pool, medindo a latência da chamada. Endpoints
+    /// que não conectaram aparecem no fim, sem consulta, como indisponíveis.
+    async fn probe_endpoints(pool: &RpcPool) -> Vec<EndpointStatus> {
+        let probes = pool.entries().enumerate().map(|(index, (endpoint, provider))| async move {
+            let started_at = Instant::now();
+            let result = provider.get_block_number().await;
+            let latency_ms = started_at.elapsed().as_millis();
+
+            match result {
+                Ok(head) => EndpointStatus {
+                    endpoint: endpoint.to_string(),
+                    primary: index == 0,
+                    alive: true,
+                    latency_ms: Some(latency_ms),
+                    head_block: Some(head.as_u64()),
+                    error: None,
+                },
+                Err(e) => EndpointStatus {
+                    endpoint: endpoint.to_string(),
+                    primary: index == 0,
+                    alive: false,
+                    latency_ms: None,
+                    head_block: None,
+                    error: Some(e.to_string()),
+                },
+            }
+        });
+
+        let mut statuses = join_all(probes).await;
+        statuses.extend(pool.unavailable().map(|endpoint| EndpointStatus {
+            endpoint: endpoint.to_string(),
+            primary: false,
+            alive: false,
+            latency_ms: None,
+            head_block: None,
+            error: Some("Endpoint não conectado ao pool".to_string()),
+        }));
+        statuses
+    }
+
+    /// Retorna a saúde da conexão do usuário na chain: endpoint primário, bloco mais alto entre os
+    /// endpoints, último bloco entregue pelo listener, atraso do listener em blocos, reconexões, o
+    /// último erro observado pelo `ConnectionSupervisor` e a quantidade de consumidores de blocos.
+    pub async fn exec(&self, user_id: i32, chain_id: u64) -> Option<ConnectionStatus> {
+        let (pool, health, block_subscribers) = {
+            let repository = self.repository.read().await;
+            (
+                repository.get_pool(user_id, chain_id)?,
+                repository.get_health(user_id, chain_id).unwrap_or_default(),
+                repository.get_block_subscribers(user_id, chain_id),
+            )
+        };
+
+        let endpoints = Self::probe_endpoints(&pool).await;
+        let head_block = endpoints.iter().filter_map(|status| status.head_block).max();
+        let lag = head_block
+            .zip(health.last_block)
+            .map(|(head, last)| head.saturating_sub(last));
+
+        Some(ConnectionStatus {
+            user_id,
+            chain_id,
+            endpoint: pool.primary_endpoint().to_string(),
+            head_block,
+            listener_block: health.last_block,
+            listener_block_at: health
+                .last_block_at
+                .and_then(|at| at.duration_since(UNIX_EPOCH).ok())
+                .map(|at| at.as_secs()),
+            lag,
+            reconnects: health.reconnects,
+            last_error: health.last_error,
+            block_subscribers,
+            endpoints,
+        })
+    }
+
+    pub async fn exec_by_user(&self, user_id: i32) -> Vec<ConnectionStatus> {
+        let chains = self.repository.read().await.get_chains(user_id);
+        let statuses = chains.into_iter().map(|chain_id| self.exec(user_id, chain_id));
+
+        join_all(statuses).await.into_iter().flatten().collect()
+    }
+}
@@ -1,33 +1,260 @@
+use crate::http_client::HttpClient;
+use crate::repositories::backfill_repository::{
+    unix_now, BackfillJob, BackfillKind, BackfillRepository, BackfillStatus, BlockRanges, JobState, LogBackfill,
+    LogDelivery,
+};
 use crate::repositories::elastic_repository::ElasticRepository;
 use crate::repositories::ethers_repository::EthersRepository;
+use crate::rpc_transport::RpcPool;
+use crate::utils::ethers_utils::{BlockDocument, ChainDocument};
 use ethers::middleware::Middleware;
 use ethers::prelude::{BlockNumber, Transaction};
-use ethers::types::{Block, BlockId};
-use serde_json::json;
+use ethers::providers::ProviderError;
+use ethers::types::{Block, BlockId, Filter, Log, Topic, TransactionReceipt, ValueOrArray, H256};
+use futures::StreamExt;
+use redis::RedisError;
+use serde::Serialize;
+use serde_json::{json, Value};
 use std::sync::Arc;
-use tokio::sync::{RwLock, Semaphore, SemaphorePermit};
-use tokio::task;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::{watch, RwLock};
+
+/// Quantidade de blocos processados simultaneamente por job.
+const MAX_CONCURRENT_BLOCKS: usize = 300;
+
+/// Quantidade de intervalos consultados simultaneamente com `eth_getLogs` por job.
+const MAX_CONCURRENT_LOG_RANGES: usize = 4;
+
+/// Tamanho inicial, em blocos, dos intervalos consultados com `eth_getLogs`. Intervalos recusados
+/// pelo nó são divididos ao meio até serem aceitos.
+const LOG_RANGE_SIZE: u64 = 2_000;
+
+/// Recibos consultados simultaneamente, um a um, quando o nó não oferece `eth_getBlockReceipts`.
+const MAX_CONCURRENT_RECEIPTS: usize = 16;
+
+/// Trechos das mensagens de erro com que os nós indicam que não oferecem um método, em minúsculas.
+const UNSUPPORTED_METHOD_PATTERNS: &[&str] = &[
+    "method not found",
+    "not supported",
+    "does not exist",
+    "not available",
+    "unsupported method",
+];
+
+/// Trechos das mensagens de erro com que os nós recusam um `eth_getLogs` por excesso de resultados
+/// ou de blocos, em minúsculas. Só esses erros dividem o intervalo; os demais são tentados de novo
+/// com backoff.
+const RANGE_ERROR_PATTERNS: &[&str] = &[
+    "query returned more than",
+    "too many results",
+    "exceeds max results",
+    "range too large",
+    "range is too large",
+    "block range is too wide",
+    "block range limit exceeded",
+    "exceed maximum block range",
+    "exceeds max block range",
+    "eth_getlogs is limited to",
+    "are limited to a",
+    "response size exceeded",
+];
+
+/// Trechos das mensagens de limite de requisições, em minúsculas. Alguns provedores usam termos
+/// parecidos com os de `RANGE_ERROR_PATTERNS` nesses erros, que não se resolvem dividindo o
+/// intervalo.
+const RATE_LIMIT_PATTERNS: &[&str] = &[
+    "429",
+    "too many requests",
+    "rate limit",
+    "request limit",
+    "exceeded the quota",
+    "capacity exceeded",
+    "compute units",
+];
+
+/// Intervalo em que o progresso e os checkpoints dos jobs são gravados no Redis.
+const PROGRESS_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Novas tentativas de um bloco ou intervalo com falha antes de registrá-lo em `failed_blocks`.
+const MAX_BLOCK_RETRIES: u32 = 3;
+
+/// Espera antes da primeira nova tentativa, dobrada a cada falha até `MAX_RETRY_BACKOFF`.
+const INITIAL_RETRY_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(30);
+
+#[derive(Error, Debug)]
+pub enum BackfillError {
+    #[error("Nenhuma conexão configurada para o user_id {0} na chain {1}")]
+    NotFound(i32, u64),
+    #[error("Intervalo inválido: from_block {0} maior que to_block {1}")]
+    InvalidRange(u64, u64),
+    #[error("Filtro inválido: {0}")]
+    InvalidFilter(String),
+    #[error("Job de backfill {0} não encontrado")]
+    JobNotFound(u64),
+    #[error("Job de backfill {0} já encerrado ({1:?})")]
+    JobFinished(u64, JobState),
+    #[error("Job de backfill {0} não está em execução nesta instância")]
+    JobNotRunning(u64),
+    #[error("Erro no Redis: {0}")]
+    RedisError(#[from] RedisError),
+}
+
+/// Resultado do processamento de um intervalo pelo job (um único bloco nos backfills de blocos),
+/// com as novas tentativas feitas e os logs entregues.
+enum RangeOutcome {
+    Done { retries: u32, logs: u64 },
+    Failed { error: String, retries: u32 },
+    Cancelled,
+    ConnectionRemoved,
+}
+
+/// Documento enviado ao webhook de um backfill de logs: os logs de um intervalo consultado.
+#[derive(Serialize)]
+struct LogBatch {
+    from_block: u64,
+    to_block: u64,
+    logs: Vec<Log>,
+}
+
+/// Documento do índice `transactions`: a transação com o timestamp do bloco em que foi incluída.
+#[derive(Serialize)]
+struct TransactionDocument {
+    #[serde(flatten)]
+    transaction: Transaction,
+    #[serde(rename = "blockTimestamp")]
+    block_timestamp: u64,
+}
 
 #[derive(Clone)]
 pub struct GetLogsService {
     repository: Arc<RwLock<EthersRepository>>,
     elastic_repository: Arc<ElasticRepository>,
+    backfill_repository: Arc<BackfillRepository>,
+    http_client: HttpClient,
 }
 
 impl GetLogsService {
     pub fn new(
         repository: Arc<RwLock<EthersRepository>>,
         elastic_repository: Arc<ElasticRepository>,
+        backfill_repository: Arc<BackfillRepository>,
+        http_client: HttpClient,
     ) -> Self {
         GetLogsService {
             repository,
             elastic_repository,
+            backfill_repository,
+            http_client,
+        }
+    }
+
+    /// Id dos documentos do índice `logs`, a mesma chave que liga o log ao recibo da transação.
+    fn log_document_id(chain_id: u64, log: &Log) -> String {
+        format!(
+            "{}-{:?}-{}",
+            chain_id,
+            log.transaction_hash.unwrap_or_default(),
+            log.log_index.unwrap_or_default()
+        )
+    }
+
+    fn is_unsupported_method(error: &ProviderError) -> bool {
+        let message = error.to_string().to_lowercase();
+        UNSUPPORTED_METHOD_PATTERNS.iter().any(|pattern| message.contains(pattern))
+    }
+
+    /// Recibos das transações do bloco, com `eth_getBlockReceipts` ou, se o nó não oferecer o
+    /// método, com um `eth_getTransactionReceipt` por transação.
+    async fn block_receipts(pool: &RpcPool, block: &Block<Transaction>) -> Result<Vec<TransactionReceipt>, String> {
+        let number = block.number.unwrap_or_default();
+        let receipts = match pool
+            .failover(|provider| async move { provider.get_block_receipts(number).await })
+            .await
+        {
+            Ok(receipts) => receipts,
+            Err(e) if Self::is_unsupported_method(&e) => {
+                let hashes: Vec<H256> = block.transactions.iter().map(|transaction| transaction.hash).collect();
+                futures::stream::iter(hashes)
+                    .map(|hash| async move {
+                        pool.failover(|provider| async move { provider.get_transaction_receipt(hash).await })
+                            .await
+                            .map_err(|e| e.to_string())?
+                            .ok_or_else(|| format!("Recibo da transação {:?} não encontrado", hash))
+                    })
+                    .buffered(MAX_CONCURRENT_RECEIPTS)
+                    .collect::<Vec<_>>()
+                    .await
+                    .into_iter()
+                    .collect::<Result<Vec<_>, String>>()?
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+
+        if receipts.len() != block.transactions.len() {
+            return Err(format!(
+                "{} recibos retornados para as {} transações do bloco {}",
+                receipts.len(),
+                block.transactions.len(),
+                number
+            ));
         }
+
+        Ok(receipts)
     }
 
-    async fn save_tx_if_block_not_saved(&self, block: Block<Transaction>) {
-        // let lock_elastic = self.elastic_repository.;
+    /// Indexa os recibos no índice `receipts` e os logs deles no índice `logs`. Os recibos são
+    /// indexados sem os logs, com a quantidade em `logCount`; o hash da transação e a posição do
+    /// log ligam os documentos.
+    async fn save_receipts(&self, chain_id: u64, receipts: Vec<TransactionReceipt>) -> Result<(), String> {
+        let mut receipt_documents = Vec::with_capacity(receipts.len());
+        let mut log_documents = Vec::new();
+
+        for mut receipt in receipts {
+            let logs = std::mem::take(&mut receipt.logs);
+            let mut document = serde_json::to_value(&receipt).map_err(|e| e.to_string())?;
+            if let Value::Object(fields) = &mut document {
+                fields.remove("logs");
+                fields.insert("logCount".to_string(), json!(logs.len()));
+            }
+
+            receipt_documents.push((
+                format!("{}-{:?}", chain_id, receipt.transaction_hash),
+                ChainDocument::new(chain_id, document),
+            ));
+            log_documents.extend(
+                logs.into_iter()
+                    .map(|log| (Self::log_document_id(chain_id, &log), ChainDocument::new(chain_id, log))),
+            );
+        }
+
+        if !receipt_documents.is_empty() {
+            self.elastic_repository
+                .index_bulk_documents_with_ids("receipts", receipt_documents)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        if !log_documents.is_empty() {
+            self.elastic_repository
+                .index_bulk_documents_with_ids("logs", log_documents)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        Ok(())
+    }
 
+    /// Indexa o cabeçalho do bloco no índice `blocks` e as transações, os recibos e os logs dele, se
+    /// as transações ainda não estiverem indexadas. As transações são indexadas por último: se algo
+    /// falhar antes, o bloco continua como não indexado e é processado por completo na nova
+    /// tentativa.
+    ///
+    /// O cabeçalho é indexado sempre, com id determinístico, para que blocos indexados antes do
+    /// índice `blocks` existir também apareçam nele ao serem reprocessados. Pelo mesmo motivo, as
+    /// transações já indexadas sem `blockTimestamp` recebem o campo via `update_by_query`, e os
+    /// recibos e logs são indexados se o bloco ainda não tiver recibos no índice `receipts`.
+    async fn save_tx_if_block_not_saved(&self, pool: &RpcPool, chain_id: u64, block: Block<Transaction>) -> Result<(), String> {
         let docs_filtrados = self.elastic_repository
             .search::<serde_json::Value>(
                 "transactions",
@@ -36,43 +263,593 @@ impl GetLogsService {
                         "bool": {
                             "must": [
                                 { "match": {"blockHash": block.hash}},
+                                { "term": {"chain_id": chain_id}},
                             ]
                         }
                     }
                 }),
             )
             .await
-            .expect("ERR");
+            .map_err(|e| e.to_string())?;
+
+        let header = BlockDocument::from(&block);
+        let block_timestamp = header.timestamp;
 
-        if docs_filtrados.len() == 0 {
+        if !docs_filtrados.is_empty() {
+            self.save_block_header(chain_id, header).await?;
             self.elastic_repository
-                .index_bulk_documents("transactions", block.transactions)
+                .update_by_query(
+                    "transactions",
+                    &json!({
+                        "query": {
+                            "bool": {
+                                "must": [
+                                    { "match": {"blockHash": block.hash}},
+                                    { "term": {"chain_id": chain_id}},
+                                ],
+                                "must_not": [
+                                    { "exists": {"field": "blockTimestamp"}},
+                                ]
+                            }
+                        },
+                        "script": {
+                            "source": "ctx._source.blockTimestamp = params.timestamp",
+                            "params": { "timestamp": block_timestamp }
+                        }
+                    }),
+                )
+                .await
+                .map_err(|e| e.to_string())?;
+
+            let receipts_count = self
+                .elastic_repository
+                .count(
+                    "receipts",
+                    &json!({
+                        "query": {
+                            "bool": {
+                                "must": [
+                                    { "match": {"blockHash": block.hash}},
+                                    { "term": {"chain_id": chain_id}},
+                                ]
+                            }
+                        }
+                    }),
+                )
                 .await
-                .expect("TODO: panic message");
+                .map_err(|e| e.to_string())?;
+
+            if receipts_count == 0 && !block.transactions.is_empty() {
+                let receipts = Self::block_receipts(pool, &block).await?;
+                self.save_receipts(chain_id, receipts).await?;
+            }
+
+            return Ok(());
         }
+
+        let receipts = Self::block_receipts(pool, &block).await?;
+        self.save_receipts(chain_id, receipts).await?;
+        self.save_block_header(chain_id, header).await?;
+
+        // Ids determinísticos, como em `receipts`: reprocessar o bloco sobrescreve os documentos.
+        let transactions = block
+            .transactions
+            .into_iter()
+            .map(|transaction| {
+                let id = format!("{}-{:?}", chain_id, transaction.hash);
+                (id, ChainDocument::new(chain_id, TransactionDocument { transaction, block_timestamp }))
+            })
+            .collect();
+
+        self.elastic_repository
+            .index_bulk_documents_with_ids("transactions", transactions)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
     }
 
-    pub async fn exec(&self, user_id: i32, from_block: u64, to_block: u64) {
-        let mut handles = Vec::new();
-        let semaphore = Arc::new(Semaphore::new(300));
+    async fn save_block_header(&self, chain_id: u64, header: BlockDocument) -> Result<(), String> {
+        self.elastic_repository
+            .index_bulk_documents_with_ids(
+                "blocks",
+                vec![(format!("{}-{:?}", chain_id, header.hash), ChainDocument::new(chain_id, header))],
+            )
+            .await
+            .map_err(|e| e.to_string())
+    }
 
-        for block_number in from_block..=to_block {
-            let provider = self.repository.read().await.get_connection(user_id).expect("ERR ");
-            let semaphore = Arc::clone(&semaphore);
-            let my_clone = self.clone();
+    /// Aguarda enquanto o job estiver pausado. Retorna `false` se ele for cancelado.
+    async fn wait_running(control: &mut watch::Receiver<JobState>) -> bool {
+        loop {
+            match *control.borrow_and_update() {
+                JobState::Running => return true,
+                JobState::Paused => {}
+                _ => return false,
+            }
+
+            if control.changed().await.is_err() {
+                return false;
+            }
+        }
+    }
 
-            let handle = task::spawn(async move {
-                let lock:SemaphorePermit = semaphore.acquire().await.unwrap();
+    async fn save_block(&self, pool: &RpcPool, chain_id: u64, block_number: u64) -> Result<(), String> {
+        let block_id = BlockId::Number(BlockNumber::Number(block_number.into()));
+        let block = pool
+            .failover(|provider| async move { provider.get_block_with_txs(block_id).await })
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "Bloco não encontrado".to_string())?;
 
-                let block_id = BlockId::Number(BlockNumber::Number(block_number.into()));
-                let block = provider.get_block_with_txs(block_id).await.expect("ERR").unwrap();
+        self.save_tx_if_block_not_saved(pool, chain_id, block).await
+    }
 
-                my_clone.save_tx_if_block_not_saved(block).await;
-                println!("Block {block_number} read!");
+    fn log_filter(backfill: &LogBackfill, from_block: u64, to_block: u64) -> Filter {
+        let mut filter = Filter::new().from_block(from_block).to_block(to_block);
+        if !backfill.addresses.is_empty() {
+            filter = filter.address(ValueOrArray::Array(backfill.addresses.clone()));
+        }
+        for (position, topics) in backfill.topics.iter().enumerate() {
+            filter.topics[position] = topics.as_ref().map(|topics| {
+                Topic::Array(topics.iter().copied().map(Some).collect())
             });
+        }
+
+        filter
+    }
+
+    /// Indica se o nó recusou o `eth_getLogs` pelo tamanho do intervalo ou do resultado. Erros de
+    /// limite de requisições nunca contam, mesmo que citem o intervalo.
+    fn is_range_error(error: &ProviderError) -> bool {
+        let message = error.to_string().to_lowercase();
+        RANGE_ERROR_PATTERNS.iter().any(|pattern| message.contains(pattern))
+            && !RATE_LIMIT_PATTERNS.iter().any(|pattern| message.contains(pattern))
+    }
+
+    /// Entrega os logs de `from_block..=to_block`: no índice `logs`, com ids derivados da transação
+    /// e da posição do log, para que reprocessar um intervalo não duplique documentos, ou no
+    /// webhook, em um único POST.
+    async fn deliver_logs(
+        &self,
+        chain_id: u64,
+        delivery: &LogDelivery,
+        from_block: u64,
+        to_block: u64,
+        logs: Vec<Log>,
+    ) -> Result<(), String> {
+        if logs.is_empty() {
+            return Ok(());
+        }
+
+        match delivery {
+            LogDelivery::Elasticsearch => {
+                let documents = logs
+                    .into_iter()
+                    .map(|log| (Self::log_document_id(chain_id, &log), ChainDocument::new(chain_id, log)))
+                    .collect();
+
+                self.elastic_repository
+                    .index_bulk_documents_with_ids("logs", documents)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+            LogDelivery::Webhook { url } => {
+                let document = ChainDocument::new(chain_id, LogBatch { from_block, to_block, logs });
+
+                self.http_client
+                    .get_client()
+                    .post(url)
+                    .json(&document)
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    /// Consulta e entrega os logs de `from_block..=to_block`, dividindo ao meio os intervalos que o
+    /// nó recusar por excesso de resultados ou de blocos. Retorna a quantidade de logs entregues.
+    async fn save_logs(
+        &self,
+        pool: &RpcPool,
+        chain_id: u64,
+        backfill: &LogBackfill,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<u64, String> {
+        let mut pending = vec![(from_block, to_block)];
+        let mut delivered = 0;
+
+        while let Some((from, to)) = pending.pop() {
+            let filter = Self::log_filter(backfill, from, to);
+            let result = pool
+                .failover(|provider| {
+                    let filter = filter.clone();
+                    async move { provider.get_logs(&filter).await }
+                })
+                .await;
+
+            match result {
+                Ok(logs) => {
+                    let count = logs.len() as u64;
+                    self.deliver_logs(chain_id, &backfill.delivery, from, to, logs).await?;
+                    delivered += count;
+                }
+                Err(e) if from < to && Self::is_range_error(&e) => {
+                    let middle = from + (to - from) / 2;
+                    log::debug!("Intervalo {}..={} recusado pelo nó, dividindo: {}", from, to, e);
+                    pending.push((middle + 1, to));
+                    pending.push((from, middle));
+                }
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+
+        Ok(delivered)
+    }
+
+    async fn save_range(
+        &self,
+        pool: &RpcPool,
+        chain_id: u64,
+        kind: &BackfillKind,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<u64, String> {
+        match kind {
+            BackfillKind::Blocks => {
+                for block_number in from_block..=to_block {
+                    self.save_block(pool, chain_id, block_number).await?;
+                }
+                Ok(0)
+            }
+            BackfillKind::Logs(backfill) => self.save_logs(pool, chain_id, backfill, from_block, to_block).await,
+        }
+    }
+
+    /// Processa o intervalo, repetindo com backoff exponencial até `MAX_BLOCK_RETRIES` vezes em
+    /// caso de falha.
+    async fn process_range(
+        &self,
+        user_id: i32,
+        chain_id: u64,
+        kind: &BackfillKind,
+        (from_block, to_block): (u64, u64),
+        mut control: watch::Receiver<JobState>,
+    ) -> RangeOutcome {
+        let mut backoff = INITIAL_RETRY_BACKOFF;
+        let mut retries = 0;
+
+        loop {
+            if !Self::wait_running(&mut control).await {
+                return RangeOutcome::Cancelled;
+            }
+
+            // O pool é obtido a cada tentativa para acompanhar as reconexões do supervisor.
+            let Some(pool) = self.repository.read().await.get_pool(user_id, chain_id) else {
+                return RangeOutcome::ConnectionRemoved;
+            };
+
+            match self.save_range(&pool, chain_id, kind, from_block, to_block).await {
+                Ok(logs) => return RangeOutcome::Done { retries, logs },
+                Err(error) if retries >= MAX_BLOCK_RETRIES => return RangeOutcome::Failed { error, retries },
+                Err(e) => {
+                    log::warn!(
+                        "Erro ao processar os blocos {}..={} da chain {}, nova tentativa em {:?}: {}",
+                        from_block,
+                        to_block,
+                        chain_id,
+                        backoff,
+                        e
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_RETRY_BACKOFF);
+                    retries += 1;
+                }
+            }
+        }
+    }
+
+    /// Acumula o tempo em execução do job desde a última atualização.
+    fn touch(job: &mut BackfillJob, since: &mut Instant) {
+        if job.state == JobState::Running {
+            job.active_ms += since.elapsed().as_millis() as u64;
+        }
+        job.updated_at = unix_now();
+        *since = Instant::now();
+    }
+
+    /// Grava os blocos concluídos desde a última gravação nos checkpoints e o progresso do job.
+    /// Se os checkpoints não puderem ser gravados, os blocos são mantidos para a próxima.
+    async fn save(&self, job: &mut BackfillJob, completed: &mut BlockRanges, since: &mut Instant) {
+        if !completed.is_empty() {
+            match self.backfill_repository.add_checkpoints(job, completed).await {
+                Ok(()) => completed.clear(),
+                Err(e) => log::error!("Erro ao gravar os checkpoints do job de backfill {}: {}", job.id, e),
+            }
+        }
+
+        Self::touch(job, since);
+        if let Err(e) = self.backfill_repository.save(job).await {
+            log::error!("Erro ao gravar o progresso do job de backfill {}: {}", job.id, e);
+        }
+    }
+
+    /// Processa os blocos `missing` do job, um a um nos backfills de blocos (até
+    /// `MAX_CONCURRENT_BLOCKS` simultâneos) e em intervalos de `LOG_RANGE_SIZE` nos de logs (até
+    /// `MAX_CONCURRENT_LOG_RANGES`), atendendo a pausas e cancelamentos e gravando o progresso e os
+    /// checkpoints no Redis a cada `PROGRESS_FLUSH_INTERVAL`.
+    async fn run(self, mut job: BackfillJob, missing: BlockRanges, mut control: watch::Receiver<JobState>) {
+        let (user_id, chain_id) = (job.user_id, job.chain_id);
+        let mut since = Instant::now();
+        let mut flush = tokio::time::interval(PROGRESS_FLUSH_INTERVAL);
+        let mut completed = BlockRanges::default();
+
+        let (range_size, concurrency) = match job.kind {
+            BackfillKind::Blocks => (1, MAX_CONCURRENT_BLOCKS),
+            BackfillKind::Logs(_) => (LOG_RANGE_SIZE, MAX_CONCURRENT_LOG_RANGES),
+        };
+
+        let (service, kind, blocks_control) = (self.clone(), Arc::new(job.kind.clone()), control.clone());
+        let mut blocks = futures::stream::iter(missing.into_chunks(range_size))
+            .map(move |range| {
+                let control = blocks_control.clone();
+                let (service, kind) = (service.clone(), kind.clone());
+                async move { (range, service.process_range(user_id, chain_id, &kind, range, control).await) }
+            })
+            .buffer_unordered(concurrency);
+
+        loop {
+            tokio::select! {
+                next = blocks.next() => match next {
+                    Some(((from, to), RangeOutcome::Done { retries, logs })) => {
+                        job.done += to - from + 1;
+                        job.retries += retries as u64;
+                        job.logs += logs;
+                        completed.insert(from, to);
+                    }
+                    Some(((from, to), RangeOutcome::Failed { error, retries })) => {
+                        log::error!(
+                            "Erro ao processar os blocos {}..={} da chain {} no job {} após {} tentativas: {}",
+                            from,
+                            to,
+                            chain_id,
+                            job.id,
+                            retries + 1,
+                            error
+                        );
+                        job.retries += retries as u64;
+                        job.failed_blocks.extend(from..=to);
+                    }
+                    Some((_, RangeOutcome::Cancelled)) => {}
+                    Some((_, RangeOutcome::ConnectionRemoved)) => {
+                        job.state = JobState::Failed;
+                        job.error = Some(format!("Conexão do user_id {} na chain {} removida", user_id, chain_id));
+                        break;
+                    }
+                    None => break,
+                },
+                changed = control.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    let state = *control.borrow_and_update();
+                    Self::touch(&mut job, &mut since);
+                    job.state = state;
+                    self.save(&mut job, &mut completed, &mut since).await;
+                    if state == JobState::Cancelled {
+                        break;
+                    }
+                },
+                _ = flush.tick() => self.save(&mut job, &mut completed, &mut since).await,
+            }
+        }
+
+        drop(blocks);
+        if job.state == JobState::Running {
+            job.state = JobState::Completed;
+        }
+        job.failed_blocks.sort_unstable();
+        self.save(&mut job, &mut completed, &mut since).await;
+        self.backfill_repository.unregister(job.id);
+
+        log::info!(
+            "Job de backfill {} encerrado ({:?}): {} blocos processados, {} já nos checkpoints, {} com falha, {} logs entregues",
+            job.id,
+            job.state,
+            job.done,
+            job.skipped,
+            job.failed_blocks.len(),
+            job.logs
+        );
+    }
+
+    /// Executa o job em background no estado `state`, processando apenas os blocos do intervalo
+    /// que ainda não constam nos checkpoints. O progresso de execuções anteriores é substituído:
+    /// os blocos já concluídos passam a contar em `skipped` e os que falharam são tentados de novo.
+    async fn start(&self, mut job: BackfillJob, state: JobState) -> Result<BackfillJob, BackfillError> {
+        let checkpoints = self.backfill_repository.checkpoints(&job).await?;
+        let missing = checkpoints.missing(job.from_block, job.to_block);
+
+        job.state = state;
+        job.skipped = job.total() - missing.count();
+        job.done = 0;
+        job.failed_blocks.clear();
+        job.retries = 0;
+        job.logs = 0;
+        job.active_ms = 0;
+        job.error = None;
+        job.updated_at = unix_now();
+        self.backfill_repository.save(&job).await?;
+
+        let control = self.backfill_repository.register(job.id, state);
+        tokio::spawn(self.clone().run(job.clone(), missing, control));
+
+        Ok(job)
+    }
+
+    async fn create(
+        &self,
+        user_id: i32,
+        chain_id: u64,
+        kind: BackfillKind,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<BackfillStatus, BackfillError> {
+        if from_block > to_block {
+            return Err(BackfillError::InvalidRange(from_block, to_block));
+        }
+        if self.repository.read().await.get_pool(user_id, chain_id).is_none() {
+            return Err(BackfillError::NotFound(user_id, chain_id));
+        }
+
+        let id = self.backfill_repository.next_id().await?;
+        let job = BackfillJob::new(id, user_id, chain_id, kind, from_block, to_block);
+
+        Ok(self.start(job, JobState::Running).await?.into())
+    }
+
+    /// Cria um job de backfill das transações dos blocos `from_block..=to_block` e o executa em
+    /// background. O progresso pode ser acompanhado com `status`.
+    pub async fn exec(&self, user_id: i32, chain_id: u64, from_block: u64, to_block: u64) -> Result<BackfillStatus, BackfillError> {
+        self.create(user_id, chain_id, BackfillKind::Blocks, from_block, to_block).await
+    }
+
+    /// Cria um job de backfill dos logs de `from_block..=to_block` que atendem ao filtro, consultados
+    /// com `eth_getLogs`, e o executa em background. A entrega é ao menos uma vez: um intervalo
+    /// reprocessado após uma falha é entregue de novo.
+    pub async fn exec_logs(
+        &self,
+        user_id: i32,
+        chain_id: u64,
+        from_block: u64,
+        to_block: u64,
+        backfill: LogBackfill,
+    ) -> Result<BackfillStatus, BackfillError> {
+        if backfill.topics.len() > 4 {
+            return Err(BackfillError::InvalidFilter(format!(
+                "no máximo 4 posições em `topics`, recebidas {}",
+                backfill.topics.len()
+            )));
+        }
+        if let LogDelivery::Webhook { url } = &backfill.delivery {
+            reqwest::Url::parse(url).map_err(|e| BackfillError::InvalidFilter(format!("webhook inválido {}: {}", url, e)))?;
+        }
+
+        self.create(user_id, chain_id, BackfillKind::Logs(backfill), from_block, to_block).await
+    }
+
+    /// Retoma, a partir dos checkpoints, os jobs que estavam em execução ou pausados quando a
+    /// instância foi encerrada. Jobs cuja conexão não foi restaurada são marcados como falhos.
+    /// Retorna a quantidade de jobs retomados.
+    pub async fn resume_jobs(&self) -> Result<usize, BackfillError> {
+        let mut resumed = 0;
+
+        for mut job in self.backfill_repository.list_all().await? {
+            if job.state.is_finished() {
+                continue;
+            }
+
+            if self.repository.read().await.get_pool(job.user_id, job.chain_id).is_none() {
+                job.state = JobState::Failed;
+                job.error = Some(format!(
+                    "Conexão do user_id {} na chain {} não restaurada ao retomar o job",
+                    job.user_id, job.chain_id
+                ));
+                job.updated_at = unix_now();
+                self.backfill_repository.save(&job).await?;
+                continue;
+            }
+
+            let state = job.state;
+            self.start(job, state).await?;
+            resumed += 1;
+        }
 
-            handles.push(handle);
+        Ok(resumed)
+    }
+
+    async fn get_job(&self, user_id: i32, job_id: u64) -> Result<BackfillJob, BackfillError> {
+        self.backfill_repository
+            .get(job_id)
+            .await?
+            .filter(|job| job.user_id == user_id)
+            .ok_or(BackfillError::JobNotFound(job_id))
+    }
+
+    pub async fn status(&self, user_id: i32, job_id: u64) -> Result<BackfillStatus, BackfillError> {
+        Ok(self.get_job(user_id, job_id).await?.into())
+    }
+
+    pub async fn list(&self, user_id: i32) -> Result<Vec<BackfillStatus>, BackfillError> {
+        let jobs = self.backfill_repository.list(user_id).await?;
+        Ok(jobs.into_iter().map(BackfillStatus::from).collect())
+    }
+
+    /// Pausa, retoma ou cancela o job. Um job encerrado pode ser retomado, processando apenas os
+    /// blocos que faltam nos checkpoints, exceto quando concluído sem falhas. Um job que não está
+    /// em execução nesta instância e não foi encerrado só pode ser cancelado, o que é gravado
+    /// direto no Redis.
+    pub async fn control(&self, user_id: i32, job_id: u64, state: JobState) -> Result<BackfillStatus, BackfillError> {
+        let mut job = self.get_job(user_id, job_id).await?;
+
+        if self.backfill_repository.control(job_id, state) {
+            job.state = state;
+            return Ok(job.into());
         }
 
+        match state {
+            JobState::Running if job.state.is_finished() => {
+                if job.state == JobState::Completed && job.failed_blocks.is_empty() {
+                    return Err(BackfillError::JobFinished(job_id, job.state));
+                }
+                if self.repository.read().await.get_pool(user_id, job.chain_id).is_none() {
+                    return Err(BackfillError::NotFound(user_id, job.chain_id));
+                }
+
+                Ok(self.start(job, JobState::Running).await?.into())
+            }
+            _ if job.state.is_finished() => Err(BackfillError::JobFinished(job_id, job.state)),
+            JobState::Cancelled => {
+                job.state = state;
+                job.updated_at = unix_now();
+                self.backfill_repository.save(&job).await?;
+                Ok(job.into())
+            }
+            _ => Err(BackfillError::JobNotRunning(job_id)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_range_error(message: &str) -> bool {
+        GetLogsService::is_range_error(&ProviderError::CustomError(message.to_string()))
+    }
+
+    #[test]
+    fn range_errors_split_the_range() {
+        assert!(is_range_error("query returned more than 10000 results"));
+        assert!(is_range_error(
+            "Log response size exceeded. You can make eth_getLogs requests with up to a 2K block range"
+        ));
+        assert!(is_range_error("eth_getLogs is limited to a 10,000 range"));
+        assert!(is_range_error("exceed maximum block range: 5000"));
+        assert!(is_range_error("block range is too wide"));
+    }
+
+    #[test]
+    fn rate_limits_and_other_errors_are_retried() {
+        assert!(!is_range_error("429 Too Many Requests"));
+        assert!(!is_range_error("daily request limit exceeded"));
+        assert!(!is_range_error("rate limit exceeded for block range queries"));
+        assert!(!is_range_error("Your app has exceeded its compute units per second capacity"));
+        assert!(!is_range_error("limit exceeded"));
+        assert!(!is_range_error("connection reset by peer"));
     }
 }
@@ -1,33 +1,109 @@
 use std::collections::HashMap;
+use crate::repositories::block_hub::{
+    BlockEvent, BlockSubscription, ConfirmationQueue, DeliveryJournal, OrphanedBlock,
+};
 use crate::repositories::ethers_repository::EthersRepository;
 use ethers::abi::{Abi, Address};
 use ethers::middleware::Middleware;
-use ethers::prelude::{Filter, Provider, Ws, H256, StreamExt, Transaction};
+use ethers::prelude::{Filter, H256, Log, StreamExt, Transaction, U256};
+use ethers::providers::ProviderError;
 use std::sync::Arc;
 use ethers::types::H160;
-use log::Log;
 use tokio::sync::RwLock;
 use crate::http_client::HttpClient;
+use crate::utils::ethers_utils::{ChainDocument, DeliveryOptions, DeliveryStatus, EthersUtils};
+use crate::rpc_transport::RpcProvider;
+use crate::services::ethers::abi_registry_service::{AbiRegistryError, AbiRegistryService, AbiSource};
+use crate::services::ethers::connection_supervisor::POLL_INTERVAL;
+use tokio::sync::watch;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+/// Tamanho máximo, em blocos, de cada `eth_getLogs` feito para recuperar os logs desde o último
+/// bloco entregue, para não exceder os limites dos provedores após uma queda longa.
+const MAX_LOG_RANGE: u64 = 2_000;
 
 pub struct ListenContractEventsService {
     repository: Arc<RwLock<EthersRepository>>,
+    abi_registry: Arc<AbiRegistryService>,
+}
+
+#[derive(Error, Debug)]
+pub enum ListenContractEventsError {
+    #[error("Endereço inválido {0}: {1}")]
+    InvalidAddress(String, String),
+    #[error(transparent)]
+    AbiError(#[from] AbiRegistryError),
+}
+
+/// Assinatura de eventos de um contrato. Com ABI (informada em `abi`, referenciada por `abi_id` ou
+/// registrada para o contrato), cada entrega também leva o evento e o calldata da transação
+/// decodificados.
+#[derive(Deserialize)]
+pub struct ContractEventSubscription {
+    pub address: String,
+    pub event_signature: String,
+    pub webhook: String,
+    #[serde(flatten)]
+    pub abi: AbiSource,
+}
+
+/// Documento enviado ao webhook: a transação indexada pelo endereço do contrato e, quando a
+/// assinatura tem ABI, o evento (`event`) e a chamada (`call`) decodificados.
+#[derive(Serialize, Clone)]
+struct ContractEvent {
+    #[serde(flatten)]
+    transaction: HashMap<H160, Transaction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    event: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    call: Option<Value>,
+}
+
+/// Estado de entrega de uma assinatura de eventos, preservado entre as reassinaturas: o que já foi
+/// notificado, para retratação em reorgs, o que aguarda confirmações e até onde os logs foram
+/// entregues. `next_block` é a primeira altura cujos logs podem não ter sido todos entregues, de
+/// onde a consulta recomeça após a troca do provider, e `last_log` a posição (bloco e índice) do
+/// último log entregue, para descartar os logs repetidos nessa consulta.
+struct EventDelivery {
+    chain_id: u64,
+    webhook: String,
+    contract_address: Address,
+    abi: Option<Abi>,
+    confirmations: u64,
+    notify_pending: bool,
+    journal: DeliveryJournal<ContractEvent>,
+    queue: ConfirmationQueue<ContractEvent>,
+    next_block: Option<u64>,
+    last_log: Option<(u64, U256)>,
 }
 
 impl ListenContractEventsService {
-    pub fn new(repository: Arc<RwLock<EthersRepository>>) -> Self {
-        ListenContractEventsService { repository }
+    pub fn new(repository: Arc<RwLock<EthersRepository>>, abi_registry: Arc<AbiRegistryService>) -> Self {
+        ListenContractEventsService { repository, abi_registry }
     }
 
-    pub async fn exec(&self,user_id: i32, contract_address: String, event_signature: String,webhook:String){
+    /// Inicia a assinatura dos eventos do contrato.
+    ///
+    /// Com `confirmations` maior que 0, os eventos só são enviados quando o bloco atinge essa
+    /// profundidade, com `"status": "confirmed"`. Com `notify_pending`, cada evento também é enviado
+    /// assim que aparece, com `"status": "pending"`.
+    pub async fn exec(&self,user_id: i32, chain_id: u64, subscription: ContractEventSubscription, options: DeliveryOptions) -> Result<(), ListenContractEventsError> {
+        let contract_address = subscription
+            .address
+            .parse::<Address>()
+            .map_err(|e| ListenContractEventsError::InvalidAddress(subscription.address.clone(), e.to_string()))?;
+        let abi = self
+            .abi_registry
+            .resolve_optional(&subscription.abi, chain_id, Some(contract_address))
+            .await?;
+
         let repository = self.repository.clone();
-        tokio::spawn(Self::spawn_process_task(repository, user_id, contract_address, event_signature,webhook));
-    }
+        let handle = tokio::spawn(Self::spawn_process_task(repository, user_id, chain_id, contract_address, subscription, abi, options));
+        self.repository.write().await.apply_task(user_id, chain_id, handle);
 
-    async fn get_provider(
-        repository: Arc<RwLock<EthersRepository>>,
-        user_id: i32,
-    ) -> Arc<Provider<Ws>> {
-        repository.read().await.get_connection(user_id).unwrap()
+        Ok(())
     }
 
     fn create_event_filter(contract_address: Address, event_signature: &str) -> Filter {
@@ -39,7 +115,7 @@ impl ListenContractEventsService {
 
     async fn send_transaction(
         webhook: String,
-        transaction: HashMap<H160, Transaction>,
+        transaction: ChainDocument<ContractEvent>,
     ) -> Result<(), reqwest::Error> {
         let client = HttpClient::new();
 
@@ -53,59 +129,305 @@ impl ListenContractEventsService {
         Ok(())
     }
 
-    async fn process_event(
-        provider: Arc<Provider<Ws>>,
-        hash: H256,
-        webhook:String,
-        address: H160
-    ){
+    async fn process_event(provider: Arc<RpcProvider>, log: Log, delivery: &mut EventDelivery) {
+        // Logs com `removed` vindos do nó são ignorados: a retratação é feita a partir dos reorgs do
+        // `BlockHub`, que também cobrem o polling via HTTP.
+        if log.removed == Some(true) {
+            return;
+        }
+
+        let (Some(hash), Some(block_number), Some(block_hash)) = (log.transaction_hash, log.block_number, log.block_hash) else {
+            return;
+        };
+
+        let position = (block_number.as_u64(), log.log_index.unwrap_or_default());
+        if delivery.last_log.is_some_and(|last| position <= last) {
+            return;
+        }
+        delivery.last_log = Some(position);
+
         let transaction_data = provider.get_transaction(hash).await;
-        let mut contract_event = HashMap::new();
 
         match transaction_data {
+            Ok(Some(value)) if delivery.confirmations == 0 => {
+                let contract_event = Self::contract_event(delivery, &log, value);
+                if let Err(e) = Self::send_transaction(delivery.webhook.clone(), ChainDocument::new(delivery.chain_id, contract_event.clone())).await {
+                    log::error!("Erro ao enviar requisição: {}", e);
+                }
+                delivery.journal.record(block_number.as_u64(), block_hash, contract_event);
+            }
             Ok(Some(value)) => {
-                contract_event.insert(address, value);
-                Self::send_transaction(webhook, contract_event).await.expect("TODO: panic message");
+                let contract_event = Self::contract_event(delivery, &log, value);
+                if delivery.notify_pending {
+                    Self::notify(delivery, contract_event.clone(), DeliveryStatus::Pending).await;
+                    delivery.journal.record(block_number.as_u64(), block_hash, contract_event.clone());
+                }
+                delivery.queue.push(block_number.as_u64(), block_hash, contract_event);
+                Self::confirm_events(delivery).await;
             }
             Ok(None) => {
-                println!("Deu bom, mas tá vazio (None)!");
+                log::warn!("Transação {:?} do log não encontrada", hash);
+            }
+            Err(e) => {
+                log::error!("Erro ao buscar a transação {:?} do log: {}", hash, e);
+            }
+        }
+
+    }
+
+    /// Monta o documento do evento, decodificando o log e o calldata quando a assinatura tem ABI.
+    /// O calldata só é decodificado se a transação chamar uma função da ABI; chamadas via outros
+    /// contratos (roteadores, multisigs) ficam sem `call`.
+    fn contract_event(delivery: &EventDelivery, log: &Log, transaction: Transaction) -> ContractEvent {
+        let (event, call) = match &delivery.abi {
+            Some(abi) => {
+                let event = EthersUtils::decode_log(abi, &log.topics, &log.data)
+                    .map_err(|e| log::warn!("Erro ao decodificar evento do contrato {:?}: {}", delivery.contract_address, e))
+                    .ok();
+                let call = EthersUtils::decode_calldata(abi, &transaction.input).ok();
+                (event, call)
+            }
+            None => (None, None),
+        };
+
+        ContractEvent {
+            transaction: HashMap::from([(delivery.contract_address, transaction)]),
+            event,
+            call,
+        }
+    }
+
+    async fn notify(delivery: &EventDelivery, contract_event: ContractEvent, status: DeliveryStatus) {
+        let document = ChainDocument::new(delivery.chain_id, contract_event).with_status(Some(status));
+
+        if let Err(e) = Self::send_transaction(delivery.webhook.clone(), document).await {
+            log::error!("Erro ao enviar requisição: {}", e);
+        }
+    }
+
+    /// Envia os eventos que atingiram a profundidade exigida com a altura atual do `BlockHub`.
+    async fn confirm_events(delivery: &mut EventDelivery) {
+        for (number, hash, contract_event) in delivery.queue.take_confirmed() {
+            Self::notify(delivery, contract_event.clone(), DeliveryStatus::Confirmed).await;
+            if !delivery.notify_pending {
+                delivery.journal.record(number, hash, contract_event);
+            }
+        }
+    }
+
+    /// Reenvia, com `"removed": true`, os eventos entregues a partir de blocos órfãos. Eventos que
+    /// ainda aguardavam confirmações são apenas descartados.
+    async fn retract_events(orphans: &[OrphanedBlock], delivery: &mut EventDelivery) {
+        delivery.queue.retract(orphans);
+
+        for contract_event in delivery.journal.retract(orphans) {
+            if let Err(e) = Self::send_transaction(delivery.webhook.clone(), ChainDocument::removed(delivery.chain_id, contract_event)).await {
+                log::error!("Erro ao enviar requisição: {}", e);
             }
+        }
+    }
+
+    /// Acompanha a chain pelo `BlockHub`: cada bloco novo pode liberar eventos que aguardavam
+    /// confirmações, e cada reorg retrata os eventos de blocos órfãos.
+    async fn handle_block_event(event: BlockEvent, delivery: &mut EventDelivery) {
+        match event {
+            BlockEvent::Block(block) => {
+                if let Some(number) = block.number {
+                    delivery.queue.set_head(number.as_u64());
+                    Self::confirm_events(delivery).await;
+                }
+            }
+            BlockEvent::Reorged(orphans) => {
+                if let Some(first) = orphans.first() {
+                    delivery.next_block = delivery.next_block.map(|next| next.min(first.number));
+                    if delivery.last_log.is_some_and(|(number, _)| number >= first.number) {
+                        delivery.last_log = None;
+                    }
+                }
+                Self::retract_events(&orphans, delivery).await
+            }
+            BlockEvent::Lagged(_) => {}
+        }
+    }
+
+    /// Consulta com `eth_getLogs` os logs de `delivery.next_block` até o bloco atual, em intervalos de
+    /// até `MAX_LOG_RANGE` blocos, avançando `next_block` a cada intervalo entregue. Sem
+    /// `next_block` (nada foi consultado ainda) não há o que recuperar.
+    async fn catch_up(provider: &Arc<RpcProvider>, filter: &Filter, delivery: &mut EventDelivery) -> Result<(), ProviderError> {
+        let Some(mut from) = delivery.next_block else {
+            return Ok(());
+        };
+        let head = provider.get_block_number().await?.as_u64();
+
+        while from <= head {
+            let to = head.min(from.saturating_add(MAX_LOG_RANGE - 1));
+            let logs = provider.get_logs(&filter.clone().from_block(from).to_block(to)).await?;
+            for log in logs {
+                Self::process_event(provider.clone(), log, delivery).await;
+            }
+
+            from = to + 1;
+            delivery.next_block = Some(from);
+        }
+
+        Ok(())
+    }
+
+    /// Repassa os logs da assinatura `eth_subscribe` até o stream terminar ou o provider ser trocado.
+    /// Após uma troca de provider, os logs desde o último bloco entregue são recuperados com
+    /// `catch_up` antes de consumir o stream; os que chegarem pelos dois caminhos são descartados
+    /// pela posição. Cada bloco do hub avança `next_block`: os logs das alturas anteriores já foram
+    /// emitidos pela assinatura.
+    /// Retorna `true` quando a saída foi causada pela troca do provider.
+    async fn subscribe_events(
+        provider: Arc<RpcProvider>,
+        filter: &Filter,
+        connection: &mut watch::Receiver<Arc<RpcProvider>>,
+        blocks: &mut BlockSubscription,
+        delivery: &mut EventDelivery,
+    ) -> bool {
+        let mut stream = match provider.subscribe_logs(filter).await {
+            Ok(stream) => stream,
             Err(e) => {
-                println!("Deu ruim: {}", e);
+                log::error!("Erro ao criar stream de logs: {}", e);
+                return false;
             }
+        };
+
+        if let Err(e) = Self::catch_up(&provider, filter, delivery).await {
+            log::warn!("Erro ao recuperar os logs desde o bloco {:?}: {}", delivery.next_block, e);
         }
 
+        loop {
+            tokio::select! {
+                log = stream.next() => match log {
+                    Some(log) => Self::process_event(provider.clone(), log, delivery).await,
+                    None => return false,
+                },
+                event = blocks.recv() => match event {
+                    Some(event) => {
+                        if let BlockEvent::Block(block) = &event {
+                            if let Some(number) = block.number {
+                                let number = number.as_u64();
+                                delivery.next_block = Some(delivery.next_block.map_or(number, |next| next.max(number)));
+                            }
+                        }
+                        Self::handle_block_event(event, delivery).await
+                    }
+                    None => return false,
+                },
+                changed = connection.changed() => return changed.is_ok(),
+            }
+        }
+    }
+
+    /// Fallback para transportes sem assinaturas (HTTP): a cada `POLL_INTERVAL` consulta
+    /// `eth_getLogs` entre o último bloco processado e o bloco atual, com `catch_up`. Após um reorg
+    /// a consulta volta para a primeira altura órfã, para entregar os eventos da nova chain. Como a
+    /// posição fica em `delivery`, após a troca do provider a consulta continua de onde parou.
+    /// Retorna `true` quando a saída foi causada pela troca do provider.
+    async fn poll_events(
+        provider: Arc<RpcProvider>,
+        filter: &Filter,
+        connection: &mut watch::Receiver<Arc<RpcProvider>>,
+        blocks: &mut BlockSubscription,
+        delivery: &mut EventDelivery,
+    ) -> bool {
+        // O intervalo é criado fora do loop para que os eventos do hub não reiniciem o prazo do
+        // polling em chains com blocos mais rápidos que `POLL_INTERVAL`.
+        let mut poll = tokio::time::interval(POLL_INTERVAL);
+        poll.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = poll.tick() => {},
+                event = blocks.recv() => match event {
+                    Some(event) => {
+                        Self::handle_block_event(event, delivery).await;
+                        continue;
+                    }
+                    None => return false,
+                },
+                changed = connection.changed() => return changed.is_ok(),
+            }
+
+            if delivery.next_block.is_none() {
+                match provider.get_block_number().await {
+                    Ok(head) => delivery.next_block = Some(head.as_u64()),
+                    Err(e) => {
+                        log::error!("Erro no polling de logs: {}", e);
+                        continue;
+                    }
+                }
+            }
+
+            if let Err(e) = Self::catch_up(&provider, filter, delivery).await {
+                log::error!("Erro ao consultar logs: {}", e);
+            }
+        }
     }
 
+    /// Mantém a assinatura de logs do contrato enquanto a conexão do usuário existir.
+    ///
+    /// Em transportes com pubsub os logs chegam via `eth_subscribe`; em HTTP são obtidos por polling
+    /// de `eth_getLogs`. A conexão é observada via `watch_connection`: quando o `ConnectionSupervisor`
+    /// substitui o provider (reconexão) ou um novo `apply_rpc` é feito, a assinatura é refeita no novo
+    /// provider. Se o stream terminar sem que o provider tenha sido trocado, a task aguarda a próxima troca.
+    ///
+    /// A task também se inscreve no `BlockHub` da conexão: quando um reorg torna órfão o bloco de um
+    /// evento já entregue, o evento é reenviado ao webhook com `"removed": true`.
     fn spawn_process_task(
         repository: Arc<RwLock<EthersRepository>>,
         user_id: i32,
-        contract_address: String,
-        event_signature: String,
-        webhook:String
+        chain_id: u64,
+        contract_address: Address,
+        subscription: ContractEventSubscription,
+        abi: Option<Abi>,
+        options: DeliveryOptions,
     ) -> impl std::future::Future<Output = ()> {
         async move {
-            let provider = Self::get_provider(repository.clone(), user_id).await;
+            let (connection, blocks) = {
+                let repository = repository.read().await;
+                (repository.watch_connection(user_id, chain_id), repository.subscribe_blocks(user_id, chain_id))
+            };
+
+            let (Some(mut connection), Some(mut blocks)) = (connection, blocks) else {
+                log::warn!("Nenhuma conexão configurada para o user_id {} na chain {}", user_id, chain_id);
+                return;
+            };
 
-            let contract_address: Address = contract_address
-                .parse()
-                .expect("Erro ao parsear o endereço do contrato");
+            let ContractEventSubscription { event_signature, webhook, .. } = subscription;
 
             let filter = Self::create_event_filter(contract_address, &event_signature);
+            let mut delivery = EventDelivery {
+                chain_id,
+                webhook,
+                contract_address,
+                abi,
+                confirmations: options.confirmations,
+                notify_pending: options.notify_pending,
+                journal: DeliveryJournal::new(),
+                queue: ConfirmationQueue::new(options.confirmations),
+                next_block: None,
+                last_log: None,
+            };
+
+            loop {
+                let provider = connection.borrow_and_update().clone();
+                log::info!("Escutando eventos: {}", event_signature);
+
+                let replaced = if provider.as_ref().as_ref().supports_pubsub() {
+                    Self::subscribe_events(provider, &filter, &mut connection, &mut blocks, &mut delivery).await
+                } else {
+                    Self::poll_events(provider, &filter, &mut connection, &mut blocks, &mut delivery).await
+                };
 
-            let mut stream = match provider.subscribe_logs(&filter).await {
-                Ok(s) => s,
-                Err(e) => {
-                    eprintln!("Erro ao criar stream de logs: {}", e);
+                if !replaced && connection.changed().await.is_err() {
+                    log::info!("Conexão do user_id {} na chain {} removida, encerrando eventos: {}", user_id, chain_id, event_signature);
                     return;
                 }
-            };
-
-            println!("Escutando eventos: {}", event_signature);
 
-            while let Some(log_result) = stream.next().await {
-                let hash = log_result.transaction_hash.unwrap();
-                Self::process_event(provider.clone(), hash, webhook.clone(),contract_address.clone()).await;
+                log::info!("Refazendo assinatura de eventos: {}", event_signature);
             }
         }
     }
@@ -1,48 +1,119 @@
 use crate::http_client::HttpClient;
+use crate::repositories::block_hub::{
+    BlockEvent, BlockSubscription, ConfirmationQueue, DeliveryJournal, OrphanedBlock,
+};
 use crate::repositories::ethers_repository::EthersRepository;
+use crate::services::ethers::token_analysis_service::{AnalysisOptions, TokenAnalysisError, TokenAnalysisService};
 use crate::utils::bytecode_utils::BytecodeUtils;
-use ethers::prelude::{Block, BlockNumber, Provider, Ws, H256};
-use ethers::providers::Middleware;
-use ethers::types::{BlockId, TransactionReceipt};
+use crate::utils::ethers_utils::{ChainDocument, DeliveryOptions, DeliveryStatus};
+use crate::rpc_transport::RpcProvider;
+use ethers::prelude::BlockNumber;
+use ethers::providers::{Middleware, ProviderError};
+use ethers::types::{Address, BlockId, TransactionReceipt, H256};
+use serde::Serialize;
+use serde_json::Value;
 use std::sync::Arc;
-use tokio::sync::mpsc::Receiver;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
+/// Intervalo entre as verificações de liquidez de um token recém-implantado com análise pedida.
+const ANALYSIS_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Tempo máximo aguardando a liquidez do token; depois disso a análise é abandonada.
+const ANALYSIS_DEADLINE: Duration = Duration::from_secs(60 * 60);
+
 pub struct ListenDeployErc20ContractsService {
     repository: Arc<RwLock<EthersRepository>>,
     http_client: HttpClient,
+    token_analysis: Arc<TokenAnalysisService>,
+}
+
+/// Estado de entrega de um listener de deploys: o que já foi notificado, para retratação em reorgs,
+/// e o que aguarda confirmações.
+struct DeployDelivery {
+    user_id: i32,
+    chain_id: u64,
+    webhook: String,
+    confirmations: u64,
+    notify_pending: bool,
+    analysis: Option<(Arc<TokenAnalysisService>, AnalysisOptions)>,
+    repository: Arc<RwLock<EthersRepository>>,
+    journal: DeliveryJournal<Vec<TransactionReceipt>>,
+    queue: ConfirmationQueue<Vec<TransactionReceipt>>,
+}
+
+/// Análise de compra e venda de um token implantado, enviada em uma requisição própria depois do
+/// deploy, quando pedida na assinatura.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AnalysisDocument {
+    transaction_hash: H256,
+    contract_address: Address,
+    analysis: Value,
 }
 
 impl ListenDeployErc20ContractsService {
-    pub fn new(repository: Arc<RwLock<EthersRepository>>, http_client: HttpClient) -> Self {
+    pub fn new(
+        repository: Arc<RwLock<EthersRepository>>,
+        http_client: HttpClient,
+        token_analysis: Arc<TokenAnalysisService>,
+    ) -> Self {
         ListenDeployErc20ContractsService {
             repository,
             http_client,
+            token_analysis,
         }
     }
 
-    pub async fn exec(&self, user_id: i32, webhook: String) {
-        let block_listener = self.repository.write().await.get_block_listener(user_id);
+    /// Inicia o listener de deploys de ERC20 da conexão.
+    ///
+    /// Com `confirmations` maior que 0, os deploys só são enviados quando o bloco atinge essa
+    /// profundidade, com `"status": "confirmed"`. Com `notify_pending`, cada deploy também é enviado
+    /// assim que aparece, com `"status": "pending"`.
+    ///
+    /// Com `analysis`, a análise de compra e venda do token (`TokenAnalysisService`) é enviada depois
+    /// do deploy, em uma requisição separada com `transactionHash`, `contractAddress` e `analysis`
+    /// (veja `spawn_analysis`).
+    pub async fn exec(
+        &self,
+        user_id: i32,
+        chain_id: u64,
+        webhook: String,
+        options: DeliveryOptions,
+        analysis: Option<AnalysisOptions>,
+    ) {
+        let mut repository = self.repository.write().await;
 
-        if let Some(receiver) = block_listener {
-            let repository = self.repository.clone();
-            tokio::spawn(Self::spawn_process_task(repository, user_id, webhook, receiver));
+        if let Some(subscription) = repository.subscribe_blocks(user_id, chain_id) {
+            let delivery = DeployDelivery {
+                user_id,
+                chain_id,
+                webhook,
+                confirmations: options.confirmations,
+                notify_pending: options.notify_pending,
+                analysis: analysis.map(|analysis| (self.token_analysis.clone(), analysis)),
+                repository: self.repository.clone(),
+                journal: DeliveryJournal::new(),
+                queue: ConfirmationQueue::new(options.confirmations),
+            };
+            let handle = tokio::spawn(Self::spawn_process_task(self.repository.clone(), user_id, subscription, delivery));
+            repository.apply_task(user_id, chain_id, handle);
         } else {
-            println!("Nenhum listener configurado para o user_id: {}", user_id);
+            log::warn!("Nenhum listener configurado para o user_id {} na chain {}", user_id, chain_id);
         }
     }
 
+    /// Retorna altura, hash e os recibos dos deploys de ERC20 do bloco.
     async fn process_block(
-        provider: &Arc<Provider<Ws>>,
-        block: Block<H256>,
-    ) -> Vec<TransactionReceipt> {
-        // let block_id = BlockId::Number(BlockNumber::Number(block.number.unwrap()));
-        let block_id = BlockId::Number(BlockNumber::Number(block.number.unwrap()));
-        let block_data = provider
-            .get_block_with_txs(block_id)
-            .await
-            .expect("ERR")
-            .unwrap();
+        provider: &Arc<RpcProvider>,
+        block_id: BlockId,
+    ) -> Result<(u64, H256, Vec<TransactionReceipt>), ProviderError> {
+        let Some(block_data) = provider.get_block_with_txs(block_id).await? else {
+            return Err(ProviderError::CustomError(format!("Bloco {:?} não encontrado", block_id)));
+        };
+        let (Some(number), Some(hash)) = (block_data.number, block_data.hash) else {
+            return Err(ProviderError::CustomError(format!("Bloco {:?} ainda pendente", block_id)));
+        };
 
         let mut transactions = Vec::new();
 
@@ -50,17 +121,154 @@ impl ListenDeployErc20ContractsService {
             let bytecode_is_deploy_erc20 =
                 BytecodeUtils::bytecode_is_deploy_erc20(transaction.input.to_string());
             if transaction.to.is_none() && bytecode_is_deploy_erc20 {
-                let receipt = provider.get_transaction_receipt(transaction.hash).await.unwrap().expect("ERR ");
-                transactions.push(receipt);
+                if let Some(receipt) = provider.get_transaction_receipt(transaction.hash).await? {
+                    transactions.push(receipt);
+                }
+            }
+        }
+
+        Ok((number.as_u64(), hash, transactions))
+    }
+
+    /// Retrata os deploys entregues a partir de blocos órfãos: os recibos são reenviados com
+    /// `"removed": true` no envelope e em cada log. Deploys que ainda aguardavam confirmações são
+    /// apenas descartados.
+    async fn retract_deploys(orphans: &[OrphanedBlock], delivery: &mut DeployDelivery) {
+        delivery.queue.retract(orphans);
+
+        let transactions: Vec<_> = delivery
+            .journal
+            .retract(orphans)
+            .into_iter()
+            .flatten()
+            .map(|mut receipt| {
+                receipt.logs.iter_mut().for_each(|log| log.removed = Some(true));
+                ChainDocument::removed(delivery.chain_id, receipt)
+            })
+            .collect();
+
+        if !transactions.is_empty() {
+            if let Err(err) = Self::send_transactions(delivery.webhook.clone(), transactions).await {
+                log::error!("Erro ao enviar requisição: {}", err);
+            }
+        }
+    }
+
+    /// Agenda a análise dos tokens implantados, fora do loop de blocos. Logo após o deploy o token
+    /// ainda não tem par no router e a análise falharia, então cada task verifica a liquidez a cada
+    /// `ANALYSIS_POLL_INTERVAL` e só analisa quando o router cota a compra. Sem liquidez em
+    /// `ANALYSIS_DEADLINE`, ou se a conexão for removida, a análise é abandonada sem envio.
+    async fn spawn_analysis(delivery: &DeployDelivery, receipts: &[TransactionReceipt]) {
+        let Some((token_analysis, options)) = &delivery.analysis else {
+            return;
+        };
+
+        for receipt in receipts {
+            let Some(token) = receipt.contract_address else {
+                continue;
+            };
+
+            let task = Self::analyze(
+                token_analysis.clone(),
+                options.clone(),
+                delivery.user_id,
+                delivery.chain_id,
+                delivery.webhook.clone(),
+                receipt.transaction_hash,
+                token,
+            );
+            let handle = tokio::spawn(task);
+            delivery.repository.write().await.apply_task(delivery.user_id, delivery.chain_id, handle);
+        }
+    }
+
+    async fn analyze(
+        token_analysis: Arc<TokenAnalysisService>,
+        options: AnalysisOptions,
+        user_id: i32,
+        chain_id: u64,
+        webhook: String,
+        transaction_hash: H256,
+        token: Address,
+    ) {
+        let deadline = tokio::time::Instant::now() + ANALYSIS_DEADLINE;
+
+        loop {
+            match token_analysis.has_liquidity(user_id, chain_id, token, &options).await {
+                Ok(true) => break,
+                Ok(false) => {}
+                Err(TokenAnalysisError::NotFound(_, _)) => return,
+                Err(e) => log::warn!("Erro ao verificar a liquidez do token {:?} na chain {}: {}", token, chain_id, e),
+            }
+
+            if tokio::time::Instant::now() + ANALYSIS_POLL_INTERVAL > deadline {
+                log::info!("Token {:?} sem liquidez na chain {}, análise abandonada", token, chain_id);
+                return;
+            }
+            tokio::time::sleep(ANALYSIS_POLL_INTERVAL).await;
+        }
+
+        let analysis = match token_analysis.exec(user_id, chain_id, token, options).await {
+            Ok(analysis) => serde_json::to_value(analysis).expect("TokenAnalysis é serializável"),
+            Err(e) => serde_json::json!({ "error": e.to_string() }),
+        };
+        let document = AnalysisDocument { transaction_hash, contract_address: token, analysis };
+
+        if let Err(err) = Self::send_transactions(webhook, vec![ChainDocument::new(chain_id, document)]).await {
+            log::error!("Erro ao enviar requisição: {}", err);
+        }
+    }
+
+    async fn notify(delivery: &DeployDelivery, receipts: &[TransactionReceipt], status: Option<DeliveryStatus>) {
+        let transactions = receipts
+            .iter()
+            .map(|receipt| ChainDocument::new(delivery.chain_id, receipt.clone()).with_status(status))
+            .collect();
+
+        if let Err(err) = Self::send_transactions(delivery.webhook.clone(), transactions).await {
+            log::error!("Erro ao enviar requisição: {}", err);
+        }
+    }
+
+    /// Entrega os deploys encontrados no bloco, imediatamente ou após as confirmações exigidas, e
+    /// libera os deploys de blocos anteriores que atingiram a profundidade com este bloco.
+    async fn deliver_block(
+        delivery: &mut DeployDelivery,
+        block_number: u64,
+        block_hash: H256,
+        receipts: Vec<TransactionReceipt>,
+    ) {
+        if delivery.confirmations == 0 {
+            if !receipts.is_empty() {
+                Self::notify(delivery, &receipts, None).await;
+                Self::spawn_analysis(delivery, &receipts).await;
+                delivery.journal.record(block_number, block_hash, receipts);
+            }
+            return;
+        }
+
+        if !receipts.is_empty() {
+            if delivery.notify_pending {
+                Self::notify(delivery, &receipts, Some(DeliveryStatus::Pending)).await;
+                delivery.journal.record(block_number, block_hash, receipts.clone());
             }
+            delivery.queue.push(block_number, block_hash, receipts);
         }
 
-        transactions
+        delivery.queue.set_head(block_number);
+
+        for (number, hash, receipts) in delivery.queue.take_confirmed() {
+            Self::notify(delivery, &receipts, Some(DeliveryStatus::Confirmed)).await;
+            Self::spawn_analysis(delivery, &receipts).await;
+            if !delivery.notify_pending {
+                delivery.journal.record(number, hash, receipts);
+            }
+        }
     }
 
-    async fn send_transactions(
+    async fn send_transactions<T: Serialize>(
         webhook: String,
-        transactions: Vec<TransactionReceipt>,
+        transactions: Vec<ChainDocument<T>>,
     ) -> Result<(), reqwest::Error> {
         let client = HttpClient::new();
 
@@ -77,20 +285,54 @@ impl ListenDeployErc20ContractsService {
     fn spawn_process_task(
         repository: Arc<RwLock<EthersRepository>>,
         user_id: i32,
-        webhook: String,
-        mut receiver: Receiver<Block<H256>>,
+        mut subscription: BlockSubscription,
+        mut delivery: DeployDelivery,
     ) -> impl std::future::Future<Output = ()> {
         async move {
-            let provider = Self::get_provider(repository, user_id).await;
-
-            while let Some(block) = receiver.recv().await {
-                println!("Block: {}", block.number.unwrap());
-                let transactions = Self::process_block(&provider, block).await;
+            let chain_id = delivery.chain_id;
 
-                if !transactions.is_empty() {
-                    if let Err(err) = Self::send_transactions(webhook.clone(), transactions).await {
-                        eprintln!("Erro ao enviar requisição: {}", err);
+            while let Some(event) = subscription.recv().await {
+                let block_ids: Vec<BlockId> = match event {
+                    BlockEvent::Block(block) => match block.hash {
+                        Some(hash) => vec![BlockId::Hash(hash)],
+                        None => continue,
+                    },
+                    BlockEvent::Reorged(orphans) => {
+                        Self::retract_deploys(&orphans, &mut delivery).await;
+                        continue;
                     }
+                    // Os blocos descartados pelo hub são buscados via RPC para não perder deploys.
+                    BlockEvent::Lagged(missed) => {
+                        log::warn!(
+                            "Listener de deploys do user_id {} na chain {} atrasado, reprocessando blocos {:?}",
+                            user_id,
+                            chain_id,
+                            missed
+                        );
+                        missed
+                            .map(|number| BlockId::Number(BlockNumber::Number(number.into())))
+                            .collect()
+                    }
+                };
+
+                // O provider é obtido a cada bloco para acompanhar as reconexões do supervisor.
+                let Some(provider) = Self::get_provider(repository.clone(), user_id, chain_id).await else {
+                    log::info!("Conexão do user_id {} na chain {} removida", user_id, chain_id);
+                    return;
+                };
+
+                for block_id in block_ids {
+                    let (block_number, block_hash, receipts) = match Self::process_block(&provider, block_id).await {
+                        Ok(result) => result,
+                        Err(e) => {
+                            log::error!("Erro ao processar o bloco {:?} da chain {}: {}", block_id, chain_id, e);
+                            continue;
+                        }
+                    };
+
+                    log::info!("Bloco {} processado na chain {}", block_number, chain_id);
+
+                    Self::deliver_block(&mut delivery, block_number, block_hash, receipts).await;
                 }
             }
         }
@@ -99,8 +341,9 @@ impl ListenDeployErc20ContractsService {
     async fn get_provider(
         repository: Arc<RwLock<EthersRepository>>,
         user_id: i32,
-    ) -> Arc<Provider<Ws>> {
-        repository.read().await.get_connection(user_id).unwrap()
+        chain_id: u64,
+    ) -> Option<Arc<RpcProvider>> {
+        repository.read().await.get_connection(user_id, chain_id)
     }
 
 }
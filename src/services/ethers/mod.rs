@@ -1,6 +1,16 @@
+pub mod abi_registry_service;
+pub mod address_inspection_service;
 pub mod apply_rpc_service;
 pub mod get_logs_service;
 pub mod listen_deploy_erc20_contracts_service;
 
+pub mod batch_call_service;
 pub mod call_functions_service;
-pub mod listen_contract_event_service;
\ No newline at end of file
+pub mod decode_service;
+pub mod connection_supervisor;
+pub mod get_connection_status_service;
+pub mod listen_contract_event_service;
+pub mod rollback_reorg_service;
+pub mod simulate_service;
+pub mod token_analysis_service;
+pub mod token_service;
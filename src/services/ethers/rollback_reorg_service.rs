@@ -0,0 +1,119 @@
+use crate::repositories::backfill_repository::{BackfillRepository, BlockRanges};
+use crate::repositories::block_hub::{BlockEvent, BlockSubscription, OrphanedBlock};
+use crate::repositories::elastic_repository::ElasticRepository;
+use crate::repositories::ethers_repository::EthersRepository;
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Índices com documentos derivados de blocos, identificados pelo campo `chain_id` e pelo campo com
+/// o hash do bloco: `blockHash` nos documentos derivados e `hash` no próprio cabeçalho.
+const BLOCK_INDICES: &[(&str, &str)] = &[
+    ("transactions", "blockHash"),
+    ("receipts", "blockHash"),
+    ("logs", "blockHash"),
+    ("blocks", "hash"),
+];
+
+/// Mantém o Elasticsearch consistente com a chain canônica: quando o `BlockHub` de uma conexão
+/// anuncia um reorg, os documentos indexados a partir dos blocos órfãos são removidos e as alturas
+/// deles saem dos checkpoints de backfill, para que um novo backfill indexe os blocos canônicos.
+pub struct RollbackReorgService {
+    repository: Arc<RwLock<EthersRepository>>,
+    elastic_repository: Arc<ElasticRepository>,
+    backfill_repository: Arc<BackfillRepository>,
+}
+
+impl RollbackReorgService {
+    pub fn new(
+        repository: Arc<RwLock<EthersRepository>>,
+        elastic_repository: Arc<ElasticRepository>,
+        backfill_repository: Arc<BackfillRepository>,
+    ) -> Self {
+        RollbackReorgService {
+            repository,
+            elastic_repository,
+            backfill_repository,
+        }
+    }
+
+    /// Inicia a task que acompanha os reorgs da conexão do usuário na chain. A task é registrada
+    /// via `apply_task` e termina junto com a conexão.
+    pub async fn exec(&self, user_id: i32, chain_id: u64) {
+        let mut repository = self.repository.write().await;
+
+        if let Some(subscription) = repository.subscribe_blocks(user_id, chain_id) {
+            let handle = tokio::spawn(Self::spawn_rollback_task(
+                self.elastic_repository.clone(),
+                self.backfill_repository.clone(),
+                chain_id,
+                subscription,
+            ));
+            repository.apply_task(user_id, chain_id, handle);
+        }
+    }
+
+    async fn spawn_rollback_task(
+        elastic_repository: Arc<ElasticRepository>,
+        backfill_repository: Arc<BackfillRepository>,
+        chain_id: u64,
+        mut subscription: BlockSubscription,
+    ) {
+        while let Some(event) = subscription.recv().await {
+            if let BlockEvent::Reorged(orphans) = event {
+                Self::rollback(&elastic_repository, &backfill_repository, chain_id, &orphans).await;
+            }
+        }
+    }
+
+    async fn rollback(
+        elastic_repository: &ElasticRepository,
+        backfill_repository: &BackfillRepository,
+        chain_id: u64,
+        orphans: &[OrphanedBlock],
+    ) {
+        let mut heights = BlockRanges::default();
+        for orphan in orphans {
+            heights.insert(orphan.number, orphan.number);
+        }
+        if let Err(e) = backfill_repository.remove_checkpoints(chain_id, &heights).await {
+            log::error!(
+                "Erro ao remover os blocos órfãos dos checkpoints de backfill (chain {}): {}",
+                chain_id,
+                e
+            );
+        }
+
+        for (index, hash_field) in BLOCK_INDICES {
+            let block_hashes: Vec<_> = orphans
+                .iter()
+                .map(|orphan| json!({ "match": { *hash_field: orphan.hash } }))
+                .collect();
+
+            let query = json!({
+                "query": {
+                    "bool": {
+                        "must": [{ "term": { "chain_id": chain_id } }],
+                        "should": block_hashes,
+                        "minimum_should_match": 1
+                    }
+                }
+            });
+
+            match elastic_repository.delete_by_query(index, &query).await {
+                Ok(deleted) => log::info!(
+                    "{} documentos de blocos órfãos removidos do índice {} (chain {})",
+                    deleted,
+                    index,
+                    chain_id
+                ),
+                Err(e) => log::error!(
+                    "Erro ao remover documentos de blocos órfãos do índice {} (chain {}): {}",
+                    index,
+                    chain_id,
+                    e
+                ),
+            }
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use crate::repositories::ethers_repository::EthersRepository;
+use crate::rpc_transport::RpcPool;
+use crate::services::ethers::abi_registry_service::{AbiRegistryError, AbiRegistryService, AbiSource};
+use crate::services::ethers::call_functions_service::{
+    BlockError, BlockRef, CallError, CallFunctionsService, FunctionCall, FunctionResult,
+};
+use crate::utils::ethers_utils::EthersUtils;
+use ethers::abi::{Abi, Function, ParamType, Token};
+use ethers::providers::call_raw::spoof;
+use ethers::types::{Address, BlockId, Bytes, TransactionRequest, H256, U256, U64};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::RwLock;
+
+#[derive(Error, Debug)]
+pub enum SimulateError {
+    #[error("Nenhuma conexão configurada para o user_id {0} na chain {1}")]
+    NotFound(i32, u64),
+    #[error("{0}")]
+    InvalidArgument(String),
+    #[error(transparent)]
+    AbiError(#[from] AbiRegistryError),
+    #[error(transparent)]
+    BlockError(#[from] BlockError),
+}
+
+/// Campos do bloco sobrescritos na simulação, no formato do quarto parâmetro do `eth_call` do
+/// geth (valores numéricos em hexadecimal).
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockOverrides {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub number: Option<U64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<U64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gas_limit: Option<U64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coinbase: Option<Address>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub random: Option<H256>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_fee: Option<U256>,
+}
+
+/// Transação a simular. O calldata pode ser informado diretamente (`calldata`) ou montado a partir
+/// de `function` e `args` com a ABI da requisição, referenciada por `abi_id` ou registrada para
+/// `to`. `state_overrides` segue o formato do geth: por endereço, `balance`, `nonce`, `code` e
+/// `stateDiff` (slots alterados) ou `state` (storage substituído por completo).
+#[derive(Deserialize)]
+pub struct SimulateRequest {
+    #[serde(default)]
+    pub from: Option<Address>,
+    #[serde(default)]
+    pub to: Option<Address>,
+    #[serde(default)]
+    pub value: Option<Value>,
+    #[serde(default)]
+    pub calldata: Option<Bytes>,
+    #[serde(default)]
+    pub function: Option<String>,
+    #[serde(default)]
+    pub args: Value,
+    #[serde(flatten)]
+    pub abi: AbiSource,
+    #[serde(default)]
+    pub gas: Option<u64>,
+    #[serde(default)]
+    pub block: Option<Value>,
+    #[serde(default)]
+    pub state_overrides: Option<spoof::State>,
+    #[serde(default)]
+    pub block_overrides: Option<BlockOverrides>,
+}
+
+/// Resultado da simulação. `result` traz o retorno decodificado, quando a função é conhecida, ou
+/// os bytes retornados; `gas_estimate` traz o resultado do `eth_estimateGas` com os mesmos
+/// parâmetros.
+#[derive(Serialize)]
+pub struct SimulateResponse {
+    pub block: BlockRef,
+    pub result: FunctionResult,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub return_data: Option<Bytes>,
+    pub gas_estimate: FunctionResult,
+}
+
+pub struct SimulateService {
+    repository: Arc<RwLock<EthersRepository>>,
+    abi_registry: Arc<AbiRegistryService>,
+}
+
+impl SimulateService {
+    pub fn new(repository: Arc<RwLock<EthersRepository>>, abi_registry: Arc<AbiRegistryService>) -> Self {
+        SimulateService { repository, abi_registry }
+    }
+
+    async fn get_pool(&self, user_id: i32, chain_id: u64) -> Result<RpcPool, SimulateError> {
+        let lock = self.repository.read().await;
+        lock.get_pool(user_id, chain_id).ok_or(SimulateError::NotFound(user_id, chain_id))
+    }
+
+    fn parse_value(value: &Value) -> Result<U256, SimulateError> {
+        match EthersUtils::json_to_token(&ParamType::Uint(256), value) {
+            Ok(Token::Uint(value)) => Ok(value),
+            Ok(_) => unreachable!("uint256 sempre resulta em Token::Uint"),
+            Err(e) => Err(SimulateError::InvalidArgument(format!("`value` inválido: {}", e))),
+        }
+    }
+
+    /// Monta o calldata e identifica a função chamada. Com `function`, a ABI é obrigatória; com
+    /// `calldata`, ela é opcional e usada apenas para decodificar o retorno e erros customizados.
+    async fn encode(
+        &self,
+        request: &SimulateRequest,
+        chain_id: u64,
+    ) -> Result<(Bytes, Option<Abi>, Option<Function>), SimulateError> {
+        match (&request.function, &request.calldata) {
+            (Some(_), Some(_)) => Err(SimulateError::InvalidArgument(
+                "Informe `calldata` ou `function`, não ambos".to_string(),
+            )),
+            (Some(function), None) => {
+                let abi = self.abi_registry.resolve(&request.abi, chain_id, request.to).await?;
+                let call = FunctionCall {
+                    function: function.clone(),
+                    args: request.args.clone(),
+                    key: None,
+                };
+                let (function, tokens) = CallFunctionsService::encode_call(&abi, &call)
+                    .map_err(|e| SimulateError::InvalidArgument(e.to_string()))?;
+                let data = function
+                    .encode_input(&tokens)
+                    .map_err(|e| SimulateError::InvalidArgument(e.to_string()))?;
+
+                Ok((data.into(), Some(abi), Some(function)))
+            }
+            (None, calldata) => {
+                let data = calldata.clone().unwrap_or_default();
+                let abi = self.abi_registry.resolve_optional(&request.abi, chain_id, request.to).await?;
+                let function = abi.as_ref().zip(data.get(..4)).and_then(|(abi, selector)| {
+                    abi.functions().find(|function| function.short_signature() == selector).cloned()
+                });
+
+                Ok((data, abi, function))
+            }
+        }
+    }
+
+    /// Parâmetros de `eth_call` e `eth_estimateGas`: a transação, o bloco e, se houver, os
+    /// overrides de estado.
+    pub fn params(tx: &TransactionRequest, block: BlockId, state: Option<&spoof::State>) -> Vec<Value> {
+        let mut params = vec![
+            serde_json::to_value(tx).expect("TransactionRequest é serializável"),
+            serde_json::to_value(block).expect("BlockId é serializável"),
+        ];
+        if let Some(state) = state {
+            params.push(serde_json::to_value(state).expect("State é serializável"));
+        }
+
+        params
+    }
+
+    pub async fn eth_call(pool: &RpcPool, params: Vec<Value>, abi: Option<&Abi>) -> Result<Bytes, CallError> {
+        pool.failover(|provider| {
+            let params = params.clone();
+            async move { provider.request::<_, Bytes>("eth_call", params).await }
+        })
+        .await
+        .map_err(|e| CallError::from_provider_error(abi, &e))
+    }
+
+    pub async fn estimate_gas(pool: &RpcPool, params: Vec<Value>, abi: Option<&Abi>) -> Result<U256, CallError> {
+        pool.failover(|provider| {
+            let params = params.clone();
+            async move { provider.request::<_, U256>("eth_estimateGas", params).await }
+        })
+        .await
+        .map_err(|e| CallError::from_provider_error(abi, &e))
+    }
+
+    /// Executa a transação com `eth_call` e estima o gas com `eth_estimateGas`, ambos no mesmo
+    /// bloco e com os mesmos overrides de estado. O override de bloco só é aceito pelo `eth_call`.
+    /// Reverts e falhas de RPC são retornados em `result` e `gas_estimate`; entradas inválidas da
+    /// requisição retornam erro.
+    pub async fn exec(&self, user_id: i32, chain_id: u64, request: SimulateRequest) -> Result<SimulateResponse, SimulateError> {
+        let pool = self.get_pool(user_id, chain_id).await?;
+        let value = request.value.as_ref().map(Self::parse_value).transpose()?;
+        let (data, abi, function) = self.encode(&request, chain_id).await?;
+        let resolved = CallFunctionsService::resolve_block(&pool, request.block.as_ref()).await?;
+
+        let mut tx = TransactionRequest::new().data(data);
+        if let Some(from) = request.from {
+            tx = tx.from(from);
+        }
+        if let Some(to) = request.to {
+            tx = tx.to(to);
+        }
+        if let Some(value) = value {
+            tx = tx.value(value);
+        }
+        if let Some(gas) = request.gas {
+            tx = tx.gas(gas);
+        }
+
+        let estimate_params = Self::params(&tx, resolved.id, request.state_overrides.as_ref());
+        let mut call_params = estimate_params.clone();
+        if let Some(block_overrides) = &request.block_overrides {
+            if request.state_overrides.is_none() {
+                call_params.push(Value::Object(Default::default()));
+            }
+            call_params.push(serde_json::to_value(block_overrides).expect("BlockOverrides é serializável"));
+        }
+
+        let (output, gas_estimate) = futures::join!(
+            Self::eth_call(&pool, call_params, abi.as_ref()),
+            Self::estimate_gas(&pool, estimate_params, abi.as_ref()),
+        );
+
+        let return_data = output.as_ref().ok().cloned();
+        let result = output.and_then(|output| match &function {
+            Some(function) => function
+                .decode_output(&output)
+                .map(|tokens| EthersUtils::outputs_to_json(&function.outputs, tokens))
+                .map_err(|e| CallError::InvalidOutput { message: e.to_string() }),
+            None => Ok(Value::String(output.to_string())),
+        });
+
+        Ok(SimulateResponse {
+            block: resolved.block,
+            result: result.into(),
+            return_data,
+            gas_estimate: gas_estimate.map(|gas| Value::String(gas.to_string())).into(),
+        })
+    }
+}
@@ -0,0 +1,587 @@
+use crate::repositories::ethers_repository::EthersRepository;
+use crate::rpc_transport::RpcPool;
+use crate::services::ethers::batch_call_service::MULTICALL3_ADDRESS;
+use crate::services::ethers::call_functions_service::{BlockError, BlockRef, CallError, CallFunctionsService};
+use crate::services::ethers::simulate_service::SimulateService;
+use crate::utils::abi_utils::AbiUtils;
+use crate::utils::ethers_utils::EthersUtils;
+use ethers::abi::{Abi, ParamType, Token};
+use ethers::providers::call_raw::spoof;
+use ethers::providers::Middleware;
+use ethers::types::{Address, BlockId, BlockNumber, TransactionRequest, U256, U512};
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::RwLock;
+
+/// Endereço que recebe o código do Multicall3 na simulação e executa a compra e a venda. Um
+/// endereço sem histórico evita que saldos preexistentes distorçam as medições.
+const SIMULATOR_ADDRESS: &str = "0x00000000000000000000000000000000005157e1";
+
+/// Conta que envia as transações simuladas, com saldo fornecido por override.
+const CALLER_ADDRESS: &str = "0x00000000000000000000000000000000005157e0";
+
+/// Valor padrão da compra simulada: 0.01 da moeda nativa.
+const DEFAULT_AMOUNT_IN: u64 = 10_000_000_000_000_000;
+
+/// Routers Uniswap V2 usados quando a requisição não informa `router`.
+const DEFAULT_ROUTERS: &[(u64, &str)] = &[
+    (1, "0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D"),
+    (56, "0x10ED43C718714eb63d5aA57B78B54704E256024E"),
+    (137, "0xa5E0829CaCEd8fFDD4De3c43696c57F7D7A678ff"),
+    (8453, "0x4752ba5DBc23f44D87826276BF6Fd6b1C372aD24"),
+    (42161, "0x1b02dA8Cb0d097eB8D57A175b88c7D8b47997506"),
+];
+
+/// Getters sem argumentos com que os tokens costumam expor limites por transação e por carteira.
+const LIMIT_GETTERS: &[&str] = &[
+    "maxTxAmount",
+    "_maxTxAmount",
+    "maxTransactionAmount",
+    "maxBuyAmount",
+    "maxSellAmount",
+    "maxWallet",
+    "maxWalletSize",
+    "_maxWalletSize",
+    "maxWalletAmount",
+];
+
+#[derive(Error, Debug)]
+pub enum TokenAnalysisError {
+    #[error("Nenhuma conexão configurada para o user_id {0} na chain {1}")]
+    NotFound(i32, u64),
+    #[error("{0}")]
+    InvalidArgument(String),
+    #[error("Nenhum router configurado para a chain {0}; informe `router`")]
+    UnknownRouter(u64),
+    #[error("Multicall3 não implantado na chain {0}")]
+    NoMulticall3(u64),
+    #[error(transparent)]
+    BlockError(#[from] BlockError),
+    #[error(transparent)]
+    CallError(#[from] CallError),
+}
+
+/// Opções da análise: o router Uniswap V2 (por padrão o de `DEFAULT_ROUTERS` para a chain), o
+/// valor da compra em wei da moeda nativa e o bloco da simulação.
+#[derive(Deserialize, Clone, Default)]
+pub struct AnalysisOptions {
+    #[serde(default)]
+    pub router: Option<Address>,
+    #[serde(default)]
+    pub amount_in: Option<Value>,
+    #[serde(default)]
+    pub block: Option<Value>,
+}
+
+/// Resultado de uma etapa simulada. `expected` é a cotação do router (`getAmountsOut`), que não
+/// considera taxas do token, e `received` o valor efetivamente recebido; `tax` é a diferença entre
+/// os dois em porcentagem.
+#[derive(Serialize, Clone)]
+pub struct SwapSimulation {
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<CallError>,
+    pub expected: Option<String>,
+    pub received: Option<String>,
+    pub tax: Option<f64>,
+    pub gas: Option<u64>,
+}
+
+/// Análise de compra e venda do token. `sell` é omitido quando a compra falha; `honeypot` indica
+/// que a compra funciona mas a venda reverte ou não rende nada. `limits` traz os limites expostos
+/// pelo contrato em `LIMIT_GETTERS`.
+#[derive(Serialize, Clone)]
+pub struct TokenAnalysis {
+    pub token: Address,
+    pub router: Address,
+    pub weth: Address,
+    pub amount_in: String,
+    pub block: BlockRef,
+    pub buy: SwapSimulation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sell: Option<SwapSimulation>,
+    pub honeypot: bool,
+    pub limits: BTreeMap<String, String>,
+}
+
+/// Chamada de um `aggregate3Value`: contrato, valor enviado e calldata.
+type Step = (Address, U256, Vec<u8>);
+
+/// Contexto de uma simulação: bloco, overrides de estado e os endereços envolvidos.
+struct Simulation<'a> {
+    pool: &'a RpcPool,
+    block: BlockId,
+    state: spoof::State,
+    simulator: Address,
+    caller: Address,
+    router: Address,
+    token: Address,
+    weth: Address,
+}
+
+/// Detecta honeypots e taxas de transferência simulando, com `eth_call` e overrides de estado,
+/// uma compra e uma venda do token por um router Uniswap V2.
+///
+/// O código do Multicall3 é colocado em um endereço sem histórico, que executa na mesma chamada a
+/// compra, a aprovação do router e a venda via `aggregate3Value`, medindo os saldos entre as
+/// etapas. Como o estado não persiste entre chamadas, a venda repete a compra antes de vender a
+/// quantidade medida na primeira simulação.
+pub struct TokenAnalysisService {
+    repository: Arc<RwLock<EthersRepository>>,
+    erc20: Abi,
+    router: Abi,
+    multicall3: Abi,
+}
+
+impl TokenAnalysisService {
+    pub fn new(repository: Arc<RwLock<EthersRepository>>) -> Self {
+        let parse = |abi: &str| serde_json::from_str::<Abi>(abi).expect("ABI embutida é válida");
+
+        TokenAnalysisService {
+            repository,
+            erc20: parse(AbiUtils::erc20_abi()),
+            router: parse(AbiUtils::uniswap_v2_router_abi()),
+            multicall3: parse(AbiUtils::multicall3_abi()),
+        }
+    }
+
+    async fn get_pool(&self, user_id: i32, chain_id: u64) -> Result<RpcPool, TokenAnalysisError> {
+        let lock = self.repository.read().await;
+        lock.get_pool(user_id, chain_id).ok_or(TokenAnalysisError::NotFound(user_id, chain_id))
+    }
+
+    fn default_router(chain_id: u64) -> Option<Address> {
+        DEFAULT_ROUTERS
+            .iter()
+            .find(|(chain, _)| *chain == chain_id)
+            .map(|(_, router)| router.parse().expect("endereço do router é válido"))
+    }
+
+    fn parse_amount(amount: &Value) -> Result<U256, TokenAnalysisError> {
+        match EthersUtils::json_to_token(&ParamType::Uint(256), amount) {
+            Ok(Token::Uint(amount)) => Ok(amount),
+            Ok(_) => unreachable!("uint256 sempre resulta em Token::Uint"),
+            Err(e) => Err(TokenAnalysisError::InvalidArgument(format!("`amount_in` inválido: {}", e))),
+        }
+    }
+
+    fn encode(abi: &Abi, function: &str, tokens: &[Token]) -> Vec<u8> {
+        abi.function(function)
+            .and_then(|function| function.encode_input(tokens))
+            .expect("função presente na ABI embutida")
+    }
+
+    fn decode_uint(data: &[u8]) -> Option<U256> {
+        match ethers::abi::decode(&[ParamType::Uint(256)], data).ok()?.pop() {
+            Some(Token::Uint(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Último valor do retorno de `getAmountsOut`, a quantidade recebida no fim do caminho.
+    fn decode_quote(data: &[u8]) -> Option<U256> {
+        let amounts = ethers::abi::decode(&[ParamType::Array(Box::new(ParamType::Uint(256)))], data).ok()?;
+        match amounts.into_iter().next()? {
+            Token::Array(amounts) => amounts.last()?.clone().into_uint(),
+            _ => None,
+        }
+    }
+
+    /// Diferença entre o valor esperado e o recebido, em porcentagem com duas casas. O produto é
+    /// calculado em 512 bits, já que tokens com supply absurdo podem cotar valores perto de
+    /// `U256::MAX`.
+    fn tax(expected: U256, received: U256) -> f64 {
+        if expected.is_zero() || received >= expected {
+            return 0.0;
+        }
+
+        let basis_points = (expected - received).full_mul(U256::from(10_000)) / U512::from(expected);
+        basis_points.low_u64() as f64 / 100.0
+    }
+
+    fn quote_step(&self, simulation: &Simulation<'_>, amount: U256, path: [Address; 2]) -> Step {
+        let path = Token::Array(path.into_iter().map(Token::Address).collect());
+        let data = Self::encode(&self.router, "getAmountsOut", &[Token::Uint(amount), path]);
+        (simulation.router, U256::zero(), data)
+    }
+
+    fn buy_step(&self, simulation: &Simulation<'_>, amount_in: U256) -> Step {
+        let data = Self::encode(
+            &self.router,
+            "swapExactETHForTokensSupportingFeeOnTransferTokens",
+            &[
+                Token::Uint(U256::zero()),
+                Token::Array(vec![Token::Address(simulation.weth), Token::Address(simulation.token)]),
+                Token::Address(simulation.simulator),
+                Token::Uint(U256::MAX),
+            ],
+        );
+        (simulation.router, amount_in, data)
+    }
+
+    fn approve_step(&self, simulation: &Simulation<'_>) -> Step {
+        let data = Self::encode(
+            &self.erc20,
+            "approve",
+            &[Token::Address(simulation.router), Token::Uint(U256::MAX)],
+        );
+        (simulation.token, U256::zero(), data)
+    }
+
+    fn sell_step(&self, simulation: &Simulation<'_>, amount: U256) -> Step {
+        let data = Self::encode(
+            &self.router,
+            "swapExactTokensForTokensSupportingFeeOnTransferTokens",
+            &[
+                Token::Uint(amount),
+                Token::Uint(U256::zero()),
+                Token::Array(vec![Token::Address(simulation.token), Token::Address(simulation.weth)]),
+                Token::Address(simulation.simulator),
+                Token::Uint(U256::MAX),
+            ],
+        );
+        (simulation.router, U256::zero(), data)
+    }
+
+    fn balance_step(&self, simulation: &Simulation<'_>, token: Address) -> Step {
+        let data = Self::encode(&self.erc20, "balanceOf", &[Token::Address(simulation.simulator)]);
+        (token, U256::zero(), data)
+    }
+
+    /// Transação do `caller` para o simulador executando as etapas com `aggregate3Value`, sem
+    /// interromper nas falhas.
+    fn aggregate_tx(&self, simulation: &Simulation<'_>, steps: &[Step]) -> TransactionRequest {
+        let value = steps.iter().fold(U256::zero(), |total, (_, value, _)| total + value);
+        let calls = steps
+            .iter()
+            .map(|(target, value, data)| {
+                Token::Tuple(vec![
+                    Token::Address(*target),
+                    Token::Bool(true),
+                    Token::Uint(*value),
+                    Token::Bytes(data.clone()),
+                ])
+            })
+            .collect();
+        let data = Self::encode(&self.multicall3, "aggregate3Value", &[Token::Array(calls)]);
+
+        TransactionRequest::new()
+            .from(simulation.caller)
+            .to(simulation.simulator)
+            .value(value)
+            .data(data)
+    }
+
+    /// Executa as etapas e retorna, para cada uma, se teve sucesso e os dados retornados. Um retorno
+    /// com quantidade de resultados diferente da de etapas resulta em `CallError::InvalidOutput`,
+    /// de modo que os chamadores podem indexar os resultados pela posição das etapas.
+    async fn aggregate(&self, simulation: &Simulation<'_>, steps: &[Step]) -> Result<Vec<(bool, Vec<u8>)>, CallError> {
+        let tx = self.aggregate_tx(simulation, steps);
+        let params = SimulateService::params(&tx, simulation.block, Some(&simulation.state));
+        let output = SimulateService::eth_call(simulation.pool, params, Some(&self.router)).await?;
+
+        let function = self.multicall3.function("aggregate3Value").expect("função presente na ABI embutida");
+        let results = match function.decode_output(&output).ok().and_then(|mut tokens| tokens.pop()) {
+            Some(Token::Array(results)) if results.len() == steps.len() => results,
+            Some(Token::Array(results)) => {
+                return Err(CallError::InvalidOutput {
+                    message: format!(
+                        "aggregate3Value retornou {} resultados para {} etapas",
+                        results.len(),
+                        steps.len()
+                    ),
+                })
+            }
+            _ => return Err(CallError::InvalidOutput { message: "Retorno inválido de aggregate3Value".to_string() }),
+        };
+
+        Ok(results
+            .into_iter()
+            .map(|result| match result {
+                Token::Tuple(mut fields) if fields.len() == 2 => match (fields.pop(), fields.pop()) {
+                    (Some(Token::Bytes(data)), Some(Token::Bool(success))) => (success, data),
+                    _ => (false, Vec::new()),
+                },
+                _ => (false, Vec::new()),
+            })
+            .collect())
+    }
+
+    async fn estimate_gas(&self, simulation: &Simulation<'_>, tx: &TransactionRequest) -> Option<u64> {
+        let params = SimulateService::params(tx, simulation.block, Some(&simulation.state));
+        SimulateService::estimate_gas(simulation.pool, params, Some(&self.router))
+            .await
+            .ok()
+            .map(|gas| gas.as_u64())
+    }
+
+    async fn simulate_buy(&self, simulation: &Simulation<'_>, amount_in: U256) -> Result<SwapSimulation, CallError> {
+        let steps = [
+            self.quote_step(simulation, amount_in, [simulation.weth, simulation.token]),
+            self.buy_step(simulation, amount_in),
+            self.balance_step(simulation, simulation.token),
+        ];
+
+        // O gas da compra é estimado como uma transação direta do `caller` para o router.
+        let (target, value, data) = steps[1].clone();
+        let buy_tx = TransactionRequest::new().from(simulation.caller).to(target).value(value).data(data);
+
+        let (results, gas) = futures::join!(self.aggregate(simulation, &steps), self.estimate_gas(simulation, &buy_tx));
+        let results = results?;
+
+        let expected = Self::decode_quote(&results[0].1);
+        let (success, data) = &results[1];
+        if !success {
+            return Ok(SwapSimulation {
+                success: false,
+                error: Some(CallError::revert(Some(&self.router), data.clone().into())),
+                expected: expected.map(|expected| expected.to_string()),
+                received: None,
+                tax: None,
+                gas: None,
+            });
+        }
+
+        let received = Self::decode_uint(&results[2].1);
+
+        Ok(SwapSimulation {
+            success: true,
+            error: None,
+            expected: expected.map(|expected| expected.to_string()),
+            received: received.map(|received| received.to_string()),
+            tax: expected.zip(received).map(|(expected, received)| Self::tax(expected, received)),
+            gas,
+        })
+    }
+
+    /// Repete a compra e vende `amount`, a quantidade recebida na compra. O gas da venda é a
+    /// diferença entre as estimativas com e sem a etapa de venda.
+    async fn simulate_sell(
+        &self,
+        simulation: &Simulation<'_>,
+        amount_in: U256,
+        amount: U256,
+    ) -> Result<SwapSimulation, CallError> {
+        let steps = vec![
+            self.buy_step(simulation, amount_in),
+            self.approve_step(simulation),
+            self.quote_step(simulation, amount, [simulation.token, simulation.weth]),
+            self.balance_step(simulation, simulation.weth),
+            self.sell_step(simulation, amount),
+            self.balance_step(simulation, simulation.weth),
+        ];
+        let without_sell: Vec<Step> = steps[..4].to_vec();
+
+        let with_sell_tx = self.aggregate_tx(simulation, &steps);
+        let without_sell_tx = self.aggregate_tx(simulation, &without_sell);
+        let (results, with_sell_gas, without_sell_gas) = futures::join!(
+            self.aggregate(simulation, &steps),
+            self.estimate_gas(simulation, &with_sell_tx),
+            self.estimate_gas(simulation, &without_sell_tx),
+        );
+        let results = results?;
+
+        let expected = Self::decode_quote(&results[2].1);
+        let failure = [&results[1], &results[4]]
+            .into_iter()
+            .find(|(success, _)| !success)
+            .map(|(_, data)| CallError::revert(Some(&self.erc20), data.clone().into()));
+
+        if let Some(error) = failure {
+            return Ok(SwapSimulation {
+                success: false,
+                error: Some(error),
+                expected: expected.map(|expected| expected.to_string()),
+                received: None,
+                tax: None,
+                gas: None,
+            });
+        }
+
+        let received = Self::decode_uint(&results[5].1)
+            .zip(Self::decode_uint(&results[3].1))
+            .map(|(after, before)| after.saturating_sub(before));
+
+        Ok(SwapSimulation {
+            success: true,
+            error: None,
+            expected: expected.map(|expected| expected.to_string()),
+            received: received.map(|received| received.to_string()),
+            tax: expected.zip(received).map(|(expected, received)| Self::tax(expected, received)),
+            gas: with_sell_gas
+                .zip(without_sell_gas)
+                .map(|(with_sell, without_sell)| with_sell.saturating_sub(without_sell)),
+        })
+    }
+
+    /// Consulta os getters de `LIMIT_GETTERS`, mantendo apenas os que o contrato responde.
+    async fn limits(&self, simulation: &Simulation<'_>) -> BTreeMap<String, String> {
+        let calls = LIMIT_GETTERS.iter().map(|getter| async move {
+            let selector = ethers::utils::id(format!("{}()", getter));
+            let tx = TransactionRequest::new().to(simulation.token).data(selector.to_vec());
+            let params = SimulateService::params(&tx, simulation.block, None);
+            let output = SimulateService::eth_call(simulation.pool, params, None).await.ok()?;
+
+            Self::decode_uint(&output).map(|limit| (getter.to_string(), limit.to_string()))
+        });
+
+        join_all(calls).await.into_iter().flatten().collect()
+    }
+
+    async fn weth(&self, pool: &RpcPool, block: BlockId, router: Address) -> Result<Address, CallError> {
+        let tx = TransactionRequest::new().to(router).data(Self::encode(&self.router, "WETH", &[]));
+        let output = SimulateService::eth_call(pool, SimulateService::params(&tx, block, None), Some(&self.router)).await?;
+
+        match ethers::abi::decode(&[ParamType::Address], &output).ok().and_then(|mut tokens| tokens.pop()) {
+            Some(Token::Address(weth)) => Ok(weth),
+            _ => Err(CallError::InvalidOutput { message: "Retorno inválido de WETH() no router".to_string() }),
+        }
+    }
+
+    /// Código do Multicall3 no bloco da simulação, colocado no endereço do simulador.
+    async fn multicall3_code(pool: &RpcPool, block: BlockId, chain_id: u64) -> Result<ethers::types::Bytes, TokenAnalysisError> {
+        let multicall: Address = MULTICALL3_ADDRESS.parse().expect("endereço do Multicall3 é válido");
+        let block = match block {
+            BlockId::Number(BlockNumber::Pending) => None,
+            block => Some(block),
+        };
+
+        let code = pool
+            .failover(|provider| async move { provider.get_code(multicall, block).await })
+            .await
+            .map_err(|e| CallError::Rpc { message: e.to_string() })?;
+
+        if code.is_empty() {
+            return Err(TokenAnalysisError::NoMulticall3(chain_id));
+        }
+
+        Ok(code)
+    }
+
+    fn router_and_amount(chain_id: u64, options: &AnalysisOptions) -> Result<(Address, U256), TokenAnalysisError> {
+        let router = options
+            .router
+            .or_else(|| Self::default_router(chain_id))
+            .ok_or(TokenAnalysisError::UnknownRouter(chain_id))?;
+        let amount_in = match &options.amount_in {
+            Some(amount_in) => Self::parse_amount(amount_in)?,
+            None => U256::from(DEFAULT_AMOUNT_IN),
+        };
+
+        Ok((router, amount_in))
+    }
+
+    /// Indica se o router já cota a compra de `token` com `amount_in` da moeda nativa no bloco mais
+    /// recente, ou seja, se o par com o WETH existe e tem liquidez. Sem isso a análise sempre falha.
+    pub async fn has_liquidity(
+        &self,
+        user_id: i32,
+        chain_id: u64,
+        token: Address,
+        options: &AnalysisOptions,
+    ) -> Result<bool, TokenAnalysisError> {
+        let (router, amount_in) = Self::router_and_amount(chain_id, options)?;
+        let pool = self.get_pool(user_id, chain_id).await?;
+        let block = BlockId::Number(BlockNumber::Latest);
+        let weth = self.weth(&pool, block, router).await?;
+
+        let path = Token::Array(vec![Token::Address(weth), Token::Address(token)]);
+        let data = Self::encode(&self.router, "getAmountsOut", &[Token::Uint(amount_in), path]);
+        let tx = TransactionRequest::new().to(router).data(data);
+
+        // Sem o par, o router reverte; qualquer revert é tratado como ausência de liquidez.
+        match SimulateService::eth_call(&pool, SimulateService::params(&tx, block, None), Some(&self.router)).await {
+            Ok(output) => Ok(Self::decode_quote(&output).is_some_and(|quote| !quote.is_zero())),
+            Err(CallError::Revert { .. }) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Simula a compra de `amount_in` da moeda nativa em `token` e a venda de tudo o que foi
+    /// recebido, retornando taxas, falhas, gas e limites do contrato.
+    pub async fn exec(
+        &self,
+        user_id: i32,
+        chain_id: u64,
+        token: Address,
+        options: AnalysisOptions,
+    ) -> Result<TokenAnalysis, TokenAnalysisError> {
+        let (router, amount_in) = Self::router_and_amount(chain_id, &options)?;
+        let pool = self.get_pool(user_id, chain_id).await?;
+        let resolved = CallFunctionsService::resolve_block(&pool, options.block.as_ref()).await?;
+        let (weth, code) = futures::join!(
+            self.weth(&pool, resolved.id, router),
+            Self::multicall3_code(&pool, resolved.id, chain_id),
+        );
+
+        let simulator: Address = SIMULATOR_ADDRESS.parse().expect("endereço do simulador é válido");
+        let caller: Address = CALLER_ADDRESS.parse().expect("endereço do caller é válido");
+        let mut state = spoof::state();
+        state.account(simulator).code(code?);
+        state.account(caller).balance(amount_in.saturating_mul(U256::from(10)));
+
+        let simulation = Simulation {
+            pool: &pool,
+            block: resolved.id,
+            state,
+            simulator,
+            caller,
+            router,
+            token,
+            weth: weth?,
+        };
+
+        let (buy, limits) = futures::join!(self.simulate_buy(&simulation, amount_in), self.limits(&simulation));
+        let buy = buy?;
+
+        let bought = buy.received.as_ref().and_then(|received| U256::from_dec_str(received).ok());
+        let sell = match bought {
+            Some(bought) if buy.success && !bought.is_zero() => {
+                Some(self.simulate_sell(&simulation, amount_in, bought).await?)
+            }
+            _ => None,
+        };
+
+        let honeypot = match &sell {
+            Some(sell) => !sell.success || sell.received.as_deref() == Some("0"),
+            None => false,
+        };
+
+        Ok(TokenAnalysis {
+            token,
+            router,
+            weth: simulation.weth,
+            amount_in: amount_in.to_string(),
+            block: resolved.block,
+            buy,
+            sell,
+            honeypot,
+            limits,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tax_is_a_percentage_with_two_decimals() {
+        assert_eq!(TokenAnalysisService::tax(U256::from(1_000), U256::from(900)), 10.0);
+        assert_eq!(TokenAnalysisService::tax(U256::from(3), U256::from(2)), 33.33);
+        assert_eq!(TokenAnalysisService::tax(U256::from(100), U256::from(100)), 0.0);
+        assert_eq!(TokenAnalysisService::tax(U256::from(100), U256::from(150)), 0.0);
+        assert_eq!(TokenAnalysisService::tax(U256::zero(), U256::zero()), 0.0);
+    }
+
+    #[test]
+    fn tax_does_not_overflow_near_u256_max() {
+        assert_eq!(TokenAnalysisService::tax(U256::MAX, U256::zero()), 100.0);
+        assert_eq!(TokenAnalysisService::tax(U256::MAX, U256::MAX / 2), 50.0);
+        assert_eq!(TokenAnalysisService::tax(U256::MAX / 10_000 + 1, U256::one()), 99.99);
+    }
+}
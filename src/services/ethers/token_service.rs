@@ -0,0 +1,408 @@
+use crate::repositories::ethers_repository::EthersRepository;
+use crate::rpc_transport::RpcPool;
+use crate::services::ethers::call_functions_service::{BlockError, BlockRef, CallError, CallFunctionsService};
+use crate::utils::abi_utils::AbiUtils;
+use crate::utils::ethers_utils::EthersUtils;
+use ethers::abi::{Abi, ParamType, Token};
+use ethers::providers::Middleware;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{Address, BlockId, Bytes, TransactionRequest, U256};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::RwLock;
+
+/// Gas usado nas consultas de `supportsInterface`, como determina o ERC165.
+const SUPPORTS_INTERFACE_GAS: u64 = 30_000;
+
+const ERC165_INTERFACE_ID: [u8; 4] = [0x01, 0xff, 0xc9, 0xa7];
+
+/// Interfaces conhecidas, que podem ser consultadas pelo nome em vez do id.
+const KNOWN_INTERFACES: &[(&str, [u8; 4])] = &[
+    ("erc165", ERC165_INTERFACE_ID),
+    ("erc721", [0x80, 0xac, 0x58, 0xcd]),
+    ("erc721_metadata", [0x5b, 0x5e, 0x13, 0x9f]),
+    ("erc721_enumerable", [0x78, 0x0e, 0x9d, 0x63]),
+    ("erc1155", [0xd9, 0xb6, 0x7a, 0x26]),
+    ("erc1155_metadata_uri", [0x0e, 0x89, 0x34, 0x1c]),
+    ("erc2981", [0x2a, 0x55, 0x20, 0x5a]),
+];
+
+#[derive(Error, Debug)]
+pub enum TokenError {
+    #[error("Nenhuma conexão configurada para o user_id {0} na chain {1}")]
+    NotFound(i32, u64),
+    #[error("{0}")]
+    InvalidArgument(String),
+    #[error(transparent)]
+    BlockError(#[from] BlockError),
+    #[error(transparent)]
+    CallError(#[from] CallError),
+}
+
+#[derive(Serialize)]
+pub struct Erc20Metadata {
+    pub address: Address,
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    pub decimals: Option<u8>,
+    pub total_supply: Option<String>,
+    pub block: BlockRef,
+}
+
+#[derive(Serialize)]
+pub struct Erc721Token {
+    pub address: Address,
+    pub token_id: String,
+    pub owner: Address,
+    pub token_uri: Option<String>,
+    pub block: BlockRef,
+}
+
+#[derive(Serialize)]
+pub struct Erc1155Balance {
+    pub account: Address,
+    pub id: String,
+    pub balance: String,
+}
+
+#[derive(Serialize)]
+pub struct Erc1155Balances {
+    pub address: Address,
+    pub balances: Vec<Erc1155Balance>,
+    pub block: BlockRef,
+}
+
+#[derive(Serialize)]
+pub struct InterfaceSupport {
+    pub address: Address,
+    pub erc165: bool,
+    pub interfaces: BTreeMap<String, bool>,
+    pub block: BlockRef,
+}
+
+/// Leituras dos padrões de token (ERC20, ERC721, ERC1155) e detecção de interfaces via ERC165,
+/// com as ABIs embutidas em `AbiUtils`.
+pub struct TokenService {
+    repository: Arc<RwLock<EthersRepository>>,
+    erc20: Abi,
+    erc721: Abi,
+    erc1155: Abi,
+    erc165: Abi,
+}
+
+impl TokenService {
+    pub fn new(repository: Arc<RwLock<EthersRepository>>) -> Self {
+        let parse = |abi: &str| serde_json::from_str::<Abi>(abi).expect("ABI embutida é válida");
+
+        TokenService {
+            repository,
+            erc20: parse(AbiUtils::erc20_abi()),
+            erc721: parse(AbiUtils::erc721_abi()),
+            erc1155: parse(AbiUtils::erc1155_abi()),
+            erc165: parse(AbiUtils::erc165_abi()),
+        }
+    }
+
+    async fn get_pool(&self, user_id: i32, chain_id: u64) -> Result<RpcPool, TokenError> {
+        self.repository
+            .read()
+            .await
+            .get_pool(user_id, chain_id)
+            .ok_or(TokenError::NotFound(user_id, chain_id))
+    }
+
+    pub fn parse_address(address: &str) -> Result<Address, TokenError> {
+        address
+            .parse()
+            .map_err(|e| TokenError::InvalidArgument(format!("Endereço inválido {}: {}", address, e)))
+    }
+
+    pub fn parse_token_id(token_id: &Value) -> Result<U256, TokenError> {
+        match EthersUtils::json_to_token(&ParamType::Uint(256), token_id) {
+            Ok(Token::Uint(token_id)) => Ok(token_id),
+            Ok(_) => unreachable!("uint256 sempre resulta em Token::Uint"),
+            Err(e) => Err(TokenError::InvalidArgument(format!("Token id inválido: {}", e))),
+        }
+    }
+
+    /// Executa `function` da ABI no contrato e retorna os tokens decodificados do retorno.
+    async fn call(
+        pool: &RpcPool,
+        block: BlockId,
+        to: Address,
+        abi: &Abi,
+        function: &str,
+        tokens: &[Token],
+    ) -> Result<Vec<Token>, CallError> {
+        let function = abi.function(function).expect("função presente na ABI embutida");
+        let data = function
+            .encode_input(tokens)
+            .map_err(|e| CallError::InvalidArguments { message: e.to_string() })?;
+        let output = Self::call_raw(pool, block, to, data.into(), None, abi).await?;
+
+        function
+            .decode_output(&output)
+            .map_err(|e| CallError::InvalidOutput { message: e.to_string() })
+    }
+
+    async fn call_raw(
+        pool: &RpcPool,
+        block: BlockId,
+        to: Address,
+        data: Bytes,
+        gas: Option<u64>,
+        abi: &Abi,
+    ) -> Result<Bytes, CallError> {
+        let mut request = TransactionRequest::new().to(to).data(data);
+        if let Some(gas) = gas {
+            request = request.gas(gas);
+        }
+        let tx: TypedTransaction = request.into();
+
+        pool.failover(|provider| {
+            let tx = tx.clone();
+            async move { provider.call(&tx, Some(block)).await }
+        })
+        .await
+        .map_err(|e| CallError::from_provider_error(Some(abi), &e))
+    }
+
+    /// Decodifica o retorno de `name`/`symbol`: `string` no padrão ou `bytes32` em tokens antigos
+    /// como o MKR, caso em que os bytes nulos à direita são descartados.
+    fn decode_text(data: &[u8]) -> Option<String> {
+        if let Ok(mut tokens) = ethers::abi::decode(&[ParamType::String], data) {
+            if let Some(Token::String(text)) = tokens.pop() {
+                return Some(text);
+            }
+        }
+
+        if data.len() == 32 {
+            let end = data.iter().position(|byte| *byte == 0).unwrap_or(32);
+            return String::from_utf8(data[..end].to_vec()).ok();
+        }
+
+        None
+    }
+
+    /// Separa "não implementado" de "nó indisponível": reverts e retornos que não decodificam viram
+    /// `None`, enquanto falhas de RPC são propagadas.
+    fn optional<T>(result: Result<T, CallError>) -> Result<Option<T>, TokenError> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(error @ CallError::Rpc { .. }) => Err(error.into()),
+            Err(_) => Ok(None),
+        }
+    }
+
+    async fn text(&self, pool: &RpcPool, block: BlockId, address: Address, function: &str) -> Result<Option<String>, TokenError> {
+        let selector = self.erc20.function(function).expect("função presente na ABI embutida").short_signature();
+        let data = Self::call_raw(pool, block, address, selector.to_vec().into(), None, &self.erc20).await;
+
+        Ok(Self::optional(data)?.and_then(|data| Self::decode_text(&data)))
+    }
+
+    /// Metadados do ERC20. Campos que o contrato não implementa (a chamada reverte ou o retorno não
+    /// decodifica) são retornados como `null`; falhas de RPC retornam erro.
+    pub async fn erc20_metadata(&self, user_id: i32, chain_id: u64, address: Address, block: Option<Value>) -> Result<Erc20Metadata, TokenError> {
+        let pool = self.get_pool(user_id, chain_id).await?;
+        let resolved = CallFunctionsService::resolve_block(&pool, block.as_ref()).await?;
+        let block = resolved.id;
+
+        let (name, symbol, decimals, total_supply) = futures::join!(
+            self.text(&pool, block, address, "name"),
+            self.text(&pool, block, address, "symbol"),
+            Self::call(&pool, block, address, &self.erc20, "decimals", &[]),
+            Self::call(&pool, block, address, &self.erc20, "totalSupply", &[]),
+        );
+
+        let (name, symbol) = (name?, symbol?);
+        let decimals = match Self::optional(decimals)?.and_then(|mut tokens| tokens.pop()) {
+            Some(Token::Uint(decimals)) if decimals <= U256::from(u8::MAX) => Some(decimals.as_u32() as u8),
+            _ => None,
+        };
+        let total_supply = match Self::optional(total_supply)?.and_then(|mut tokens| tokens.pop()) {
+            Some(Token::Uint(total_supply)) => Some(total_supply.to_string()),
+            _ => None,
+        };
+
+        Ok(Erc20Metadata {
+            address,
+            name,
+            symbol,
+            decimals,
+            total_supply,
+            block: resolved.block,
+        })
+    }
+
+    /// Dono e `tokenURI` de um token ERC721. Um `ownerOf` que reverte (token inexistente ou
+    /// queimado) retorna o erro decodificado; a falta de `tokenURI` resulta em `null`.
+    pub async fn erc721_token(&self, user_id: i32, chain_id: u64, address: Address, token_id: U256, block: Option<Value>) -> Result<Erc721Token, TokenError> {
+        let pool = self.get_pool(user_id, chain_id).await?;
+        let resolved = CallFunctionsService::resolve_block(&pool, block.as_ref()).await?;
+        let block = resolved.id;
+        let args = [Token::Uint(token_id)];
+
+        let (owner, token_uri) = futures::join!(
+            Self::call(&pool, block, address, &self.erc721, "ownerOf", &args),
+            Self::call(&pool, block, address, &self.erc721, "tokenURI", &args),
+        );
+
+        let owner = match owner?.pop() {
+            Some(Token::Address(owner)) => owner,
+            _ => return Err(CallError::InvalidOutput { message: "Retorno inválido de ownerOf".to_string() }.into()),
+        };
+        let token_uri = match Self::optional(token_uri)?.and_then(|mut tokens| tokens.pop()) {
+            Some(Token::String(token_uri)) => Some(token_uri),
+            _ => None,
+        };
+
+        Ok(Erc721Token {
+            address,
+            token_id: token_id.to_string(),
+            owner,
+            token_uri,
+            block: resolved.block,
+        })
+    }
+
+    /// Saldos ERC1155 via `balanceOfBatch`, um para cada par (`accounts[i]`, `ids[i]`).
+    pub async fn erc1155_balances(
+        &self,
+        user_id: i32,
+        chain_id: u64,
+        address: Address,
+        accounts: Vec<Address>,
+        ids: Vec<U256>,
+        block: Option<Value>,
+    ) -> Result<Erc1155Balances, TokenError> {
+        if accounts.len() != ids.len() {
+            return Err(TokenError::InvalidArgument(format!(
+                "`accounts` e `ids` precisam ter o mesmo tamanho ({} e {})",
+                accounts.len(),
+                ids.len()
+            )));
+        }
+
+        let pool = self.get_pool(user_id, chain_id).await?;
+        let resolved = CallFunctionsService::resolve_block(&pool, block.as_ref()).await?;
+
+        let args = [
+            Token::Array(accounts.iter().copied().map(Token::Address).collect()),
+            Token::Array(ids.iter().copied().map(Token::Uint).collect()),
+        ];
+        let balances = match Self::call(&pool, resolved.id, address, &self.erc1155, "balanceOfBatch", &args).await?.pop() {
+            Some(Token::Array(balances)) if balances.len() == accounts.len() => balances,
+            _ => return Err(CallError::InvalidOutput { message: "Retorno inválido de balanceOfBatch".to_string() }.into()),
+        };
+
+        let balances = accounts
+            .into_iter()
+            .zip(ids)
+            .zip(balances)
+            .map(|((account, id), balance)| Erc1155Balance {
+                account,
+                id: id.to_string(),
+                balance: balance.into_uint().unwrap_or_default().to_string(),
+            })
+            .collect();
+
+        Ok(Erc1155Balances { address, balances, block: resolved.block })
+    }
+
+    /// Converte o nome de uma interface conhecida ou um id hexadecimal de 4 bytes.
+    pub fn parse_interface(interface: &str) -> Result<[u8; 4], TokenError> {
+        if let Some((_, id)) = KNOWN_INTERFACES.iter().find(|(name, _)| name.eq_ignore_ascii_case(interface)) {
+            return Ok(*id);
+        }
+
+        ethers::utils::hex::decode(interface.trim_start_matches("0x"))
+            .ok()
+            .and_then(|bytes| <[u8; 4]>::try_from(bytes).ok())
+            .ok_or_else(|| TokenError::InvalidArgument(format!("Interface inválida: {}", interface)))
+    }
+
+    /// Consulta `supportsInterface`. Um revert ou retorno inválido conta como não suportado; falhas
+    /// de RPC retornam erro.
+    async fn supports_interface(&self, pool: &RpcPool, block: BlockId, address: Address, interface_id: [u8; 4]) -> Result<bool, TokenError> {
+        let function = self.erc165.function("supportsInterface").expect("função presente na ABI embutida");
+        let data = function
+            .encode_input(&[Token::FixedBytes(interface_id.to_vec())])
+            .expect("bytes4 sempre é codificável");
+
+        let output = Self::call_raw(pool, block, address, data.into(), Some(SUPPORTS_INTERFACE_GAS), &self.erc165).await;
+
+        Ok(matches!(
+            Self::optional(output)?
+                .and_then(|output| function.decode_output(&output).ok())
+                .and_then(|mut tokens| tokens.pop()),
+            Some(Token::Bool(true))
+        ))
+    }
+
+    /// Detecta as interfaces do contrato via ERC165. O contrato só é considerado compatível com o
+    /// ERC165 quando responde `true` para `0x01ffc9a7` e `false` para `0xffffffff`; caso contrário
+    /// todas as interfaces são reportadas como não suportadas. Sem `interfaces`, consulta todas as
+    /// interfaces conhecidas. Se o nó falhar em alguma consulta, retorna o erro de RPC em vez de
+    /// reportar a interface como não suportada.
+    pub async fn interfaces(
+        &self,
+        user_id: i32,
+        chain_id: u64,
+        address: Address,
+        interfaces: Vec<String>,
+        block: Option<Value>,
+    ) -> Result<InterfaceSupport, TokenError> {
+        let interfaces: Vec<(String, [u8; 4])> = if interfaces.is_empty() {
+            KNOWN_INTERFACES.iter().map(|(name, id)| (name.to_string(), *id)).collect()
+        } else {
+            interfaces
+                .into_iter()
+                .map(|interface| Self::parse_interface(&interface).map(|id| (interface, id)))
+                .collect::<Result<_, _>>()?
+        };
+
+        let pool = self.get_pool(user_id, chain_id).await?;
+        let resolved = CallFunctionsService::resolve_block(&pool, block.as_ref()).await?;
+        let block = resolved.id;
+
+        let (supports_erc165, supports_invalid) = futures::join!(
+            self.supports_interface(&pool, block, address, ERC165_INTERFACE_ID),
+            self.supports_interface(&pool, block, address, [0xff; 4]),
+        );
+        let erc165 = supports_erc165? && !supports_invalid?;
+
+        let mut support = BTreeMap::new();
+        for (name, id) in interfaces {
+            let supported = erc165 && self.supports_interface(&pool, block, address, id).await?;
+            support.insert(name, supported);
+        }
+
+        Ok(InterfaceSupport {
+            address,
+            erc165,
+            interfaces: support,
+            block: resolved.block,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_rpc_failures_are_errors() {
+        let revert = CallError::Revert { message: "sem dados de revert".to_string(), reason: None, data: Bytes::new() };
+        let output = CallError::InvalidOutput { message: "retorno vazio".to_string() };
+        let rpc = CallError::Rpc { message: "connection refused".to_string() };
+
+        assert!(matches!(TokenService::optional(Ok(1)), Ok(Some(1))));
+        assert!(matches!(TokenService::optional::<u8>(Err(revert)), Ok(None)));
+        assert!(matches!(TokenService::optional::<u8>(Err(output)), Ok(None)));
+        assert!(matches!(TokenService::optional::<u8>(Err(rpc)), Err(TokenError::CallError(CallError::Rpc { .. }))));
+    }
+}
@@ -1,19 +1,578 @@
-use ethers::abi::Token;
-use serde_json::json;
+use ethers::abi::{Abi, Param, ParamType, RawLog, Token};
+use ethers::types::{Address, Block, BlockId, BlockNumber, Transaction, H256, I256, U256};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 
 pub struct EthersUtils;
 
+/// Seletor de `Error(string)`, usado por `require` e `revert` com mensagem.
+const REVERT_ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// Seletor de `Panic(uint256)`, usado em falhas de `assert`, overflow, divisão por zero etc.
+const REVERT_PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// Envelope que adiciona o `chain_id` a um documento, mantendo os campos originais no mesmo nível.
+/// Usado nos payloads de webhook e nos documentos indexados no Elasticsearch.
+///
+/// Em webhooks que retratam um documento entregue anteriormente, porque o bloco de origem saiu da
+/// chain canônica em um reorg, o envelope também leva `"removed": true`. Em assinaturas com
+/// `confirmations`, o envelope informa se a entrega é `"pending"` ou `"confirmed"` em `status`.
+#[derive(Serialize)]
+pub struct ChainDocument<T> {
+    pub chain_id: u64,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub removed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<DeliveryStatus>,
+    #[serde(flatten)]
+    pub document: T,
+}
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    Pending,
+    Confirmed,
+}
+
+/// Opções de entrega dos listeners: `confirmations` é a profundidade que o bloco precisa atingir
+/// antes da entrega (0 entrega imediatamente, sem `status`) e `notify_pending` também envia cada
+/// documento assim que aparece, com `"status": "pending"`.
+#[derive(Deserialize, Clone, Copy, Default)]
+pub struct DeliveryOptions {
+    #[serde(default)]
+    pub confirmations: u64,
+    #[serde(default)]
+    pub notify_pending: bool,
+}
+
+/// Documento do índice `blocks`: o cabeçalho do bloco com os campos usados em consultas por
+/// intervalo de tempo e análises de gas. Valores numéricos como números JSON, para consultas de
+/// intervalo no Elasticsearch; `timestamp` em segundos.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockDocument {
+    pub number: u64,
+    pub hash: H256,
+    pub parent_hash: H256,
+    pub timestamp: u64,
+    pub base_fee_per_gas: Option<u64>,
+    pub gas_used: u64,
+    pub gas_limit: u64,
+    pub miner: Option<Address>,
+    pub transaction_count: usize,
+}
+
+impl From<&Block<Transaction>> for BlockDocument {
+    fn from(block: &Block<Transaction>) -> Self {
+        BlockDocument {
+            number: block.number.unwrap_or_default().as_u64(),
+            hash: block.hash.unwrap_or_default(),
+            parent_hash: block.parent_hash,
+            timestamp: u64::try_from(block.timestamp).unwrap_or(u64::MAX),
+            base_fee_per_gas: block.base_fee_per_gas.and_then(|fee| u64::try_from(fee).ok()),
+            gas_used: u64::try_from(block.gas_used).unwrap_or(u64::MAX),
+            gas_limit: u64::try_from(block.gas_limit).unwrap_or(u64::MAX),
+            miner: block.author,
+            transaction_count: block.transactions.len(),
+        }
+    }
+}
+
+impl<T> ChainDocument<T> {
+    pub fn new(chain_id: u64, document: T) -> Self {
+        ChainDocument { chain_id, removed: false, status: None, document }
+    }
+
+    pub fn removed(chain_id: u64, document: T) -> Self {
+        ChainDocument { chain_id, removed: true, status: None, document }
+    }
+
+    pub fn with_status(self, status: Option<DeliveryStatus>) -> Self {
+        ChainDocument { status, ..self }
+    }
+}
+
 impl EthersUtils {
+    /// Converte um `Token` em JSON, no mesmo formato aceito por `json_to_token`:
+    ///
+    /// - `address`: string hexadecimal.
+    /// - `uint<N>`/`int<N>`: string decimal, com sinal em `int<N>`, para não perder precisão.
+    /// - `bytes`/`bytes<N>`: string hexadecimal com `0x`.
+    /// - `T[]`/`T[N]` e tuplas: arrays JSON, recursivamente. A ABI do ethers não guarda os nomes dos
+    ///   componentes de tuplas, então apenas os parâmetros de primeiro nível viram objetos (veja
+    ///   `params_to_json` e `params_from_json`).
     pub fn token_to_json(token: Token) -> serde_json::Value {
         match token {
             Token::String(s) => json!(s),
             Token::Uint(u) => json!(u.to_string()),
-            Token::Int(i) => json!(i.to_string()),
+            Token::Int(i) => json!(I256::from_raw(i).to_string()),
             Token::Bool(b) => json!(b),
             Token::Address(addr) => json!(format!("{:?}", addr)),
-            Token::Bytes(bytes) => json!(bytes),
-            _ => json!(format!("{:?}", token)),
+            Token::Bytes(bytes) | Token::FixedBytes(bytes) => {
+                json!(format!("0x{}", ethers::utils::hex::encode(bytes)))
+            }
+            Token::Array(items) | Token::FixedArray(items) | Token::Tuple(items) => {
+                Value::Array(items.into_iter().map(Self::token_to_json).collect())
+            }
         }
     }
 
-}
\ No newline at end of file
+    /// Converte uma lista de parâmetros decodificados em um objeto JSON indexado pelos nomes da
+    /// ABI. Se algum parâmetro não tiver nome, ou houver nomes repetidos, retorna um array na ordem
+    /// da ABI.
+    pub fn params_to_json(names: &[&str], tokens: Vec<Token>) -> Value {
+        let named = names.iter().enumerate().all(|(index, name)| {
+            !name.is_empty() && !names[..index].contains(name)
+        });
+
+        if named && names.len() == tokens.len() {
+            Value::Object(
+                names
+                    .iter()
+                    .zip(tokens)
+                    .map(|(name, token)| (name.to_string(), Self::token_to_json(token)))
+                    .collect(),
+            )
+        } else {
+            Value::Array(tokens.into_iter().map(Self::token_to_json).collect())
+        }
+    }
+
+    /// Converte o retorno de uma função. Com uma única saída retorna o próprio valor; com várias,
+    /// um objeto indexado pelos nomes das saídas (veja `params_to_json`).
+    pub fn outputs_to_json(outputs: &[Param], mut tokens: Vec<Token>) -> Value {
+        if outputs.len() == 1 && tokens.len() == 1 {
+            return Self::token_to_json(tokens.remove(0));
+        }
+
+        let names: Vec<&str> = outputs.iter().map(|output| output.name.as_str()).collect();
+        Self::params_to_json(&names, tokens)
+    }
+
+    /// Decodifica o calldata de uma transação com a função da ABI cujo seletor corresponde aos
+    /// quatro primeiros bytes.
+    pub fn decode_calldata(abi: &Abi, data: &[u8]) -> Result<Value, String> {
+        if data.len() < 4 {
+            return Err("Calldata menor que o seletor da função".to_string());
+        }
+
+        let (selector, arguments) = data.split_at(4);
+        let function = abi
+            .functions()
+            .find(|function| function.short_signature() == selector)
+            .ok_or_else(|| format!("Nenhuma função da ABI com o seletor 0x{}", ethers::utils::hex::encode(selector)))?;
+
+        let tokens = function
+            .decode_input(arguments)
+            .map_err(|e| format!("Erro ao decodificar argumentos de {}: {}", function.name, e))?;
+        let names: Vec<&str> = function.inputs.iter().map(|input| input.name.as_str()).collect();
+        let kinds: Vec<&ParamType> = function.inputs.iter().map(|input| &input.kind).collect();
+
+        Ok(json!({
+            "function": function.name,
+            "signature": Self::signature(&function.name, &kinds),
+            "args": Self::params_to_json(&names, tokens),
+        }))
+    }
+
+    /// Decodifica um log com o evento da ABI cujo hash corresponde ao primeiro tópico. Parâmetros
+    /// indexados de tipos dinâmicos vêm como o hash do valor, como registrado no tópico.
+    pub fn decode_log(abi: &Abi, topics: &[H256], data: &[u8]) -> Result<Value, String> {
+        let topic0 = topics.first().ok_or_else(|| "Log sem tópicos".to_string())?;
+        let event = abi
+            .events()
+            .find(|event| !event.anonymous && event.signature() == *topic0)
+            .ok_or_else(|| format!("Nenhum evento da ABI com o tópico {:?}", topic0))?;
+
+        let log = event
+            .parse_log(RawLog { topics: topics.to_vec(), data: data.to_vec() })
+            .map_err(|e| format!("Erro ao decodificar o evento {}: {}", event.name, e))?;
+        let names: Vec<&str> = log.params.iter().map(|param| param.name.as_str()).collect();
+        let kinds: Vec<&ParamType> = event.inputs.iter().map(|input| &input.kind).collect();
+        let tokens = log.params.iter().map(|param| param.value.clone()).collect();
+
+        Ok(json!({
+            "event": event.name,
+            "signature": Self::signature(&event.name, &kinds),
+            "args": Self::params_to_json(&names, tokens),
+        }))
+    }
+
+    /// Decodifica os dados de um revert em uma descrição legível e um JSON no mesmo formato de
+    /// `decode_calldata`: `Error(string)`, `Panic(uint256)` ou um erro customizado da ABI.
+    /// Retorna `None` para dados vazios ou com seletor desconhecido.
+    pub fn decode_revert(abi: Option<&Abi>, data: &[u8]) -> Option<(String, Value)> {
+        if data.len() < 4 {
+            return None;
+        }
+
+        let (selector, arguments) = data.split_at(4);
+
+        if selector == REVERT_ERROR_SELECTOR {
+            let message = match ethers::abi::decode(&[ParamType::String], arguments).ok()?.pop()? {
+                Token::String(message) => message,
+                _ => return None,
+            };
+            let reason = json!({
+                "error": "Error",
+                "signature": "Error(string)",
+                "args": { "message": message },
+            });
+            return Some((message, reason));
+        }
+
+        if selector == REVERT_PANIC_SELECTOR {
+            let code = match ethers::abi::decode(&[ParamType::Uint(256)], arguments).ok()?.pop()? {
+                Token::Uint(code) => code,
+                _ => return None,
+            };
+            let description = Self::panic_description(code);
+            let reason = json!({
+                "error": "Panic",
+                "signature": "Panic(uint256)",
+                "args": { "code": code.to_string() },
+                "description": description,
+            });
+            return Some((format!("Panic 0x{:02x}: {}", code, description), reason));
+        }
+
+        let error = abi?.errors().find(|error| error.signature()[..4] == *selector)?;
+        let tokens = error.decode(arguments).ok()?;
+        let names: Vec<&str> = error.inputs.iter().map(|input| input.name.as_str()).collect();
+        let kinds: Vec<&ParamType> = error.inputs.iter().map(|input| &input.kind).collect();
+        let signature = Self::signature(&error.name, &kinds);
+
+        let reason = json!({
+            "error": error.name,
+            "signature": signature,
+            "args": Self::params_to_json(&names, tokens),
+        });
+        Some((signature, reason))
+    }
+
+    /// Descrição dos códigos de `Panic(uint256)` emitidos pelo compilador Solidity.
+    fn panic_description(code: U256) -> &'static str {
+        match code.low_u64() {
+            _ if code > U256::from(u64::MAX) => "código desconhecido",
+            0x00 => "erro genérico do compilador",
+            0x01 => "assert falhou",
+            0x11 => "overflow ou underflow aritmético",
+            0x12 => "divisão ou módulo por zero",
+            0x21 => "conversão inválida para enum",
+            0x22 => "array de bytes em storage com codificação inválida",
+            0x31 => "pop em array vazio",
+            0x32 => "acesso fora dos limites do array",
+            0x41 => "memória alocada em excesso",
+            0x51 => "chamada de função interna não inicializada",
+            _ => "código desconhecido",
+        }
+    }
+
+    fn signature(name: &str, kinds: &[&ParamType]) -> String {
+        let kinds: Vec<String> = kinds.iter().map(|kind| kind.to_string()).collect();
+        format!("{}({})", name, kinds.join(","))
+    }
+
+    /// Interpreta o bloco informado em uma requisição: número (JSON ou string decimal/hexadecimal),
+    /// hash de 32 bytes ou uma das tags `latest`, `pending`, `safe`, `finalized` e `earliest`.
+    pub fn parse_block_id(value: &Value) -> Result<BlockId, String> {
+        let text = match value {
+            Value::Number(number) => {
+                return number
+                    .as_u64()
+                    .map(|number| BlockId::Number(BlockNumber::Number(number.into())))
+                    .ok_or_else(|| format!("Número de bloco inválido: {}", number))
+            }
+            Value::String(text) => text.trim(),
+            _ => return Err(format!("Bloco inválido: {}", value)),
+        };
+
+        let tag = match text {
+            "latest" => Some(BlockNumber::Latest),
+            "pending" => Some(BlockNumber::Pending),
+            "safe" => Some(BlockNumber::Safe),
+            "finalized" => Some(BlockNumber::Finalized),
+            "earliest" => Some(BlockNumber::Earliest),
+            _ => None,
+        };
+
+        if let Some(tag) = tag {
+            return Ok(BlockId::Number(tag));
+        }
+
+        match text.strip_prefix("0x") {
+            Some(hex) if hex.len() == 64 => text
+                .parse::<H256>()
+                .map(BlockId::Hash)
+                .map_err(|e| format!("Hash de bloco inválido {}: {}", text, e)),
+            Some(hex) => u64::from_str_radix(hex, 16)
+                .map(|number| BlockId::Number(BlockNumber::Number(number.into())))
+                .map_err(|e| format!("Número de bloco inválido {}: {}", text, e)),
+            None => text
+                .parse::<u64>()
+                .map(|number| BlockId::Number(BlockNumber::Number(number.into())))
+                .map_err(|e| format!("Bloco inválido {}: {}", text, e)),
+        }
+    }
+
+    /// Converte um valor JSON no `Token` do tipo ABI `kind`, para uso como argumento de função.
+    ///
+    /// - `address`: string hexadecimal.
+    /// - `uint<N>`/`int<N>`: número JSON ou string decimal ou hexadecimal (`0x`), com sinal `-`
+    ///   permitido em `int<N>`. O valor precisa caber em `N` bits.
+    /// - `bool`: `true`/`false`, como booleano ou string.
+    /// - `bytes`/`bytes<N>`: string hexadecimal; em `bytes<N>` com exatamente `N` bytes.
+    /// - `T[]`/`T[N]`: array JSON; em `T[N]` com exatamente `N` itens.
+    /// - tuplas: array JSON com um item por componente, na ordem da ABI.
+    pub fn json_to_token(kind: &ParamType, value: &Value) -> Result<Token, String> {
+        match kind {
+            ParamType::Address => {
+                let text = Self::json_str(kind, value)?;
+                text.parse::<Address>()
+                    .map(Token::Address)
+                    .map_err(|e| format!("Endereço inválido {}: {}", text, e))
+            }
+            ParamType::Uint(bits) => {
+                let (negative, magnitude) = Self::json_integer(kind, value)?;
+                if negative {
+                    return Err(format!("{} não aceita valores negativos: {}", kind, value));
+                }
+                if magnitude.bits() > *bits {
+                    return Err(format!("Valor {} não cabe em {}", value, kind));
+                }
+                Ok(Token::Uint(magnitude))
+            }
+            ParamType::Int(bits) => {
+                let (negative, magnitude) = Self::json_integer(kind, value)?;
+                let limit = U256::one() << (bits - 1);
+                if (negative && magnitude > limit) || (!negative && magnitude >= limit) {
+                    return Err(format!("Valor {} não cabe em {}", value, kind));
+                }
+                // `Token::Int` guarda o valor em complemento de dois.
+                let raw = if negative { (!magnitude).overflowing_add(U256::one()).0 } else { magnitude };
+                Ok(Token::Int(raw))
+            }
+            ParamType::Bool => match value {
+                Value::Bool(b) => Ok(Token::Bool(*b)),
+                Value::String(text) if text == "true" || text == "false" => Ok(Token::Bool(text == "true")),
+                _ => Err(format!("Esperado bool, recebido {}", value)),
+            },
+            ParamType::String => Self::json_str(kind, value).map(|text| Token::String(text.to_string())),
+            ParamType::Bytes => Self::json_bytes(kind, value).map(Token::Bytes),
+            ParamType::FixedBytes(size) => {
+                let bytes = Self::json_bytes(kind, value)?;
+                if bytes.len() != *size {
+                    return Err(format!("Esperados {} bytes para {}, recebidos {}", size, kind, bytes.len()));
+                }
+                Ok(Token::FixedBytes(bytes))
+            }
+            ParamType::Array(inner) => Self::json_items(kind, value, None)?
+                .iter()
+                .map(|item| Self::json_to_token(inner, item))
+                .collect::<Result<_, _>>()
+                .map(Token::Array),
+            ParamType::FixedArray(inner, size) => Self::json_items(kind, value, Some(*size))?
+                .iter()
+                .map(|item| Self::json_to_token(inner, item))
+                .collect::<Result<_, _>>()
+                .map(Token::FixedArray),
+            ParamType::Tuple(components) => Self::json_items(kind, value, Some(components.len()))?
+                .iter()
+                .zip(components)
+                .map(|(item, component)| Self::json_to_token(component, item))
+                .collect::<Result<_, _>>()
+                .map(Token::Tuple),
+        }
+    }
+
+    /// Indica se os argumentos JSON têm o formato esperado pelas entradas: `null` para nenhuma
+    /// entrada, um array com a mesma quantidade de itens ou um objeto com exatamente os nomes das
+    /// entradas. Usado para escolher entre sobrecargas de uma função.
+    pub fn params_match(inputs: &[Param], value: &Value) -> bool {
+        match value {
+            Value::Null => inputs.is_empty(),
+            Value::Array(items) => items.len() == inputs.len(),
+            Value::Object(fields) => {
+                fields.len() == inputs.len() && inputs.iter().all(|input| fields.contains_key(&input.name))
+            }
+            _ => false,
+        }
+    }
+
+    /// Inverso de `params_to_json`: converte os argumentos JSON nos tokens das entradas da ABI.
+    /// Aceita um array na ordem da ABI ou um objeto indexado pelos nomes das entradas, como os
+    /// retornados por `params_to_json`. Componentes de tuplas continuam sendo arrays.
+    pub fn params_from_json(inputs: &[Param], value: &Value) -> Result<Vec<Token>, String> {
+        if !Self::params_match(inputs, value) {
+            return Err(format!("Esperados {} argumentos, recebido {}", inputs.len(), value));
+        }
+
+        inputs
+            .iter()
+            .enumerate()
+            .map(|(index, input)| {
+                let item = match value {
+                    Value::Object(fields) => &fields[&input.name],
+                    _ => &value[index],
+                };
+                Self::json_to_token(&input.kind, item).map_err(|e| match input.name.as_str() {
+                    "" => format!("Argumento {}: {}", index, e),
+                    name => format!("Argumento {}: {}", name, e),
+                })
+            })
+            .collect()
+    }
+
+    fn json_str<'a>(kind: &ParamType, value: &'a Value) -> Result<&'a str, String> {
+        value
+            .as_str()
+            .ok_or_else(|| format!("Esperada string para {}, recebido {}", kind, value))
+    }
+
+    fn json_bytes(kind: &ParamType, value: &Value) -> Result<Vec<u8>, String> {
+        let text = Self::json_str(kind, value)?;
+        ethers::utils::hex::decode(text.trim_start_matches("0x"))
+            .map_err(|e| format!("Hexadecimal inválido {}: {}", text, e))
+    }
+
+    fn json_items<'a>(kind: &ParamType, value: &'a Value, size: Option<usize>) -> Result<&'a Vec<Value>, String> {
+        let items = value
+            .as_array()
+            .ok_or_else(|| format!("Esperado array para {}, recebido {}", kind, value))?;
+
+        match size {
+            Some(size) if items.len() != size => {
+                Err(format!("Esperados {} itens para {}, recebidos {}", size, kind, items.len()))
+            }
+            _ => Ok(items),
+        }
+    }
+
+    /// Lê um inteiro JSON (número ou string decimal/hexadecimal) como sinal e magnitude.
+    fn json_integer(kind: &ParamType, value: &Value) -> Result<(bool, U256), String> {
+        let text = match value {
+            Value::Number(number) if number.is_u64() || number.is_i64() => number.to_string(),
+            Value::String(text) => text.trim().to_string(),
+            _ => return Err(format!("Esperado inteiro para {}, recebido {}", kind, value)),
+        };
+
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, text.as_str()),
+        };
+
+        let magnitude = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+            Some(hex) => U256::from_str_radix(hex, 16).map_err(|e| e.to_string()),
+            None => U256::from_dec_str(digits).map_err(|e| e.to_string()),
+        }
+        .map_err(|e| format!("Inteiro inválido {}: {}", text, e))?;
+
+        Ok((negative && !magnitude.is_zero(), magnitude))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn param(name: &str, kind: ParamType) -> Param {
+        Param { name: name.to_string(), kind, internal_type: None }
+    }
+
+    fn round_trip(kind: ParamType, token: Token) {
+        let value = EthersUtils::token_to_json(token.clone());
+        assert_eq!(EthersUtils::json_to_token(&kind, &value), Ok(token), "{} -> {}", kind, value);
+    }
+
+    #[test]
+    fn integers_round_trip() {
+        round_trip(ParamType::Uint(256), Token::Uint(U256::MAX));
+        round_trip(ParamType::Uint(8), Token::Uint(U256::from(255)));
+        round_trip(ParamType::Int(256), Token::Int(I256::MIN.into_raw()));
+        round_trip(ParamType::Int(256), Token::Int(I256::MAX.into_raw()));
+        round_trip(ParamType::Int(32), Token::Int(I256::from(-1).into_raw()));
+        round_trip(ParamType::Int(8), Token::Int(I256::from(-128).into_raw()));
+    }
+
+    #[test]
+    fn integers_out_of_range_are_rejected() {
+        assert!(EthersUtils::json_to_token(&ParamType::Uint(8), &json!("256")).is_err());
+        assert!(EthersUtils::json_to_token(&ParamType::Uint(256), &json!("-1")).is_err());
+        assert!(EthersUtils::json_to_token(&ParamType::Int(8), &json!("128")).is_err());
+        assert!(EthersUtils::json_to_token(&ParamType::Int(8), &json!("-129")).is_err());
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        round_trip(ParamType::FixedBytes(4), Token::FixedBytes(vec![0xde, 0xad, 0xbe, 0xef]));
+        round_trip(ParamType::FixedBytes(32), Token::FixedBytes(vec![0x11; 32]));
+        round_trip(ParamType::Bytes, Token::Bytes(Vec::new()));
+        round_trip(ParamType::Bytes, Token::Bytes(vec![1, 2, 3]));
+
+        assert!(EthersUtils::json_to_token(&ParamType::FixedBytes(4), &json!("0xdead")).is_err());
+    }
+
+    #[test]
+    fn fixed_arrays_and_nested_tuples_round_trip() {
+        round_trip(
+            ParamType::FixedArray(Box::new(ParamType::Address), 2),
+            Token::FixedArray(vec![Token::Address(Address::repeat_byte(1)), Token::Address(Address::zero())]),
+        );
+
+        let kind = ParamType::Tuple(vec![
+            ParamType::Bool,
+            ParamType::String,
+            ParamType::Array(Box::new(ParamType::Tuple(vec![ParamType::Int(64), ParamType::FixedBytes(2)]))),
+        ]);
+        let token = Token::Tuple(vec![
+            Token::Bool(true),
+            Token::String("andromeda".to_string()),
+            Token::Array(vec![
+                Token::Tuple(vec![Token::Int(I256::from(-7).into_raw()), Token::FixedBytes(vec![0xab, 0xcd])]),
+                Token::Tuple(vec![Token::Int(U256::from(7)), Token::FixedBytes(vec![0, 0])]),
+            ]),
+        ]);
+        round_trip(kind, token);
+
+        assert!(EthersUtils::json_to_token(
+            &ParamType::FixedArray(Box::new(ParamType::Bool), 2),
+            &json!([true])
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn named_params_round_trip() {
+        let inputs = vec![
+            param("owner", ParamType::Address),
+            param("amounts", ParamType::FixedArray(Box::new(ParamType::Uint(128)), 2)),
+            param("config", ParamType::Tuple(vec![ParamType::Int(16), ParamType::FixedBytes(1)])),
+        ];
+        let tokens = vec![
+            Token::Address(Address::repeat_byte(0xaa)),
+            Token::FixedArray(vec![Token::Uint(U256::one()), Token::Uint(U256::from(u128::MAX))]),
+            Token::Tuple(vec![Token::Int(I256::from(-300).into_raw()), Token::FixedBytes(vec![0xff])]),
+        ];
+
+        let names: Vec<&str> = inputs.iter().map(|input| input.name.as_str()).collect();
+        let value = EthersUtils::params_to_json(&names, tokens.clone());
+        assert!(value.is_object());
+        assert_eq!(EthersUtils::params_from_json(&inputs, &value), Ok(tokens.clone()));
+
+        // O mesmo argumento em formato posicional.
+        let positional = Value::Array(tokens.iter().cloned().map(EthersUtils::token_to_json).collect());
+        assert_eq!(EthersUtils::params_from_json(&inputs, &positional), Ok(tokens));
+    }
+
+    #[test]
+    fn params_must_match_inputs() {
+        let inputs = vec![param("to", ParamType::Address), param("amount", ParamType::Uint(256))];
+
+        assert!(EthersUtils::params_match(&[], &Value::Null));
+        assert!(!EthersUtils::params_match(&inputs, &Value::Null));
+        assert!(!EthersUtils::params_match(&inputs, &json!([1])));
+        assert!(!EthersUtils::params_match(&inputs, &json!({ "to": "0x0", "value": "1" })));
+        assert!(EthersUtils::params_from_json(&inputs, &json!({ "to": "0x0", "amount": "1" })).is_err());
+    }
+}